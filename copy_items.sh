@@ -3,13 +3,15 @@
 SCRIPT_DIR=$(cd "$(dirname "$0")" >/dev/null 2>&1 && pwd)
 
 SRC_DIR="$SCRIPT_DIR/fgmapeditor/items"
-DST_DIR="$SCRIPT_DIR/fgserver/items"
+DST_DIRS="$SCRIPT_DIR/fgserver/items $SCRIPT_DIR/fgclient/items"
 
-for file in "$SRC_DIR"/*.tres; do
-  if [ -e "$file" ]; then
-    cp "$file" "$DST_DIR/" || {
-      echo "Failed to copy $file" >&2
-      exit 1
-    }
-  fi
+for DST_DIR in $DST_DIRS; do
+  for file in "$SRC_DIR"/*.tres; do
+    if [ -e "$file" ]; then
+      cp "$file" "$DST_DIR/" || {
+        echo "Failed to copy $file" >&2
+        exit 1
+      }
+    fi
+  done
 done
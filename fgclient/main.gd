@@ -56,6 +56,10 @@ func _on_connection_success() -> void:
 	manager.set_context_menu.connect(game_menu.set_context_menu)
 	manager.got_friend_request.connect(game_menu._on_get_friend_request)
 	manager.got_friend_update.connect(game_menu._on_friend_data_update)
+	manager.system_message.connect(game_menu.system_chat.emit)
+	manager.action_progress.connect(game_menu.show_action_progress)
+	manager.dialogue_opened.connect(game_menu.show_dialogue)
+	manager.dialogue_closed.connect(game_menu.hide_dialogue)
 	
 	game_node.add_child(manager)
 	ui_node.add_child(game_menu)
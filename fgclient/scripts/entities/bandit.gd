@@ -0,0 +1,4 @@
+extends GenericEntity
+
+func _ready() -> void:
+	visible_name = "Bandit"
@@ -0,0 +1,11 @@
+extends GenericEntity
+
+@onready var panel: ColorRect = $Panel
+
+func _ready() -> void:
+	visible_name = "Door"
+	interactable_string = "Open or close"
+
+func receive_data(new_data: Dictionary) -> void:
+	super(new_data)
+	panel.modulate.a = 0.3 if data.get("open", false) else 1.
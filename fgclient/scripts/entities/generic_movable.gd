@@ -50,6 +50,22 @@ func move(newpos: Vector2i, newspeed: int) -> void:
 	target = pos * posmult
 	speed = newspeed
 
+## Shows the damage of a hit floating above the player or entity, 0 damage being a miss
+func show_hit(damage: int) -> void:
+	var hit_label := Label.new()
+	hit_label.text = str(damage) if damage > 0 else "Miss"
+	hit_label.modulate = Color.RED if damage > 0 else Color.LIGHT_BLUE
+	hit_label.add_theme_color_override("font_outline_color", Color.BLACK)
+	hit_label.add_theme_constant_override("outline_size", 4)
+	hit_label.z_index = 2
+	hit_label.position = Vector2(posmult / 2. - 8., 0.)
+	add_child(hit_label)
+	var tween := create_tween()
+	tween.set_parallel()
+	tween.tween_property(hit_label, "position:y", -30., 0.8)
+	tween.tween_property(hit_label, "modulate:a", 0., 0.8)
+	tween.chain().tween_callback(hit_label.queue_free)
+
 func set_direction(dir: Dir) -> void:
 	direction = dir
 
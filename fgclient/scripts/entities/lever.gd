@@ -0,0 +1,11 @@
+extends GenericEntity
+
+@onready var handle: ColorRect = $Handle
+
+func _ready() -> void:
+	visible_name = "Lever"
+	interactable_string = "Pull"
+
+func receive_data(new_data: Dictionary) -> void:
+	super(new_data)
+	handle.rotation_degrees = 30. if data.get("pulled", false) else -30.
@@ -18,6 +18,17 @@ signal your_data_updated(data: PlayerContainer)
 signal set_context_menu(menu: PopupPanel)
 signal got_friend_request(pid: int, uname: String)
 signal got_friend_update(uname: String, server: String)
+signal bank_opened(items: Array[ItemResource])
+signal bank_closed
+signal player_leveled_up(pid: int, skill: String, old_level: int, new_level: int)
+signal system_message(text: String)
+## ticks_total of 0 means the action was cancelled
+signal action_progress(ticks_done: int, ticks_total: int)
+## damage of 0 means the attack missed, ids of -1 mean the other one of the pid and entity id is used
+signal combat_hit(attacker_pid: int, attacker_entity_id: int, target_pid: int, target_entity_id: int, damage: int, hp: int, max_hp: int)
+signal player_died(pid: int)
+signal dialogue_opened(entity_id: int, text: String, options: PackedStringArray)
+signal dialogue_closed
 
 func _ready() -> void:
 	ServerNode.player_update.connect(_on_player_update)
@@ -139,11 +150,50 @@ func _on_generic_response(response: GenericResponse) -> void:
 		GenericResponse.RESPONSE_DATA_UPDATE:
 			var resp = response.as_data_update()
 			handle_data_version(resp["data_version"], resp["pid"])
+		GenericResponse.RESPONSE_BANK_CONTENTS:
+			bank_opened.emit(response.as_bank_contents())
+		GenericResponse.RESPONSE_BANK_CLOSED:
+			bank_closed.emit()
+		GenericResponse.RESPONSE_LEVEL_UP:
+			var resp = response.as_level_up()
+			player_leveled_up.emit(resp["pid"], resp["skill"], resp["old_level"], resp["new_level"])
+			if resp["pid"] == player_pid:
+				var skill_name := SkillRegistry.get_display_name(resp["skill"])
+				system_message.emit("Your %s level is now %s." % [skill_name, resp["new_level"]])
+		GenericResponse.RESPONSE_ACTION_PROGRESS:
+			var resp = response.as_action_progress()
+			action_progress.emit(resp["ticks_done"], resp["ticks_total"])
+		GenericResponse.RESPONSE_HIT:
+			var resp = response.as_hit()
+			combat_hit.emit(
+				resp["attacker_pid"], resp["attacker_entity_id"],
+				resp["target_pid"], resp["target_entity_id"],
+				resp["damage"], resp["hp"], resp["max_hp"]
+			)
+			if players.has(resp["target_pid"]):
+				players[resp["target_pid"]].show_hit(resp["damage"])
+			elif entities.has(resp["target_entity_id"]):
+				entities[resp["target_entity_id"]].show_hit(resp["damage"])
+		GenericResponse.RESPONSE_DEATH:
+			var pid := response.as_death()
+			player_died.emit(pid)
+			if pid == player_pid:
+				system_message.emit("You died.")
+		GenericResponse.RESPONSE_SET_COLLISION:
+			var resp = response.as_set_collision()
+			if map != null:
+				map.set_at(resp["x"], resp["y"], resp["blocked"])
+		GenericResponse.RESPONSE_DIALOGUE:
+			var resp = response.as_dialogue()
+			dialogue_opened.emit(resp["entity_id"], resp["text"], resp["options"])
+		GenericResponse.RESPONSE_DIALOGUE_CLOSED:
+			dialogue_closed.emit()
 
 func load_map(mapname: String):
 	if map != null: map.queue_free()
 	
 	print("Loading map: ", mapname)
+	dialogue_closed.emit()
 	for pid: int in players.keys():
 		if pid != player_pid:
 			despawn_player(pid)
@@ -220,6 +270,8 @@ func open_context_at(pos: Vector2i) -> void:
 	var context_menu: ContextPopup = context_menu_scene.instantiate()
 	context_menu.walk_to_pos.connect(player_controller.go_to_pos)
 	context_menu.interact_with_entity.connect(interact_with_entity)
+	context_menu.attack_player.connect(attack_player)
+	context_menu.attack_entity.connect(attack_entity)
 	context_menu.entities = entities_at
 	context_menu.players = players_at
 	context_menu.related_pos = pos
@@ -235,6 +287,13 @@ func interact_with_entity(entity: GenericEntity) -> void:
 	
 	ServerNode.send_event(event)
 
+func attack_player(pid: int) -> void:
+	ServerNode.send_event(GenericEvent.attack(pid))
+
+func attack_entity(entity: GenericEntity) -> void:
+	var entity_pos := entity.pos
+	ServerNode.send_event(GenericEvent.attack_entity(entity_pos.x, entity_pos.y, entity.entity_id))
+
 func get_players_at(pos: Vector2i) -> Array[PlayerEntity]:
 	var at_list: Array[PlayerEntity] = []
 	for player: PlayerEntity in players.values():
@@ -111,6 +111,11 @@ func _on_chat_send(text: String) -> void:
 	text = text.strip_edges()
 	if text.is_empty():
 		return
+	# "/channel" shows the current channel, "/channel 2" switches to channel 2
+	if text == "/channel" or text.begins_with("/channel "):
+		var channel := text.trim_prefix("/channel").strip_edges()
+		ServerNode.send_event(GenericEvent.switch_channel(channel.to_int() if channel.is_valid_int() else 0))
+		return
 	if is_dming:
 		ServerNode.send_dm(text, dm_target)
 		push_chat_message(text, "To " + dm_username, dm_target, true)
@@ -6,6 +6,8 @@ extends Panel
 signal inspect_player(data: PlayerContainer, show_invite_btn: bool, show_dm_button: bool)
 signal interact_with_entity(entity_id: int)
 signal walk_to_pos(pos: Vector2i)
+signal attack_player(pid: int)
+signal attack_entity(entity: GenericEntity)
 
 var related_pos: Vector2i
 var players: Array[PlayerEntity]
@@ -18,7 +20,11 @@ func _ready() -> void:
 	
 	for player in players:
 		add_inspect_option(player.data)
+		if player.data.get_pid() != player_pid:
+			add_attack_option(player.data)
 	for entity in entities:
+		if entity.data.get("attackable", false):
+			add_attack_entity_option(entity)
 		if entity.interactable:
 			add_interact_option(entity)
 	var walk_button = Button.new()
@@ -40,6 +46,20 @@ func add_inspect_option(data: PlayerContainer) -> void:
 	new_button.pressed.connect(self.queue_free)
 	vbox.add_child(new_button)
 
+func add_attack_option(data: PlayerContainer) -> void:
+	var new_button = Button.new()
+	new_button.text = "Attack player \"%s\"" % data.get_name()
+	new_button.pressed.connect(attack_player.emit.bind(data.get_pid()))
+	new_button.pressed.connect(self.queue_free)
+	vbox.add_child(new_button)
+
+func add_attack_entity_option(entity: GenericEntity) -> void:
+	var new_button = Button.new()
+	new_button.text = "Attack " + entity.visible_name
+	new_button.pressed.connect(attack_entity.emit.bind(entity))
+	new_button.pressed.connect(self.queue_free)
+	vbox.add_child(new_button)
+
 func add_interact_option(entity: GenericEntity) -> void:
 	var interact_string: String
 	if not entity.interactable_string.is_empty():
@@ -7,6 +7,11 @@ extends Control
 @onready var big_menu: Control = get_node("BigMenu")
 
 var context_menu: ContextPopup = null
+var action_bar: ProgressBar = null
+var action_tween: Tween = null
+var dialogue_panel: PanelContainer = null
+var dialogue_text: Label = null
+var dialogue_options: VBoxContainer = null
 
 signal system_chat(text: String)
 signal disconnect_pressed
@@ -27,6 +32,29 @@ func _ready() -> void:
 	player_details.set_dm_target.connect(chat.set_dm_target)
 	
 	$BigMenu/VBox/DisconnectButton.pressed.connect(ServerNode.disconnect_from_server)
+	
+	action_bar = ProgressBar.new()
+	action_bar.show_percentage = false
+	action_bar.custom_minimum_size = Vector2(200., 12.)
+	action_bar.set_anchors_and_offsets_preset(Control.PRESET_CENTER_BOTTOM)
+	action_bar.position.y -= 80.
+	action_bar.mouse_filter = Control.MOUSE_FILTER_IGNORE
+	action_bar.visible = false
+	add_child(action_bar)
+	
+	dialogue_panel = PanelContainer.new()
+	dialogue_panel.custom_minimum_size = Vector2(400., 0.)
+	dialogue_panel.set_anchors_and_offsets_preset(Control.PRESET_CENTER_BOTTOM)
+	dialogue_panel.position.y -= 220.
+	dialogue_panel.visible = false
+	var dialogue_vbox := VBoxContainer.new()
+	dialogue_text = Label.new()
+	dialogue_text.autowrap_mode = TextServer.AUTOWRAP_WORD_SMART
+	dialogue_options = VBoxContainer.new()
+	dialogue_vbox.add_child(dialogue_text)
+	dialogue_vbox.add_child(dialogue_options)
+	dialogue_panel.add_child(dialogue_vbox)
+	add_child(dialogue_panel)
 
 func _process(delta: float) -> void:
 	if Input.is_action_just_pressed("ChatOpen"):
@@ -40,6 +68,9 @@ func _unhandled_input(event: InputEvent) -> void:
 	elif event.is_action_pressed("Close"):
 		if context_menu != null:
 			context_menu.queue_free()
+		elif dialogue_panel.visible:
+			ServerNode.send_event(GenericEvent.close_dialogue())
+			hide_dialogue()
 		elif player_details.close_all():
 			big_menu.visible = not big_menu.visible
 
@@ -52,6 +83,37 @@ func _on_get_friend_request(pid: int, uname: String) -> void:
 func _on_got_chat(text: String, from: String, from_pid: int, is_dm: bool) -> void:
 	chat._on_got_chat(text, from, from_pid, is_dm)
 
+## Fills the action bar over the remaining ticks, hides it once the action ends
+func show_action_progress(ticks_done: int, ticks_total: int) -> void:
+	if action_tween != null:
+		action_tween.kill()
+		action_tween = null
+	if ticks_done >= ticks_total:
+		action_bar.visible = false
+		return
+	
+	action_bar.max_value = ticks_total
+	action_bar.value = ticks_done
+	action_bar.visible = true
+	action_tween = create_tween()
+	action_tween.tween_property(action_bar, "value", ticks_total, (ticks_total - ticks_done) * Ticker.wait_time)
+
+## Shows the dialogue node with a button per option, the server validates the choice and answers with the next node or closing
+func show_dialogue(_entity_id: int, text: String, options: PackedStringArray) -> void:
+	dialogue_text.text = text
+	for child in dialogue_options.get_children():
+		child.queue_free()
+	for i in options.size():
+		var button := Button.new()
+		button.text = options[i]
+		button.alignment = HORIZONTAL_ALIGNMENT_LEFT
+		button.pressed.connect(func(): ServerNode.send_event(GenericEvent.choose_dialogue_option(i)))
+		dialogue_options.add_child(button)
+	dialogue_panel.visible = true
+
+func hide_dialogue() -> void:
+	dialogue_panel.visible = false
+
 func update_inventory(data: PlayerContainer) -> void:
 	inventory.populate(data)
 
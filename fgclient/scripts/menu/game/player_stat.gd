@@ -7,17 +7,19 @@ extends Control
 
 var skill: String
 
-func set_skill(skill: String) -> void:
+func set_skill(new_skill: String) -> void:
+	skill = new_skill
 	var texture: Texture2D = load("res://graphics/icons/%s.png" % skill)
 	skill_icon.texture = texture
 	
-	top_label.text = skill.capitalize()
+	top_label.text = SkillRegistry.get_display_name(skill)
 
 func set_stats(level: int, xp: int) -> void:
-	bottom_label.text = "%s/100" % level
+	var max_level := SkillRegistry.get_max_level(skill)
+	bottom_label.text = "%s/%s" % [level, max_level]
 	if xp != 0:
-		if level < 100:
-			var xp_required := level * level * 100
+		if level < max_level:
+			var xp_required := SkillRegistry.get_xp_for_level(skill, level + 1)
 			vbox.tooltip_text = "Xp: %s / %s" % [xp, xp_required]
 		else:
-			vbox.tooltip_text = "Xp: 980100 / 980100"
+			vbox.tooltip_text = "Xp: %s" % xp
@@ -17,6 +17,12 @@ signal connection_success
 signal connection_failure(err: String)
 
 func _ready() -> void:
+	# Loaded here since this node is ready before any of the game scenes
+	ItemRegistry.load_directory("res://items")
+	SkillRegistry.load_file("res://skills.cfg")
+	RecipeRegistry.load_directory("res://recipes")
+	QuestRegistry.load_directory("res://quests")
+	
 	# If done using @rpc instead of this way, doesn't work with godot-rust configs for some reason
 	rpc_config("pmove", pmove_config)
 	rpc_config("pdata", pdata_config)
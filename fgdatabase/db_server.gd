@@ -12,6 +12,11 @@ const unlock_absolutely_all_query: String = "UPDATE pdata SET lock_id = null"
 
 const index_query: String = "CREATE INDEX idx_pid ON pdata(pid);"
 
+## Persistent entity states, one row per instance
+const edata_table_query: String = "CREATE TABLE IF NOT EXISTS edata (key TEXT PRIMARY KEY NOT NULL, data BLOB NOT NULL);"
+const edata_save_query: String = "INSERT OR REPLACE INTO edata (key, data) VALUES (?, ?)"
+const edata_prefix_query: String = "SELECT key, data FROM edata WHERE substr(key, 1, ?) = ?"
+
 func _ready() -> void:
 	var config := ConfigFile.new()
 	config.load("res://db.cfg")
@@ -54,6 +59,9 @@ func create_or_open_db() -> void:
 			print("Failed to create db table somehow: ", db.error_message)
 		if not db.query(index_query):
 			print("Failed to create pid index: ", db.error_message)
+	# Also for databases created before entity states were persisted
+	if not db.query(edata_table_query):
+		print("Failed to create edata table: ", db.error_message)
 
 func create_new_player(pid: int, username: String) -> void:
 	db.insert_row("pdata", {
@@ -123,6 +131,20 @@ func _retrieve(pid: int, lock: bool = false) -> void:
 	
 	rpc_id(server_id, "_retrieve", pid, data)
 
+@rpc("any_peer", "call_remote", "reliable", 0)
+func _save_entity_states(key: String, data: PackedByteArray) -> void:
+	print("Saving entity states for ", key)
+	db.query_with_bindings(edata_save_query, [key, data])
+
+@rpc("any_peer", "call_remote", "reliable", 0)
+func _retrieve_entity_states(prefix: String) -> void:
+	var server_id := multiplayer.get_remote_sender_id()
+	db.query_with_bindings(edata_prefix_query, [prefix.length(), prefix])
+	var states := {}
+	for row: Dictionary in db.query_result_by_reference:
+		states[row["key"]] = row["data"]
+	rpc_id(server_id, "_retrieve_entity_states", states)
+
 @rpc("any_peer", "call_remote", "reliable", 1)
 func _relay_dm(text: String, from: String, from_pid: int, target_pid: int) -> void:
 	db.query_with_bindings(check_lock_query, [target_pid])
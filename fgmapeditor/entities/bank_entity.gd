@@ -0,0 +1,11 @@
+class_name BankEntity
+
+extends GenericScriptedEntity
+
+func _ready() -> void:
+	interactable = true
+	related_scene = "chest"
+	public_data["open"] = false
+
+func _on_player_interaction(player: PlayerContainer, net_id: int) -> Array[ScriptResponse]:
+	return [ScriptResponse.open_bank(net_id)]
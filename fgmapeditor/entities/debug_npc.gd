@@ -7,28 +7,61 @@ const greetings: Array[String] = [
 	"Howdy!",
 	"What's up!"
 ]
+var dialogue: DialogueResource = preload("res://dialogue/debug_npc.tres")
 @export var max_home_distance: int = 2
 var home: Vector2i
-var ticks_till_next_move = 20
 
 func _ready() -> void:
 	home = pos
 	interactable = true
 	related_scene = "debug_npc"
+	proximity_radius = 1
+	tags = PackedStringArray(["debug_npc"])
 
-func _process(delta: float) -> void:
-	ticks_till_next_move -= 1
-	if ticks_till_next_move <= 0:
-		ticks_till_next_move = randi_range(15, 60)
-		var xdelta := randi_range(-1, 1)
-		var ydelta := randi_range(-1, 1)
-		var newx = pos.x + xdelta
-		var newy = pos.y + ydelta
-		if abs(newx - home.x) > max_home_distance:
-			newx = pos.x
-		if abs(newy - home.y) > max_home_distance:
-			newy = pos.y
-		emit_response(ScriptResponse.move_self(newx, newy, 2))
+func _on_registered() -> Array[ScriptResponse]:
+	return [ScriptResponse.start_timer("move", 20, false, true)]
 
-func _on_player_interaction(player: PlayerContainer, net_id: int) -> Array[ScriptResponse]:
+func _on_timer(name: String) -> Array[ScriptResponse]:
+	var xdelta := randi_range(-1, 1)
+	var ydelta := randi_range(-1, 1)
+	var newx = pos.x + xdelta
+	var newy = pos.y + ydelta
+	if abs(newx - home.x) > max_home_distance:
+		newx = pos.x
+	if abs(newy - home.y) > max_home_distance:
+		newy = pos.y
+	return [
+		ScriptResponse.move_self(newx, newy, 2),
+		ScriptResponse.start_timer("move", randi_range(15, 60), false, true)
+	]
+
+## Trying again soon instead of standing still, usually having walked into a wall
+func _on_move_rejected(x: int, y: int, reason: String) -> Array[ScriptResponse]:
+	return [ScriptResponse.start_timer("move", 5, false, true)]
+
+func _on_player_enter_range(player: PlayerContainer, net_id: int) -> Array[ScriptResponse]:
+	if player.get_flag("met_debug_npc"):
+		return [ScriptResponse.chat_message("Welcome back!", net_id)]
 	return [ScriptResponse.chat_message(greetings.pick_random(), net_id)]
+
+func _on_player_interaction(player: PlayerContainer, net_id: int) -> Array[ScriptResponse]:
+	return [
+		ScriptResponse.set_player_flag("met_debug_npc", true, net_id),
+		ScriptResponse.open_dialogue(dialogue, net_id)
+	]
+
+func _on_dialogue_choice(player: PlayerContainer, action: String, net_id: int) -> Array[ScriptResponse]:
+	if action == "tip":
+		return [ScriptResponse.change_gold(-1, net_id)]
+	if action == "start_firewood":
+		return [ScriptResponse.start_quest("firewood", net_id)]
+	return []
+
+func _on_quest_completed(player: PlayerContainer, quest_id: String, net_id: int) -> Array[ScriptResponse]:
+	if quest_id != "firewood":
+		return []
+	return [
+		ScriptResponse.take_item("oak_wood", 3, net_id),
+		ScriptResponse.change_gold(10, net_id),
+		ScriptResponse.chat_message("Thanks for the firewood! Here's a little something.", net_id)
+	]
@@ -2,10 +2,9 @@ class_name FairyEntity
 
 extends GenericScriptedEntity
 
-var fairy_item: ItemResource = preload("res://items/fairy.tres")
+var fairy_loot: LootTableResource = preload("res://loot/fairy.tres")
 @export var max_home_distance: int = 2
 var home: Vector2i
-var ticks_till_next_move = 20
 
 func _ready() -> void:
 	home = pos
@@ -13,21 +12,24 @@ func _ready() -> void:
 	related_scene = "fairy"
 	print("Fairy spawned")
 
-func _process(delta: float) -> void:
-	ticks_till_next_move -= 1
-	if ticks_till_next_move <= 0:
-		print("Fairy movement tick!")
-		ticks_till_next_move = randi_range(15, 40)
-		var xdelta := randi_range(-1, 1)
-		var ydelta := randi_range(-1, 1)
-		var newx = pos.x + xdelta
-		var newy = pos.y + ydelta
-		if abs(newx - home.x) > max_home_distance:
-			newx = pos.x
-		if abs(newy - home.y) > max_home_distance:
-			newy = pos.y
-		emit_response(ScriptResponse.move_self(newx, newy, 1))
+func _on_registered() -> Array[ScriptResponse]:
+	return [ScriptResponse.start_timer("move", 20, false, true)]
+
+func _on_timer(name: String) -> Array[ScriptResponse]:
+	print("Fairy movement tick!")
+	var xdelta := randi_range(-1, 1)
+	var ydelta := randi_range(-1, 1)
+	var newx = pos.x + xdelta
+	var newy = pos.y + ydelta
+	if abs(newx - home.x) > max_home_distance:
+		newx = pos.x
+	if abs(newy - home.y) > max_home_distance:
+		newy = pos.y
+	return [
+		ScriptResponse.move_self(newx, newy, 1),
+		ScriptResponse.start_timer("move", randi_range(15, 40), false, true)
+	]
 
 func _on_player_interaction(player: PlayerContainer, net_id: int) -> Array[ScriptResponse]:
 	print("Fairy interacted!!!!!!")
-	return [ScriptResponse.give_item(fairy_item, net_id), ScriptResponse.despawn_self()]
+	return [ScriptResponse.give_loot(fairy_loot, net_id), ScriptResponse.despawn_self()]
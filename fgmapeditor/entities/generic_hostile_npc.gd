@@ -0,0 +1,18 @@
+class_name GenericHostileNpc
+
+extends GenericScriptedEntity
+
+## Name of the client scene to show
+@export var scene: String = "bandit"
+## Rolled for the player who lands the killing blow, leave empty for no loot
+@export var kill_loot: LootTableResource
+
+func _ready() -> void:
+	related_scene = scene
+	if npc == null:
+		npc = NpcBehavior.new()
+
+func _on_npc_death(player: PlayerContainer, net_id: int) -> Array[ScriptResponse]:
+	if kill_loot == null:
+		return []
+	return [ScriptResponse.give_loot(kill_loot, net_id)]
@@ -3,10 +3,19 @@ extends GenericScriptedEntity
 
 @export var mapname: String
 @export var to_where: Vector2i
+## Sends players to a private instance of the map instead of a shared one, like a dungeon
+@export var private: bool = false
+## If private, the player's friends can join the instance the player opened and the other way around
+@export var friends_join: bool = true
 
 func _ready() -> void:
 	walkable = true
 	related_scene = "portal"
 
 func _on_player_walk(player: PlayerContainer, net_id: int) -> Array[ScriptResponse]:
+	if private:
+		var members := PackedInt32Array()
+		if friends_join:
+			members.append_array(player.get_friends())
+		return [ScriptResponse.move_player_to_private_map(mapname, to_where.x, to_where.y, members, net_id)]
 	return [ScriptResponse.move_player_to_map(mapname, to_where.x, to_where.y, net_id)]
@@ -12,6 +12,11 @@ func _notification(what: int) -> void:
 		var entities: Node = this_scene.get_node("Entities")
 		this_scene.remove_child(entities)
 		this_scene.set_script(null)
+		# The server only gets the entities, so map properties it needs are stored as their metadata
+		entities.set_meta("respawn_point", this_scene.respawn_point)
+		entities.set_meta("capacity", this_scene.capacity)
+		entities.set_meta("idle_timeout", this_scene.idle_timeout)
+		entities.set_meta("private_lifetime", this_scene.private_lifetime)
 		
 		# Needed so that PackedScene.pack() saves it correctly
 		# Otherwise, the BaseMap node is still the owner
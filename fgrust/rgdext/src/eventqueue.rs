@@ -61,8 +61,7 @@ impl EQueue {
 
     pub fn iter_game(&self) -> std::vec::IntoIter<GameEvent> {
         let vec = std::mem::take(&mut *self.game_events.borrow_mut());
-        let iter = vec.into_iter();
-        iter
+        vec.into_iter()
     }
 
     // pub fn to_string(&self) -> String {
@@ -92,6 +91,8 @@ pub enum GameEvent {
     /// 
     /// You might be asking yourself, could this possibly not be an event? No, because it happens at Instance level, but needs to be handled at GameManager level
     PlayerJoinInstance{mapname: String, x: i32, y: i32, net_id: i32},
+    /// Joins player to the private instance of the map they're a member of, opening one for the members if there's none
+    PlayerJoinPrivateInstance{mapname: String, x: i32, y: i32, members: Vec<i32>, net_id: i32},
     PlayerChat{text: GString, target_pid: i32, net_id: i32},
     PlayerDm{text: GString, from: GString, from_pid: i32, target_pid: i32},
     GenericEvent{event: GenericPlayerEvent, net_id: i32},
@@ -0,0 +1,95 @@
+use godot::prelude::*;
+use crate::eventqueue::{EQueue, ServerEvent};
+use rgdext_shared::{genericevent::GenericServerResponse, playerdata::{dialogue::Dialogue, playercontainer::PlayerContainer, requirements}};
+
+use super::{entity::GenericScriptedEntity, player::{OpenDialogue, Player}, Instance};
+
+impl Instance {
+    pub(super) fn open_dialogue(&mut self, entity: Gd<GenericScriptedEntity>, dialogue: Dialogue, net_id: i32) {
+        let Some(player) = self.players.get(&net_id) else {return};
+        let node = dialogue.start.clone();
+        player.borrow_mut().dialogue = Some(OpenDialogue{entity, dialogue, node, shown: Vec::new()});
+        self.show_dialogue_node(net_id);
+    }
+
+    /// Sends the player the current node of their dialogue, with the options they meet the requirements of
+    fn show_dialogue_node(&mut self, net_id: i32) {
+        let Some(player) = self.players.get(&net_id) else {return};
+        let mut p = player.borrow_mut();
+        let p = &mut *p;
+        let Some(open) = &mut p.dialogue else {return};
+        // The entity may have despawned while the dialogue was open
+        let entity_valid = open.entity.is_instance_valid() && !open.entity.is_queued_for_deletion();
+
+        let response = match open.dialogue.node(&open.node) {
+            Some(node) if entity_valid => {
+                open.shown = node.available_options(&p.data);
+                GenericServerResponse::Dialogue{
+                    entity_id: open.entity.bind().entity_id,
+                    text: node.text.clone(),
+                    options: open.shown.iter().map(|&i| node.options[i].text.clone()).collect(),
+                }
+            },
+            Some(_) => {
+                p.dialogue = None;
+                GenericServerResponse::DialogueClosed
+            },
+            None => {
+                godot_error!("Dialogue opened by entity {} has no node {}, closing it", open.entity.get_name(), open.node);
+                p.dialogue = None;
+                GenericServerResponse::DialogueClosed
+            },
+        };
+        self.equeue.push_server(ServerEvent::GenericResponse{response: response.to_bytearray(), net_id});
+    }
+
+    /// index is the position of the option among the ones shown to the player
+    pub(super) fn handle_dialogue_choice(&mut self, index: usize, net_id: i32) {
+        let Some(player) = self.players.get(&net_id).cloned() else {return};
+        let mut pb = player.borrow_mut();
+        let p = &mut *pb;
+        let Some(open) = &mut p.dialogue else {return};
+        let Some(node) = open.dialogue.node(&open.node) else {return};
+        let Some(option) = open.shown.get(index).map(|&i| node.options[i].clone()) else {return};
+
+        // The player may have lost items or gold since the options were shown
+        if let Some(unmet) = requirements::first_unmet(&option.requirements, &p.data) {
+            self.equeue.push_server(
+                ServerEvent::PlayerChat{text: unmet.failure_message().into(), from: "".into(), from_pid: -1, is_dm: false, net_id}
+            );
+            drop(pb);
+            self.show_dialogue_node(net_id);
+            return;
+        }
+
+        let entity = open.entity.clone();
+        if option.next.is_empty() {
+            Self::close_dialogue(&self.equeue, p, net_id);
+        }
+        else {
+            open.node = option.next.clone();
+        }
+        let container = PlayerContainer::from_data(p.data.clone());
+        drop(pb);
+
+        if !option.next.is_empty() {
+            self.show_dialogue_node(net_id);
+        }
+        if !option.action.is_empty() && entity.is_instance_valid() && !entity.is_queued_for_deletion() {
+            let responses = GenericScriptedEntity::on_dialogue_choice(entity.clone(), container, option.action.into(), net_id);
+            for response in responses.iter_shared() {
+                self.handle_entity_response(entity.clone(), response);
+            }
+        }
+    }
+
+    /// Closes the player's dialogue if they have one open, notifying them
+    pub(super) fn close_dialogue(equeue: &EQueue, p: &mut Player, net_id: i32) {
+        if p.dialogue.take().is_some() {
+            equeue.push_server(ServerEvent::GenericResponse{
+                response: GenericServerResponse::DialogueClosed.to_bytearray(),
+                net_id
+            });
+        }
+    }
+}
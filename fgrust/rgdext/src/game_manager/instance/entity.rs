@@ -1,7 +1,18 @@
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::{HashMap, HashSet}, rc::Rc};
 
-use godot::prelude::*;
-use rgdext_shared::{basemap::spatialhash::SpatialHash, playerdata::{item::ItemResource, playercontainer::PlayerContainer}};
+use godot::{classes::Script, prelude::*};
+use rgdext_shared::{basemap::spatialhash::{SpatialHash, CHECK_RADIUS, GRID_SIZE}, playerdata::{dialogue::DialogueResource, item::ItemResource, loot::LootTableResource, playercontainer::PlayerContainer, requirements::Requirement}};
+
+use super::npc::{NpcBehavior, NpcState};
+
+/// Players further away than this are outside the spatial hash adjacency of the entity, so proximity radii get capped to it
+pub const MAX_PROXIMITY_RADIUS: i32 = GRID_SIZE * CHECK_RADIUS;
+/// Slowest move speed scripts can give, the same as the slowest player move
+pub const MAX_MOVE_SPEED: i32 = 3;
+
+/// entity_id -> entity of every registered entity of an instance. Shared with the entities so that scripts
+/// can query each other while the instance is busy calling their hooks.
+pub type EntityDirectory = Rc<RefCell<HashMap<i32, Gd<GenericScriptedEntity>>>>;
 
 /// Only overwite on_* virtual methods.
 /// 
@@ -26,10 +37,53 @@ pub struct GenericScriptedEntity {
     /// Walked on response is based on the [method _on_player_walk] method.
     pub walkable: bool,
     #[var]
+    /// Whether players and other entities are kept from moving onto the entity's tile. Should be set in _ready(),
+    /// as it's only read when the entity registers.
+    pub blocks_movement: bool,
+    #[var]
+    /// Max Chebyshev distance at which players are in range of the entity, for [method _on_player_enter_range]
+    /// and [method _on_player_leave_range]. Negative disables range checks, zero means just the position of the entity.
+    /// Capped to 24 tiles. Should be set in _ready().
+    pub proximity_radius: i32,
+    /// net_ids of the players in proximity range as of the last tick
+    pub players_in_range: HashSet<i32>,
+    #[var]
     /// Whether the entity has a client scene that should be shown to the clients. Should be set in _ready().
     /// 
     /// Set to the name of the scene minus .tscn suffix. Leave empty to leave the entity invisible to clients.
     related_scene: GString,
+    #[var]
+    /// Crafting station type this entity provides, recipes with the same station can be crafted
+    /// within interactable_distance of it. Leave empty if it's not a station. Should be set in _ready().
+    pub station: GString,
+
+    #[export]
+    /// Skill name -> minimum level (including equipment bonuses) needed to interact with the entity.
+    required_skills: Dictionary,
+    #[export]
+    /// id_string of an item that has to be equipped to interact with the entity, leave empty for none.
+    required_equipped: GString,
+    #[export]
+    /// Item id_string -> count of items the player has to have to interact with the entity.
+    required_items: Dictionary,
+    #[export]
+    /// Gold the player has to have to interact with the entity. Isn't taken away.
+    required_gold: i32,
+
+    #[export]
+    /// Tags other entities can find this entity by with [method get_entities_with_tag],
+    /// or send it messages by with [method ScriptResponse.send_message_to_tag].
+    pub tags: PackedStringArray,
+    /// Set on registration
+    directory: Option<EntityDirectory>,
+
+    #[export]
+    /// Makes the entity a hostile NPC that players can attack, moved and fought with server-side.
+    /// Should be set in _ready() at the latest, position at registration being its home.
+    /// 
+    /// The "attackable" public value gets set to true for the client.
+    pub npc: Option<Gd<NpcBehavior>>,
+    pub npc_state: Option<NpcState>,
 
     #[export]
     /// Custom entity data that gets synchronised between client and server.
@@ -38,6 +92,13 @@ pub struct GenericScriptedEntity {
     /// Change either using [method set_public_value], or by calling [method increment_data_version]
     /// to update the data version for client synchronization.
     public_data: Dictionary,
+    #[var]
+    /// Saved when the instance unloads and on the periodic saves of the players in it, keyed by server, map, channel and
+    /// the entity's node path under the map's Entities node, and restored before _ready() the next time that instance loads.
+    /// Fill it in [method on_unload] at the latest, keeping it up to date as well if losing the latest changes on a crash matters.
+    /// 
+    /// Only entities placed in the map get restored, and only in public instances.
+    pub persistent_state: Dictionary,
     pub public_data_version: i32,
     pub data_just_updated: bool,
     pub last_speed: i32,
@@ -55,8 +116,21 @@ impl INode for GenericScriptedEntity {
             interactable: false,
             interactable_distance: 1,
             walkable: false,
+            blocks_movement: false,
+            proximity_radius: -1,
+            players_in_range: HashSet::new(),
             related_scene: "".to_godot(),
+            station: "".into(),
+            required_skills: Dictionary::new(),
+            required_equipped: "".into(),
+            required_items: Dictionary::new(),
+            required_gold: 0,
+            tags: PackedStringArray::new(),
+            directory: None,
+            npc: None,
+            npc_state: None,
             public_data: Dictionary::new(),
+            persistent_state: Dictionary::new(),
             public_data_version: 0,
             data_just_updated: false,
             last_speed: 0,
@@ -69,6 +143,8 @@ impl INode for GenericScriptedEntity {
 }
 
 #[godot_api]
+// The arguments of the virtual hooks are only used by the scripts overriding them
+#[allow(unused_variables)]
 impl GenericScriptedEntity {
     #[signal]
     fn entity_response(object: Gd<GenericScriptedEntity>, response: Gd<ScriptResponse>);
@@ -97,6 +173,20 @@ impl GenericScriptedEntity {
         self.data_just_updated = true;
     }
 
+    #[func(gd_self, virtual)]
+    /// Called once the entity has been registered to the instance, after _ready(). Responses emitted
+    /// with [method emit_response] before this point are lost, so timers should be started from here.
+    pub fn on_registered(this: Gd<Self>) -> Array<Gd<ScriptResponse>> {
+        Array::from(&[ScriptResponse::null_response()])
+    }
+
+    #[func(gd_self, virtual)]
+    /// Called when a timer started with [method ScriptResponse.start_timer] fires, after players have moved
+    /// and their actions have ticked. The responses get handled at the start of the next tick.
+    pub fn on_timer(this: Gd<Self>, name: GString) -> Array<Gd<ScriptResponse>> {
+        Array::from(&[ScriptResponse::null_response()])
+    }
+
     #[func(gd_self, virtual)]
     pub fn on_player_walk(this: Gd<Self>, player: Gd<PlayerContainer>, net_id: i32) -> Array<Gd<ScriptResponse>> {
         Array::from(&[ScriptResponse::null_response()])
@@ -107,11 +197,157 @@ impl GenericScriptedEntity {
         Array::from(&[ScriptResponse::null_response()])
     }
 
+    #[func(gd_self, virtual)]
+    /// Called when the player chooses a dialogue option with an action, in a dialogue opened by this entity with
+    /// [method ScriptResponse.open_dialogue]. The option's requirements have been checked again by then.
+    pub fn on_dialogue_choice(this: Gd<Self>, player: Gd<PlayerContainer>, action: GString, net_id: i32) -> Array<Gd<ScriptResponse>> {
+        Array::from(&[ScriptResponse::null_response()])
+    }
+
+    #[func(gd_self, virtual)]
+    /// Called when another entity sends this one a message with [method ScriptResponse.send_message] or [method ScriptResponse.send_message_to_tag].
+    /// Messages are delivered in the same tick they're sent, and so are the responses returned from here.
+    pub fn on_message(this: Gd<Self>, from_entity_id: i32, message: Variant) -> Array<Gd<ScriptResponse>> {
+        Array::from(&[ScriptResponse::null_response()])
+    }
+
+    #[func(gd_self, virtual)]
+    /// Called when a player comes within [member proximity_radius] of the entity, by moving, spawning or the entity moving.
+    pub fn on_player_enter_range(this: Gd<Self>, player: Gd<PlayerContainer>, net_id: i32) -> Array<Gd<ScriptResponse>> {
+        Array::from(&[ScriptResponse::null_response()])
+    }
+
+    #[func(gd_self, virtual)]
+    /// Called when a player in range gets further than [member proximity_radius] from the entity or leaves the instance.
+    pub fn on_player_leave_range(this: Gd<Self>, player: Gd<PlayerContainer>, net_id: i32) -> Array<Gd<ScriptResponse>> {
+        Array::from(&[ScriptResponse::null_response()])
+    }
+
+    #[func(gd_self, virtual)]
+    /// Called when an action started by this entity with [method ScriptResponse.start_action] completes,
+    /// after the action's on_complete responses.
+    pub fn on_action_completed(this: Gd<Self>, player: Gd<PlayerContainer>, net_id: i32) -> Array<Gd<ScriptResponse>> {
+        Array::from(&[ScriptResponse::null_response()])
+    }
+
+    #[func(gd_self, virtual)]
+    /// Called on every entity in the instance when a player in it levels up a skill, possibly by several levels at once.
+    pub fn on_player_level_up(this: Gd<Self>, player: Gd<PlayerContainer>, skill: GString, old_level: i32, new_level: i32, net_id: i32) -> Array<Gd<ScriptResponse>> {
+        Array::from(&[ScriptResponse::null_response()])
+    }
+
+    #[func(gd_self, virtual)]
+    /// Called on every entity in the instance when a player completes a step of a quest other than the last one.
+    /// Quest rewards are meant to be handed out from here, e.g. with [method ScriptResponse.give_item].
+    pub fn on_quest_step_completed(this: Gd<Self>, player: Gd<PlayerContainer>, quest_id: GString, step: i32, net_id: i32) -> Array<Gd<ScriptResponse>> {
+        Array::from(&[ScriptResponse::null_response()])
+    }
+
+    #[func(gd_self, virtual)]
+    /// Called on every entity in the instance when a player completes the last step of a quest.
+    pub fn on_quest_completed(this: Gd<Self>, player: Gd<PlayerContainer>, quest_id: GString, net_id: i32) -> Array<Gd<ScriptResponse>> {
+        Array::from(&[ScriptResponse::null_response()])
+    }
+
+    #[func(gd_self, virtual)]
+    /// Called when a [method ScriptResponse.move_self] or [method ScriptResponse.teleport_self] to the given position
+    /// gets rejected, the entity staying where it was. Also called for a [method ScriptResponse.set_collision] outside the map.
+    /// The reason is meant for debugging.
+    pub fn on_move_rejected(this: Gd<Self>, x: i32, y: i32, reason: GString) -> Array<Gd<ScriptResponse>> {
+        Array::from(&[ScriptResponse::null_response()])
+    }
+
+    #[func(gd_self, virtual)]
+    /// Called when a player kills this entity's NPC, after which the NPC starts over at its home with full hp.
+    pub fn on_npc_death(this: Gd<Self>, player: Gd<PlayerContainer>, net_id: i32) -> Array<Gd<ScriptResponse>> {
+        Array::from(&[ScriptResponse::null_response()])
+    }
+
+    #[func(gd_self, virtual)]
+    /// Called when the instance is about to be freed, either after staying empty for long enough or on shutdown.
+    /// The instance gets started again from the map scene on the next join, so any state worth keeping should be saved here.
+    pub fn on_unload(this: Gd<Self>) {}
+
     #[func]
     fn ticks_since_last_move(&self) -> i32 {
         self.ticks_since_last_move
     }
 
+    #[func]
+    /// Unique within the instance, -1 until the entity is registered.
+    fn get_entity_id(&self) -> i32 {
+        self.entity_id
+    }
+
+    #[func]
+    /// Returns the registered entity with the given id in the same instance, or null.
+    fn get_entity(&self, entity_id: i32) -> Option<Gd<GenericScriptedEntity>> {
+        self.directory.as_ref()?.borrow().get(&entity_id).cloned()
+    }
+
+    #[func]
+    /// Returns the registered entities in the same instance with the tag, including this entity if it has it.
+    fn get_entities_with_tag(&self, tag: GString) -> Array<Gd<GenericScriptedEntity>> {
+        self.query_entities(|entity| entity.bind().tags.contains(&tag))
+    }
+
+    #[func]
+    /// Returns the registered entities in the same instance whose script has the class_name, or extends a script that does.
+    fn get_entities_of_class(&self, class_name: StringName) -> Array<Gd<GenericScriptedEntity>> {
+        self.query_entities(|entity| {
+            let mut script = entity.get_script().try_to::<Gd<Script>>().ok();
+            while let Some(s) = script {
+                if s.get_global_name() == class_name {
+                    return true;
+                }
+                script = s.get_base_script();
+            }
+            false
+        })
+    }
+
+    #[func]
+    /// Returns the other registered entities in the same instance within the Chebyshev distance of this entity.
+    fn get_entities_in_radius(&self, radius: i32) -> Array<Gd<GenericScriptedEntity>> {
+        let pos = self.pos;
+        self.query_entities(|entity| {
+            let b = entity.bind();
+            b.entity_id != self.entity_id && (b.pos.x - pos.x).abs().max((b.pos.y - pos.y).abs()) <= radius
+        })
+    }
+
+    /// Registered entities passing the filter, ordered by entity_id
+    fn query_entities(&self, mut filter: impl FnMut(&Gd<GenericScriptedEntity>) -> bool) -> Array<Gd<GenericScriptedEntity>> {
+        let Some(directory) = &self.directory else {return Array::new()};
+        let mut found: Vec<Gd<GenericScriptedEntity>> = directory.borrow().values().filter(|entity| filter(entity)).cloned().collect();
+        found.sort_by_key(|entity| entity.bind().entity_id);
+        found.into_iter().collect()
+    }
+
+    /// Requirements checked before [method on_player_interaction] gets called
+    pub fn interaction_requirements(&self) -> Vec<Requirement> {
+        let mut requirements = Vec::new();
+        for (k, v) in self.required_skills.iter_shared() {
+            match v.try_to_relaxed::<i32>() {
+                Ok(level) => requirements.push(Requirement::SkillLevel{skill: k.to_string(), level}),
+                Err(_) => godot_error!("Required skills of entity {} should map skill names to ints!", self.base().get_name()),
+            }
+        }
+        if !self.required_equipped.is_empty() {
+            requirements.push(Requirement::Equipped{id_string: self.required_equipped.to_string()});
+        }
+        for (k, v) in self.required_items.iter_shared() {
+            match v.try_to_relaxed::<i32>() {
+                Ok(count) => requirements.push(Requirement::HasItem{id_string: k.to_string(), count}),
+                Err(_) => godot_error!("Required items of entity {} should map item ids to ints!", self.base().get_name()),
+            }
+        }
+        if self.required_gold > 0 {
+            requirements.push(Requirement::Gold{amount: self.required_gold});
+        }
+        requirements
+    }
+
     pub fn get_data(&self) -> (bool, bool, GString, Dictionary) {
         (self.interactable, self.walkable, self.related_scene.clone(), self.public_data.clone())
     }
@@ -142,12 +378,25 @@ impl ScriptResponse {
     }
 
     #[func]
+    /// Moves the entity to an adjacent free tile, or keeps it in place, speed being the ticks the move takes, from 1 to 3.
+    /// 
+    /// Invalid moves are rejected with [method GenericScriptedEntity.on_move_rejected].
     fn move_self(x: i32, y: i32, speed: i32) -> Gd<ScriptResponse> {
         let response = ResponseType::MoveSelf{x, y, speed};
         
         Gd::from_init_fn(|base| ScriptResponse {response, base})
     }
 
+    #[func]
+    /// Moves the entity straight to any free tile within the map.
+    /// 
+    /// Invalid moves are rejected with [method GenericScriptedEntity.on_move_rejected].
+    fn teleport_self(x: i32, y: i32) -> Gd<ScriptResponse> {
+        let response = ResponseType::TeleportSelf{x, y};
+        
+        Gd::from_init_fn(|base| ScriptResponse {response, base})
+    }
+
     #[func]
     fn give_item(item: Gd<ItemResource>, net_id: i32) -> Gd<ScriptResponse> {
         let response = ResponseType::GiveItem{item, net_id};
@@ -155,6 +404,14 @@ impl ScriptResponse {
         Gd::from_init_fn(|base| ScriptResponse {response, base})
     }
 
+    #[func]
+    /// Rolls the loot table server-side and gives the player the result. Loot that doesn't fit into the inventory is lost.
+    fn give_loot(table: Gd<LootTableResource>, net_id: i32) -> Gd<ScriptResponse> {
+        let response = ResponseType::GiveLoot{table, net_id};
+        
+        Gd::from_init_fn(|base| ScriptResponse {response, base})
+    }
+
     #[func]
     fn take_item(id_string: GString, amount: i32, net_id: i32) -> Gd<ScriptResponse> {
         let response = ResponseType::TakeItem{id_string, amount, net_id};
@@ -197,6 +454,119 @@ impl ScriptResponse {
         Gd::from_init_fn(|base| ScriptResponse {response, base})
     }
 
+    #[func]
+    /// Moves the player to a private instance of the map that only the given members can join,
+    /// or to the one they're already a member of. The player is always a member.
+    /// 
+    /// Passing a party's or friends' pids lets the first of them to enter open the instance for the rest.
+    fn move_player_to_private_map(mapname: GString, x: i32, y: i32, members: PackedInt32Array, net_id: i32) -> Gd<ScriptResponse> {
+        let response = ResponseType::MovePlayerToPrivateMap{mapname, x, y, members, net_id};
+        
+        Gd::from_init_fn(|base| ScriptResponse {response, base})
+    }
+
+    #[func]
+    /// Opens the player's bank, sending them its contents. The bank closes once the player moves.
+    fn open_bank(net_id: i32) -> Gd<ScriptResponse> {
+        let response = ResponseType::OpenBank{net_id};
+        
+        Gd::from_init_fn(|base| ScriptResponse {response, base})
+    }
+
+    #[func]
+    /// Starts an action that completes after the given amount of ticks, unless the player moves or interacts with another entity first.
+    /// 
+    /// On completion, the on_complete responses are handled and then [method GenericScriptedEntity.on_action_completed] is called.
+    /// Replaces any action the player already had in progress.
+    fn start_action(ticks: i32, on_complete: Array<Gd<ScriptResponse>>, net_id: i32) -> Gd<ScriptResponse> {
+        let response = ResponseType::StartAction{ticks, on_complete, net_id};
+        
+        Gd::from_init_fn(|base| ScriptResponse {response, base})
+    }
+
+    #[func]
+    /// Changes whether the tile blocks movement for everyone in the instance, like a door opening or closing.
+    /// 
+    /// Positions outside the map are rejected through [method GenericScriptedEntity.on_move_rejected].
+    fn set_collision(x: i32, y: i32, blocked: bool) -> Gd<ScriptResponse> {
+        let response = ResponseType::SetCollision{x, y, blocked};
+        
+        Gd::from_init_fn(|base| ScriptResponse {response, base})
+    }
+
+    #[func]
+    /// Opens the dialogue for the player at its start node, replacing any dialogue they had open. The dialogue closes once the player moves.
+    /// 
+    /// Choosing an option with an action calls [method GenericScriptedEntity.on_dialogue_choice] of this entity.
+    fn open_dialogue(dialogue: Gd<DialogueResource>, net_id: i32) -> Gd<ScriptResponse> {
+        let response = ResponseType::OpenDialogue{dialogue, net_id};
+        
+        Gd::from_init_fn(|base| ScriptResponse {response, base})
+    }
+
+    #[func]
+    /// Starts the quest for the player, if it exists and they haven't started it before. The quest then advances on its own
+    /// as the player does its objectives, calling [method GenericScriptedEntity.on_quest_step_completed] and
+    /// [method GenericScriptedEntity.on_quest_completed] of every entity in the instance.
+    fn start_quest(quest_id: GString, net_id: i32) -> Gd<ScriptResponse> {
+        let response = ResponseType::StartQuest{quest_id, net_id};
+        
+        Gd::from_init_fn(|base| ScriptResponse {response, base})
+    }
+
+    #[func]
+    /// Sets the player flag, readable with [method PlayerContainer.get_flag]. Values can be bools, ints, floats, strings or vectors.
+    /// 
+    /// Players have a limited amount of flags, flags that don't fit are dropped with an error.
+    fn set_player_flag(key: GString, value: Variant, net_id: i32) -> Gd<ScriptResponse> {
+        let response = ResponseType::SetPlayerFlag{key, value, net_id};
+        
+        Gd::from_init_fn(|base| ScriptResponse {response, base})
+    }
+
+    #[func]
+    fn clear_player_flag(key: GString, net_id: i32) -> Gd<ScriptResponse> {
+        let response = ResponseType::ClearPlayerFlag{key, net_id};
+        
+        Gd::from_init_fn(|base| ScriptResponse {response, base})
+    }
+
+    #[func]
+    /// Starts a timer that calls [method GenericScriptedEntity.on_timer] with the name after the given amount of ticks,
+    /// at least 1, and again every as many ticks if repeating. Restarts the entity's timer of the same name if there's one.
+    /// 
+    /// Timers that pause when empty don't count down while there are no players in the instance.
+    fn start_timer(name: GString, ticks: i32, repeating: bool, pause_when_empty: bool) -> Gd<ScriptResponse> {
+        let response = ResponseType::StartTimer{name, ticks, repeating, pause_when_empty};
+        
+        Gd::from_init_fn(|base| ScriptResponse {response, base})
+    }
+
+    #[func]
+    /// Stops the entity's timer of the given name, if it has one.
+    fn cancel_timer(name: GString) -> Gd<ScriptResponse> {
+        let response = ResponseType::CancelTimer{name};
+        
+        Gd::from_init_fn(|base| ScriptResponse {response, base})
+    }
+
+    #[func]
+    /// Delivers the message to [method GenericScriptedEntity.on_message] of the entity with the given id in the same instance.
+    /// Messages to entities that don't exist are dropped.
+    fn send_message(entity_id: i32, message: Variant) -> Gd<ScriptResponse> {
+        let response = ResponseType::SendMessage{entity_id, message};
+        
+        Gd::from_init_fn(|base| ScriptResponse {response, base})
+    }
+
+    #[func]
+    /// Delivers the message to [method GenericScriptedEntity.on_message] of every other entity in the same instance with the tag.
+    fn send_message_to_tag(tag: GString, message: Variant) -> Gd<ScriptResponse> {
+        let response = ResponseType::SendMessageToTag{tag, message};
+        
+        Gd::from_init_fn(|base| ScriptResponse {response, base})
+    }
+
     #[func]
     fn null_response() -> Gd<ScriptResponse> {
         Gd::from_init_fn(|base| ScriptResponse {response: ResponseType::Null, base})
@@ -207,18 +577,54 @@ impl ScriptResponse {
 pub enum ResponseType {
     /// map name, x, y, net_id
     MovePlayerToMap{mapname: GString, x: i32, y: i32, net_id: i32},
+    MovePlayerToPrivateMap{mapname: GString, x: i32, y: i32, members: PackedInt32Array, net_id: i32},
     MovePlayer{x: i32, y: i32, speed: i32, net_id: i32},
     MoveSelf{x: i32, y: i32, speed: i32},
+    TeleportSelf{x: i32, y: i32},
     GiveItem{item: Gd<ItemResource>, net_id: i32},
+    GiveLoot{table: Gd<LootTableResource>, net_id: i32},
     TakeItem{id_string: GString, amount: i32, net_id: i32},
     ChangeGold{amount: i32, net_id: i32},
     GiveXp{skill: GString, amount: i32, net_id: i32},
     DespawnSelf{},
     RegisterEntity{entity: Gd<GenericScriptedEntity>},
     SystemChatMessage{text: GString, net_id: i32},
+    OpenBank{net_id: i32},
+    StartAction{ticks: i32, on_complete: Array<Gd<ScriptResponse>>, net_id: i32},
+    SetCollision{x: i32, y: i32, blocked: bool},
+    StartTimer{name: GString, ticks: i32, repeating: bool, pause_when_empty: bool},
+    CancelTimer{name: GString},
+    SendMessage{entity_id: i32, message: Variant},
+    SendMessageToTag{tag: GString, message: Variant},
+    OpenDialogue{dialogue: Gd<DialogueResource>, net_id: i32},
+    StartQuest{quest_id: GString, net_id: i32},
+    SetPlayerFlag{key: GString, value: Variant, net_id: i32},
+    ClearPlayerFlag{key: GString, net_id: i32},
     Null
 }
 
+/// Why a [ResponseType::MoveSelf], [ResponseType::TeleportSelf] or [ResponseType::SetCollision] was rejected
+pub enum MoveRejection {
+    OutOfBounds,
+    Blocked,
+    Occupied,
+    NotAdjacent,
+    InvalidSpeed,
+}
+
+impl MoveRejection {
+    /// Passed to [GenericScriptedEntity::on_move_rejected]
+    pub fn reason(&self) -> &'static str {
+        match self {
+            MoveRejection::OutOfBounds => "out of map bounds",
+            MoveRejection::Blocked => "blocked by collision",
+            MoveRejection::Occupied => "occupied by an entity that blocks movement",
+            MoveRejection::NotAdjacent => "not an adjacent tile",
+            MoveRejection::InvalidSpeed => "speed should be between 1 and 3",
+        }
+    }
+}
+
 // impl ResponseType {
 //     pub fn is_null(&self) -> bool {
 //         match self {
@@ -236,6 +642,11 @@ pub struct Entities {
 
     // interactables: HashMap<(i32, i32), Gd<GenericScriptedEntity>>,
     walkable_hash: HashMap<(i32, i32), Gd<GenericScriptedEntity>>,
+    /// Tile -> amount of entities blocking movement on it
+    occupancy: HashMap<(i32, i32), u32>,
+    /// entity_ids of the entities that blocked movement when they registered
+    blocking: HashSet<i32>,
+    directory: EntityDirectory,
     entity_hash: SpatialHash<i32, Gd<GenericScriptedEntity>>,
     entity_list: Vec<Gd<GenericScriptedEntity>>
 }
@@ -251,13 +662,48 @@ impl Entities {
         }
     }
 
+    /// Moves the occupancy of the entity along with it, if it blocks movement
+    pub fn move_blocking(&mut self, oldpos: (i32, i32), newpos: (i32, i32), entity_id: i32) {
+        if self.blocking.contains(&entity_id) {
+            self.unoccupy(oldpos);
+            *self.occupancy.entry(newpos).or_default() += 1;
+        }
+    }
+
+    fn unoccupy(&mut self, pos: (i32, i32)) {
+        if let Some(count) = self.occupancy.get_mut(&pos) {
+            *count -= 1;
+            if *count == 0 {
+                self.occupancy.remove(&pos);
+            }
+        }
+    }
+
+    /// Whether an entity blocking movement is on the tile
+    pub fn is_occupied(&self, pos: (i32, i32)) -> bool {
+        self.occupancy.contains_key(&pos)
+    }
+
     pub fn remove_entity(&mut self, pos: (i32, i32), entity_id: i32) {
         // self.interactables.remove(&pos);
         // self.walkables.remove(&pos);
-        self.entity_hash.remove(entity_id, pos);
+        let removed = self.entity_hash.remove(entity_id, pos);
+        if removed.is_some() && self.blocking.remove(&entity_id) {
+            self.unoccupy(pos);
+        }
         if let Some(index) = self.entity_list.iter().position(|v| v.bind().entity_id == entity_id) {
             self.entity_list.remove(index);
         }
+        self.directory.borrow_mut().remove(&entity_id);
+    }
+
+    pub fn get_by_id(&self, entity_id: i32) -> Option<Gd<GenericScriptedEntity>> {
+        self.directory.borrow().get(&entity_id).cloned()
+    }
+
+    /// Entities with the tag, ordered by entity_id
+    pub fn get_with_tag(&self, tag: &GString) -> Vec<Gd<GenericScriptedEntity>> {
+        self.entity_list.iter().filter(|entity| entity.bind().tags.contains(tag)).cloned().collect()
     }
 
     pub fn set_spatial_hash(&mut self, hash: SpatialHash<i32, Gd<GenericScriptedEntity>>) {
@@ -269,7 +715,7 @@ impl Entities {
     }
 
     pub fn get_walkable_at(&mut self, x: i32, y: i32) -> Option<&mut Gd<GenericScriptedEntity>> {
-        return self.walkable_hash.get_mut(&(x, y));
+        self.walkable_hash.get_mut(&(x, y))
     }
 
     pub fn get_hash(&self) -> &SpatialHash<i32, Gd<GenericScriptedEntity>> {
@@ -282,16 +728,30 @@ impl Entities {
         let mut e = entity.bind_mut();
         let x = e.pos.x; let y = e.pos.y;
         e.entity_id = self.last_id;
+        e.directory = Some(self.directory.clone());
+        if let Some(npc) = e.npc.clone() {
+            e.npc_state = Some(NpcState::new(&npc.bind(), (x, y)));
+            e.public_data.set("attackable", true);
+        }
         std::mem::drop(e);
 
         if entity.bind().walkable {
             self.walkable_hash.insert((x, y), entity.clone());
         }
+        if entity.bind().blocks_movement {
+            *self.occupancy.entry((x, y)).or_default() += 1;
+            self.blocking.insert(self.last_id);
+        }
         self.entity_list.push(entity.clone());
+        self.directory.borrow_mut().insert(self.last_id, entity.clone());
         self.entity_hash.insert(self.last_id, entity, (x, y));
     }
 
     pub fn iter_visibles_mut(&mut self) -> impl Iterator<Item = &mut Gd<GenericScriptedEntity>> {
         self.entity_list.iter_mut()
     }
+
+    pub fn iter_visibles(&self) -> impl Iterator<Item = &Gd<GenericScriptedEntity>> {
+        self.entity_list.iter()
+    }
 }
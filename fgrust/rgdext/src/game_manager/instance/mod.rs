@@ -1,17 +1,58 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{cell::RefCell, collections::{HashMap, HashSet}, rc::Rc, time::{SystemTime, UNIX_EPOCH}};
 
 use godot::{classes::{FileAccess, ResourceLoader}, prelude::*};
 use crate::eventqueue::{EQueue, ServerEvent, GameEvent};
-use rgdext_shared::{basemap::{spatialhash::SpatialHash, CollisionArray}, genericevent::{GenericPlayerEvent, GenericServerResponse}, playerdata::{playercontainer::PlayerContainer, skills::Skill, MAX_ITEMS}};
-use player::Player; use entity::{Entities, GenericScriptedEntity, ResponseType, ScriptResponse};
+use rgdext_shared::{basemap::{spatialhash::{MoveDelta, SpatialHash}, CollisionArray, DEFAULT_IDLE_TIMEOUT, DEFAULT_INSTANCE_CAPACITY, DEFAULT_PRIVATE_LIFETIME}, genericevent::{Combatant, GenericPlayerEvent, GenericServerResponse}, playerdata::{combat::{ATTACK_COOLDOWN_TICKS, HP_REGEN_TICKS, XP_PER_DAMAGE}, itemvalue::ItemValue, playercontainer::PlayerContainer, quest::{self, QuestEvent, QuestUpdate}, recipe, requirements, rng::SeededRng, skills::LevelUp, CraftError, PlayerData, MAX_ITEMS}};
+use player::{Action, Player}; use entity::{Entities, GenericScriptedEntity, MoveRejection, ResponseType, ScriptResponse, MAX_MOVE_SPEED, MAX_PROXIMITY_RADIUS};
+use scheduler::Scheduler;
 
 pub mod player;
 mod entity;
+mod npc;
+mod scheduler;
+mod dialogue;
+
+/// How many messages deep entities can answer messages with more messages within a tick, to stop endless back and forth
+const MAX_MESSAGE_DEPTH: u32 = 8;
+
+/// Chebyshev distance in tiles
+fn distance(a: (i32, i32), b: (i32, i32)) -> i32 {
+    a.0.abs_diff(b.0).max(a.1.abs_diff(b.1)) as i32
+}
+
+/// Every scripted entity under the node, however deeply nested
+fn scripted_entities_under(node: &Gd<Node>) -> Vec<Gd<GenericScriptedEntity>> {
+    let mut entities = Vec::new();
+    let mut stack = vec![node.clone()];
+    while let Some(node) = stack.pop() {
+        for child in node.get_children().iter_shared() {
+            stack.push(child.clone());
+            if let Ok(entity) = child.try_cast::<GenericScriptedEntity>() {
+                entities.push(entity);
+            }
+        }
+    }
+    entities
+}
 
 #[derive(GodotClass)]
 #[class(no_init, base=Node)]
 pub struct Instance {
     pub mapname: String,
+    /// Number of this instance among the instances of the same map, starting from 1
+    pub channel: i32,
+    /// Max players, set by the map
+    capacity: i32,
+    /// pids of the players allowed in, None for public instances
+    pub members: Option<Vec<i32>>,
+    /// Seconds the instance stays loaded after emptying if it's public, set by the map
+    idle_timeout: f64,
+    /// Seconds the instance stays open after emptying if it's private, set by the map
+    private_lifetime: f64,
+    /// Seconds since the last player left, 0 while there are players
+    empty_time: f64,
+    /// Entity node path -> persistent state, restored when the map loads
+    pub saved_states: Dictionary,
     equeue: EQueue,
     
     entities_node: Option<Gd<Node>>,
@@ -29,6 +70,18 @@ pub struct Instance {
 
     entities: Entities,
     deferred_responses: Vec<(Gd<GenericScriptedEntity>, Gd<ScriptResponse>)>,
+    /// (net_id, level up), broadcast and passed to entity hooks at the start of the next tick
+    deferred_level_ups: Vec<(i32, LevelUp)>,
+    /// Entity timers, ticked after player actions
+    scheduler: Scheduler,
+    /// How many messages deep the responses currently being handled are
+    message_depth: u32,
+    /// Rolls loot and combat damage
+    rng: SeededRng,
+    /// Where players who die on this map respawn
+    respawn_point: (i32, i32),
+    /// Collision tiles changed by scripts since the map was loaded, sent to players joining the instance
+    collision_changes: HashMap<(i32, i32), bool>,
 
     base: Base<Node>
 }
@@ -40,9 +93,8 @@ impl INode for Instance {
 
         // Registering registerable entities
         for child in self.entities_node.as_ref().unwrap().get_children().iter_shared() {
-            if let Ok(mut entity) = child.try_cast::<GenericScriptedEntity>() {
-                entity.connect("entity_response", &Callable::from_object_method(&self.to_gd(), "handle_entity_response"));
-                self.entities.register_entity(entity);
+            if let Ok(entity) = child.try_cast::<GenericScriptedEntity>() {
+                self.register_entity(entity);
             }
         }
 
@@ -67,7 +119,7 @@ impl INode for Instance {
                     walkable: false,
                     related_scene: "".into(),
                     data: Dictionary::new(), 
-                    entity_id: entity_id,
+                    entity_id,
                     net_id: *net_id
                 });
             });
@@ -75,14 +127,28 @@ impl INode for Instance {
             // self.entities.despawn_entity();
         }
 
+        for (net_id, level_up) in std::mem::take(&mut self.deferred_level_ups) {
+            self.handle_level_up(net_id, level_up);
+        }
+
         for (entity, response) in std::mem::take(&mut self.deferred_responses) {
             self.handle_entity_response(entity, response);
         }
 
         // Sending out packets from players
         // This is done before ticking movement so that scripts can set ticks_since_last_move to 0 and have it work
+        let mut quest_updates = Vec::new();
         for (net_id, p) in self.players.iter() {
             let mut p = p.borrow_mut();
+            // Quest steps can only complete when the player's data changes, be it items, skills or objectives done
+            if p.data_just_updated || p.private_data_just_updated {
+                let updates = p.data.update_quests();
+                if !updates.is_empty() {
+                    p.set_private_change();
+                    quest_updates.extend(updates.into_iter().map(|update| (*net_id, update)));
+                }
+            }
+
             // Broadcasting movement responses from just moved or spawned player to adjacent players
             // 0 ticks since last move means a move just happened
             if p.ticks_since_move == 0 {
@@ -110,11 +176,14 @@ impl INode for Instance {
 
             if p.private_data_just_updated {
                 self.equeue.push_server(
-                    ServerEvent::PlayerDataResponse{data: p.data.to_bytearray(), net_id: *net_id}
+                    ServerEvent::PlayerDataResponse{data: p.data.get_private().to_bytearray(), net_id: *net_id}
                 );
                 p.private_data_just_updated = false;
             }
         }
+        for (net_id, update) in quest_updates {
+            self.handle_quest_update(net_id, update);
+        }
 
         // Ticking player movement
         let col_array = &self.col_array;
@@ -131,35 +200,25 @@ impl INode for Instance {
                     let move_valid = x.abs_diff(nextx) <= 1 &&
                         y.abs_diff(nexty) <= 1 &&
                         (nextspeed == 2 || nextspeed == 3) &&
-                        !col_array.get_at(nextx, nexty);
+                        !col_array.get_at(nextx, nexty) &&
+                        !self.entities.is_occupied((nextx, nexty));
                     if move_valid {
                         p.set_full_pos(nextx, nexty, nextspeed);
 
-                        // Updating player on all players that just entered their spatial hash adjacency
-                        let delta = self.spatial_hash.update_pos(*net_id, (x, y), (nextx, nexty));
-                        delta.for_each_with(&self.spatial_hash, |(_other_net_id, pdata)| {
-                            let b = pdata.borrow();
-                            self.equeue.push_server(ServerEvent::PlayerMoveResponse{
-                                x: b.x(),
-                                y: b.y(),
-                                speed: 0,
-                                pid: b.pid(),
-                                data_version: b.data_version(),
-                                net_id: *net_id
-                            });
-                        });
-                        // And on all entities that just entered their spatial hash adjacency
-                        delta.for_each_with(self.entities.get_hash(), |(entity_id, entity)| {
-                            let b = entity.bind();
-                            self.equeue.push_server(ServerEvent::EntityMoveResponse{
-                                x: b.pos.x,
-                                y: b.pos.y,
-                                speed: 0,
-                                entity_id: *entity_id,
-                                data_version: b.public_data_version,
+                        if p.bank_open {
+                            p.bank_open = false;
+                            self.equeue.push_server(ServerEvent::GenericResponse{
+                                response: GenericServerResponse::BankClosed.to_bytearray(),
                                 net_id: *net_id
                             });
-                        });
+                        }
+                        Self::close_dialogue(&self.equeue, &mut p, *net_id);
+                        if p.data.quest_event(&QuestEvent::Walked{map: &self.mapname, x: nextx, y: nexty}) {
+                            p.set_private_change();
+                        }
+
+                        let delta = self.spatial_hash.update_pos(*net_id, (x, y), (nextx, nexty));
+                        self.send_entered_adjacency(&delta, *net_id);
 
                         // Handling walkable entity
                         if let Some(entity) = self.entities.get_walkable_at(nextx, nexty) {
@@ -186,6 +245,57 @@ impl INode for Instance {
             }
         }
 
+        // Ticking attack cooldowns and hp regeneration
+        for p in self.players.values() {
+            let mut p = p.borrow_mut();
+            p.attack_cooldown = (p.attack_cooldown - 1).max(0);
+            p.regen_ticks -= 1;
+            if p.regen_ticks <= 0 {
+                p.regen_ticks = HP_REGEN_TICKS;
+                if p.data.heal(1) {
+                    p.set_private_change();
+                }
+            }
+        }
+
+        // Ticking actions in progress
+        let mut completed_actions = Vec::new();
+        for (net_id, p) in self.players.iter() {
+            let mut p = p.borrow_mut();
+            let Some(action) = &mut p.action else {continue};
+            action.ticks_done += 1;
+            if action.ticks_done >= action.ticks_total {
+                let action = p.action.take().unwrap();
+                Self::send_action_progress(&self.equeue, action.ticks_total, action.ticks_total, *net_id);
+                completed_actions.push((action, PlayerContainer::from_data(p.data.clone()), *net_id));
+            }
+        }
+        for (action, container, net_id) in completed_actions {
+            let entity = action.entity;
+            // The entity may have despawned while the action was in progress
+            if !entity.is_instance_valid() || entity.is_queued_for_deletion() {
+                continue;
+            }
+            for response in action.on_complete {
+                self.deferred_responses.push((entity.clone(), response));
+            }
+            let responses = GenericScriptedEntity::on_action_completed(entity.clone(), container, net_id);
+            for response in responses.iter_shared() {
+                self.deferred_responses.push((entity.clone(), response));
+            }
+        }
+
+        // Ticking entity timers
+        for (entity, name) in self.scheduler.tick(self.playercount == 0) {
+            let responses = GenericScriptedEntity::on_timer(entity.clone(), name);
+            for response in responses.iter_shared() {
+                self.deferred_responses.push((entity.clone(), response));
+            }
+        }
+
+        self.tick_npcs();
+        self.tick_proximity();
+
         // Ticking entity movement to send out packets to players
         for entity in self.entities.iter_visibles_mut() {
             let mut e = entity.bind_mut();
@@ -213,10 +323,17 @@ impl INode for Instance {
 
 #[godot_api]
 impl Instance {
-    pub fn new(mapname: impl ToString, equeue: EQueue) -> Gd<Instance> {
+    pub fn new(mapname: impl ToString, channel: i32, equeue: EQueue) -> Gd<Instance> {
         Gd::from_init_fn(|base| {
             Instance {
                 mapname: mapname.to_string(),
+                channel,
+                capacity: DEFAULT_INSTANCE_CAPACITY,
+                members: None,
+                idle_timeout: DEFAULT_IDLE_TIMEOUT,
+                private_lifetime: DEFAULT_PRIVATE_LIFETIME,
+                empty_time: 0.,
+                saved_states: Dictionary::new(),
                 equeue,
                 entities_node: None,
                 col_array: CollisionArray::new(),
@@ -228,6 +345,12 @@ impl Instance {
 
                 entities: Entities::default(),
                 deferred_responses: Vec::new(),
+                deferred_level_ups: Vec::new(),
+                scheduler: Scheduler::default(),
+                message_depth: 0,
+                rng: SeededRng::new(SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64)),
+                respawn_point: (0, 0),
+                collision_changes: HashMap::new(),
 
                 base
             }
@@ -241,6 +364,27 @@ impl Instance {
         let entities_resource = ResourceLoader::singleton().load(&format!("res://maps/{}.tscn", &self.mapname)).unwrap();
         let entities_scene: Gd<PackedScene> = entities_resource.cast();
         let entities_node = entities_scene.instantiate().unwrap();
+        if entities_node.has_meta("respawn_point") {
+            let point = entities_node.get_meta("respawn_point").try_to::<Vector2i>().unwrap_or(Vector2i::ZERO);
+            self.respawn_point = (point.x, point.y);
+        }
+        if entities_node.has_meta("idle_timeout") {
+            self.idle_timeout = entities_node.get_meta("idle_timeout").try_to::<f64>().unwrap_or(DEFAULT_IDLE_TIMEOUT);
+        }
+        if entities_node.has_meta("private_lifetime") {
+            self.private_lifetime = entities_node.get_meta("private_lifetime").try_to::<f64>().unwrap_or(DEFAULT_PRIVATE_LIFETIME);
+        }
+        if entities_node.has_meta("capacity") {
+            self.capacity = entities_node.get_meta("capacity").try_to::<i32>().unwrap_or(DEFAULT_INSTANCE_CAPACITY).max(1);
+        }
+
+        // Restoring before the entities' _ready()
+        for mut entity in scripted_entities_under(&entities_node) {
+            let path = entities_node.get_path_to(&entity).to_string();
+            if let Some(state) = self.saved_states.get(path.as_str()) {
+                entity.bind_mut().persistent_state = state.try_to().unwrap_or_default();
+            }
+        }
 
         let col_array_data = FileAccess::get_file_as_bytes(&format!("res://maps/{}.col", &self.mapname));
         let col_array = CollisionArray::from_bytes(col_array_data.as_slice()).unwrap();
@@ -265,6 +409,11 @@ impl Instance {
         self.equeue.push_server(
             ServerEvent::GenericResponse{response: GenericServerResponse::LoadMap{mapname: self.mapname.clone()}.to_bytearray(), net_id}
         );
+        for (&(x, y), &blocked) in self.collision_changes.iter() {
+            self.equeue.push_server(
+                ServerEvent::GenericResponse{response: GenericServerResponse::SetCollision{x, y, blocked}.to_bytearray(), net_id}
+            );
+        }
 
         // Initial update about nearby player on spawn
         self.spatial_hash.for_each_adjacent(pos, |adjacent| {
@@ -290,57 +439,472 @@ impl Instance {
         });
     }
 
+    pub fn is_full(&self) -> bool {
+        self.playercount >= self.capacity
+    }
+
+    /// Share of the capacity in use, 1.0 being full
+    pub fn fill_level(&self) -> f64 {
+        self.playercount as f64 / self.capacity as f64
+    }
+
+    /// Returns true once the instance has been empty for its idle timeout, or its lifetime if it's private
+    pub fn tick_idle(&mut self, delta: f64) -> bool {
+        if self.playercount > 0 {
+            self.empty_time = 0.;
+            return false;
+        }
+        self.empty_time += delta;
+        let timeout = if self.members.is_some() {self.private_lifetime} else {self.idle_timeout};
+        self.empty_time >= timeout
+    }
+
+    /// Lets the entities persist their state before the instance gets freed, returning the persistent states
+    /// of the entities placed in the map keyed by node path. Responses they emit from here on are dropped.
+    pub fn unload(&mut self) -> Dictionary {
+        let callable = Callable::from_object_method(&self.to_gd(), "handle_entity_response");
+        for entity in self.entities.iter_visibles() {
+            let mut entity = entity.clone();
+            entity.disconnect("entity_response", &callable);
+            GenericScriptedEntity::on_unload(entity);
+        }
+        self.deferred_responses.clear();
+        self.scheduler.clear();
+        self.persistent_states()
+    }
+
+    /// The non-empty persistent states of the entities placed in the map, keyed by node path under the Entities node
+    pub fn persistent_states(&self) -> Dictionary {
+        let mut states = Dictionary::new();
+        let Some(entities_node) = &self.entities_node else {return states};
+        for entity in scripted_entities_under(entities_node) {
+            let state = entity.bind().persistent_state.clone();
+            if !state.is_empty() {
+                states.set(entities_node.get_path_to(&entity).to_string(), state);
+            }
+        }
+        states
+    }
+
+    /// Public instances let anyone in
+    pub fn allows(&self, pid: i32) -> bool {
+        self.members.as_ref().is_none_or(|members| members.contains(&pid))
+    }
+
+    /// How many of the given pids are players in this instance
+    pub fn count_present(&self, pids: &[i32]) -> usize {
+        self.players.values().filter(|p| pids.contains(&p.borrow().pid())).count()
+    }
+
     /// Removes all Player refcounts from the instance
     pub fn despawn_player(&mut self, net_id: i32) -> Rc<RefCell<Player>> {
         let player = self.players.remove(&net_id).unwrap();
+        player.borrow_mut().bank_open = false;
+        player.borrow_mut().action = None;
+        player.borrow_mut().dialogue = None;
 
         let p = player.borrow();
         self.spatial_hash.remove(net_id, p.get_pos());
         self.deferred_despawns.push((p.x(), p.y(), p.pid()));
         self.playercount -= 1;
+
+        // Leaving the range of every entity the player was in range of
+        let left: Vec<Gd<GenericScriptedEntity>> = self.entities.iter_visibles()
+            .filter(|entity| entity.bind().players_in_range.contains(&net_id))
+            .cloned()
+            .collect();
+        if !left.is_empty() {
+            let container = PlayerContainer::from_data(p.data.clone());
+            for mut entity in left {
+                entity.bind_mut().players_in_range.remove(&net_id);
+                let responses = GenericScriptedEntity::on_player_leave_range(entity.clone(), container.clone(), net_id);
+                for response in responses.iter_shared() {
+                    self.deferred_responses.push((entity.clone(), response));
+                }
+            }
+        }
         drop(p);
         
         player   
     }
 
+    /// Calls the proximity hooks of entities for the players that came within or left their proximity radius since the last tick
+    fn tick_proximity(&mut self) {
+        let entities: Vec<Gd<GenericScriptedEntity>> = self.entities.iter_visibles()
+            .filter(|entity| entity.bind().proximity_radius >= 0)
+            .cloned()
+            .collect();
+        for mut entity in entities {
+            let mut e = entity.bind_mut();
+            let pos = (e.pos.x, e.pos.y);
+            let radius = e.proximity_radius.min(MAX_PROXIMITY_RADIUS);
+            let mut in_range = HashSet::new();
+            self.spatial_hash.for_each_adjacent(pos, |(net_id, p)| {
+                if distance(p.borrow().get_pos(), pos) <= radius {
+                    in_range.insert(*net_id);
+                }
+            });
+            let left: Vec<i32> = e.players_in_range.difference(&in_range).copied().collect();
+            let entered: Vec<i32> = in_range.difference(&e.players_in_range).copied().collect();
+            e.players_in_range = in_range;
+            drop(e);
+
+            for (net_id, entering) in left.into_iter().map(|id| (id, false)).chain(entered.into_iter().map(|id| (id, true))) {
+                let container = PlayerContainer::from_data(self.players[&net_id].borrow().data.clone());
+                let responses = if entering {
+                    GenericScriptedEntity::on_player_enter_range(entity.clone(), container, net_id)
+                }
+                else {
+                    GenericScriptedEntity::on_player_leave_range(entity.clone(), container, net_id)
+                };
+                for response in responses.iter_shared() {
+                    self.deferred_responses.push((entity.clone(), response));
+                }
+            }
+        }
+    }
+
+    /// Updates the player on all players and entities that just entered their spatial hash adjacency
+    fn send_entered_adjacency(&self, delta: &MoveDelta<i32>, net_id: i32) {
+        delta.for_each_with(&self.spatial_hash, |(_other_net_id, pdata)| {
+            let b = pdata.borrow();
+            self.equeue.push_server(ServerEvent::PlayerMoveResponse{
+                x: b.x(),
+                y: b.y(),
+                speed: 0,
+                pid: b.pid(),
+                data_version: b.data_version(),
+                net_id
+            });
+        });
+        delta.for_each_with(self.entities.get_hash(), |(entity_id, entity)| {
+            let b = entity.bind();
+            self.equeue.push_server(ServerEvent::EntityMoveResponse{
+                x: b.pos.x,
+                y: b.pos.y,
+                speed: 0,
+                entity_id: *entity_id,
+                data_version: b.public_data_version,
+                net_id
+            });
+        });
+    }
+
+    /// Moves the player straight to the given tile on this map, dropping any queued moves
+    fn teleport_player(&mut self, net_id: i32, x: i32, y: i32) {
+        let Some(player) = self.players.get(&net_id) else {return};
+        let mut p = player.borrow_mut();
+        let (oldx, oldy) = p.get_pos();
+        p.clear_moves();
+        p.set_full_pos(x, y, 0);
+        if p.bank_open {
+            p.bank_open = false;
+            self.equeue.push_server(ServerEvent::GenericResponse{
+                response: GenericServerResponse::BankClosed.to_bytearray(),
+                net_id
+            });
+        }
+        Self::close_dialogue(&self.equeue, &mut p, net_id);
+        let pid = p.pid();
+        drop(p);
+
+        // Players around the old position forget about the player, the ones around the new position
+        // get a move response next tick since ticks_since_move is now 0
+        let response = GenericServerResponse::DespawnPlayer{pid}.to_bytearray();
+        self.spatial_hash.for_each_adjacent((oldx, oldy), |(other_net_id, _)| {
+            if *other_net_id != net_id {
+                self.equeue.push_server(ServerEvent::GenericResponse{response: response.clone(), net_id: *other_net_id});
+            }
+        });
+        let delta = self.spatial_hash.update_pos(net_id, (oldx, oldy), (x, y));
+        self.send_entered_adjacency(&delta, net_id);
+    }
+
+    fn handle_attack(&mut self, target_pid: i32, net_id: i32) {
+        let Some(attacker) = self.players.get(&net_id) else {return};
+        let Some((&target_net_id, target)) = self.players.iter().find(|(_, p)| p.borrow().pid() == target_pid) else {return};
+        if target_net_id == net_id {
+            return;
+        }
+
+        let mut a = attacker.borrow_mut();
+        if a.attack_cooldown > 0 {
+            return;
+        }
+        let mut t = target.borrow_mut();
+        let style = a.data.attack_style();
+        if !self.check_attack_reach(a.get_pos(), t.get_pos(), style.range(), net_id) {
+            return;
+        }
+
+        // Attacking cancels the action in progress, like interacting with another entity
+        if a.action.take().is_some() {
+            Self::send_action_progress(&self.equeue, 0, 0, net_id);
+        }
+        a.attack_cooldown = ATTACK_COOLDOWN_TICKS;
+        let damage = a.data.roll_attack(t.data.defence_level(), &mut self.rng);
+        Self::give_combat_xp(&mut a, style.skill(), damage, net_id, &mut self.deferred_level_ups);
+
+        let died = t.data.take_damage(damage);
+        t.set_private_change();
+        self.broadcast_hit(Combatant::Player{pid: a.pid()}, Combatant::Player{pid: target_pid}, damage, t.data.hp, t.data.max_hp(), t.get_pos());
+        drop(t);
+        drop(a);
+
+        if died {
+            self.kill_player(target_net_id);
+        }
+    }
+
+    /// Gives the attacker xp in the skill of their attack style based on the damage dealt
+    fn give_combat_xp(p: &mut Player, skill: &str, damage: i32, net_id: i32, level_ups: &mut Vec<(i32, LevelUp)>) {
+        if damage <= 0 {
+            return;
+        }
+        match p.data.add_xp(skill, damage.saturating_mul(XP_PER_DAMAGE)) {
+            Some(level_up) if level_up.leveled_up() => {
+                p.set_public_change();
+                level_ups.push((net_id, level_up));
+            },
+            _ => p.set_private_change(),
+        }
+    }
+
+    /// Notifies everyone around the player about their death, then respawns them with full hp at the map's respawn point
+    fn kill_player(&mut self, net_id: i32) {
+        let Some(player) = self.players.get(&net_id) else {return};
+        let mut p = player.borrow_mut();
+        let response = GenericServerResponse::Death{pid: p.pid()}.to_bytearray();
+        self.spatial_hash.for_each_adjacent(p.get_pos(), |adjacent| {
+            self.equeue.push_server(ServerEvent::GenericResponse{response: response.clone(), net_id: adjacent.0});
+        });
+
+        p.data.restore_hp();
+        p.set_private_change();
+        if p.action.take().is_some() {
+            Self::send_action_progress(&self.equeue, 0, 0, net_id);
+        }
+        drop(p);
+
+        let (x, y) = self.respawn_point;
+        self.teleport_player(net_id, x, y);
+    }
+
+    /// Notifies the player and everyone around them about the level up, then runs the level up hooks of all entities
+    fn handle_level_up(&mut self, net_id: i32, level_up: LevelUp) {
+        let Some(player) = self.players.get(&net_id) else {return};
+        let p = player.borrow();
+        let response = GenericServerResponse::LevelUp{
+            pid: p.pid(),
+            skill: level_up.skill.clone(),
+            old_level: level_up.old_level,
+            new_level: level_up.new_level
+        }.to_bytearray();
+        self.spatial_hash.for_each_adjacent(p.get_pos(), |adjacent| {
+            self.equeue.push_server(ServerEvent::GenericResponse{response: response.clone(), net_id: adjacent.0});
+        });
+
+        let container = PlayerContainer::from_data(p.data.clone());
+        drop(p);
+        let skill = GString::from(&level_up.skill);
+        for entity in self.entities.iter_visibles() {
+            let responses = GenericScriptedEntity::on_player_level_up(
+                entity.clone(), container.clone(), skill.clone(), level_up.old_level, level_up.new_level, net_id
+            );
+            for response in responses.iter_shared() {
+                self.deferred_responses.push((entity.clone(), response));
+            }
+        }
+    }
+
+    /// Tells the player about the completed quest step, then runs the quest hooks of all entities, which hand out the rewards
+    fn handle_quest_update(&mut self, net_id: i32, update: QuestUpdate) {
+        let Some(player) = self.players.get(&net_id) else {return};
+        let Some(def) = quest::get(&update.id) else {return};
+        let text = match def.steps.get(update.step + 1) {
+            Some(next) if !update.completed => format!("Quest updated: {} - {}", def.name, next.description),
+            _ => format!("Quest completed: {}", def.name),
+        };
+        self.equeue.push_server(ServerEvent::PlayerChat{text: text.into(), from: "".into(), from_pid: -1, is_dm: false, net_id});
+
+        let container = PlayerContainer::from_data(player.borrow().data.clone());
+        let quest_id = GString::from(&update.id);
+        for entity in self.entities.iter_visibles() {
+            let responses = if update.completed {
+                GenericScriptedEntity::on_quest_completed(entity.clone(), container.clone(), quest_id.clone(), net_id)
+            }
+            else {
+                GenericScriptedEntity::on_quest_step_completed(entity.clone(), container.clone(), quest_id.clone(), update.step as i32, net_id)
+            };
+            for response in responses.iter_shared() {
+                self.deferred_responses.push((entity.clone(), response));
+            }
+        }
+    }
+
     pub fn player_move(&mut self, x: i32, y: i32, speed: i32, net_id: i32) {
         if let Some(player) = self.players.get_mut(&net_id) {
-            player.borrow_mut().insert_next_move(x, y, speed);
+            let mut p = player.borrow_mut();
+            p.insert_next_move(x, y, speed);
+            if p.action.take().is_some() {
+                Self::send_action_progress(&self.equeue, 0, 0, net_id);
+            }
+        }
+    }
+
+    /// Entities outside the map would be silently left out of the spatial hash, so they aren't registered at all
+    fn register_entity(&mut self, mut entity: Gd<GenericScriptedEntity>) {
+        let pos = entity.bind().pos;
+        if !self.col_array.in_bounds(pos.x, pos.y) {
+            godot_error!("Entity {} at {} is outside the bounds of map {}, not registering it", entity.get_name(), pos, self.mapname);
+            return;
+        }
+        entity.connect("entity_response", &Callable::from_object_method(&self.to_gd(), "handle_entity_response"));
+        self.entities.register_entity(entity.clone());
+
+        let responses = GenericScriptedEntity::on_registered(entity.clone());
+        for response in responses.iter_shared() {
+            self.deferred_responses.push((entity.clone(), response));
+        }
+    }
+
+    /// Moves the entity and sends the move to players around it next tick
+    fn move_entity(&mut self, entity: &mut Gd<GenericScriptedEntity>, x: i32, y: i32, speed: i32) {
+        let mut b = entity.bind_mut();
+        let oldpos = (b.pos.x, b.pos.y);
+        let entity_id = b.entity_id;
+        let walkable = b.walkable;
+        let newpos = (x, y);
+
+        b.pos = Vector2i::new(x, y);
+        b.last_speed = speed;
+        b.ticks_since_last_move = 0;
+        drop(b);
+
+        self.entities.move_entity(oldpos, newpos, entity_id);
+        if walkable {
+            self.entities.move_walkable(oldpos, newpos);
+        }
+        self.entities.move_blocking(oldpos, newpos, entity_id);
+    }
+
+    /// Moves the entity straight to the given tile, players around the old position forgetting about it
+    fn teleport_entity(&mut self, entity: &mut Gd<GenericScriptedEntity>, x: i32, y: i32) {
+        let (pos, entity_id) = {
+            let b = entity.bind();
+            (b.pos, b.entity_id)
+        };
+        // Players around the new position get a move response next tick, respawning the entity for the ones in both
+        self.spatial_hash.for_each_adjacent((pos.x, pos.y), |(net_id, _)| {
+            self.equeue.push_server(ServerEvent::EntityDataResponse{
+                interactable: false,
+                walkable: false,
+                related_scene: "".into(),
+                data: Dictionary::new(),
+                entity_id,
+                net_id: *net_id
+            });
+        });
+        self.move_entity(entity, x, y, 0);
+    }
+
+    /// Returns why an entity at from can't move to the given tile, if it can't
+    fn check_entity_destination(&self, from: (i32, i32), x: i32, y: i32) -> Option<MoveRejection> {
+        if !self.col_array.in_bounds(x, y) {
+            Some(MoveRejection::OutOfBounds)
+        }
+        else if self.col_array.get_at(x, y) {
+            Some(MoveRejection::Blocked)
+        }
+        else if (x, y) != from && self.entities.is_occupied((x, y)) {
+            Some(MoveRejection::Occupied)
+        }
+        else {
+            None
         }
     }
 
+    fn reject_entity_move(&mut self, entity: &Gd<GenericScriptedEntity>, x: i32, y: i32, rejection: MoveRejection) {
+        let responses = GenericScriptedEntity::on_move_rejected(entity.clone(), x, y, rejection.reason().into());
+        for response in responses.iter_shared() {
+            self.deferred_responses.push((entity.clone(), response));
+        }
+    }
+
+    /// Calls the target's message hook and handles its responses right away, so that chains of entities react within the tick
+    fn deliver_message(&mut self, from: &Gd<GenericScriptedEntity>, target: Gd<GenericScriptedEntity>, message: &Variant) {
+        if self.message_depth >= MAX_MESSAGE_DEPTH {
+            godot_error!("Dropping message from entity {} to {}, messages went {} deep", from.get_name(), target.get_name(), MAX_MESSAGE_DEPTH);
+            return;
+        }
+        let from_id = from.bind().entity_id;
+        let responses = GenericScriptedEntity::on_message(target.clone(), from_id, message.clone());
+        self.message_depth += 1;
+        for response in responses.iter_shared() {
+            self.handle_entity_response(target.clone(), response);
+        }
+        self.message_depth -= 1;
+    }
+
+    /// A ticks_total of 0 means the action was cancelled
+    fn send_action_progress(equeue: &EQueue, ticks_done: i32, ticks_total: i32, net_id: i32) {
+        equeue.push_server(ServerEvent::GenericResponse{
+            response: GenericServerResponse::ActionProgress{ticks_done, ticks_total}.to_bytearray(),
+            net_id
+        });
+    }
+
     #[func]
     fn handle_entity_response(&mut self, mut entity: Gd<GenericScriptedEntity>, mut response: Gd<ScriptResponse>) {
         match &mut response.bind_mut().response {
             ResponseType::MovePlayerToMap{mapname, x, y, net_id} => {
                 self.equeue.push_game(GameEvent::PlayerJoinInstance{mapname: mapname.to_string(), x: *x, y: *y, net_id: *net_id});
             },
+            ResponseType::MovePlayerToPrivateMap{mapname, x, y, members, net_id} => {
+                self.equeue.push_game(GameEvent::PlayerJoinPrivateInstance{
+                    mapname: mapname.to_string(),
+                    x: *x,
+                    y: *y,
+                    members: members.to_vec(),
+                    net_id: *net_id
+                });
+            },
             ResponseType::MovePlayer{x, y, speed, net_id} => {
                 if let Some(player) = self.players.get(net_id) {
                     player.borrow_mut().set_full_pos(*x, *y, *speed);
                 }
             },
             ResponseType::MoveSelf{x, y, speed} => {
-                let mut b = entity.bind_mut();
-                let oldpos = (b.pos.x, b.pos.y);
-                let entity_id = b.entity_id;
-                let walkable = b.walkable;
-                let newpos = (*x, *y);
-
-                b.pos = Vector2i::new(*x, *y);
-                b.last_speed = *speed;
-                b.ticks_since_last_move = 0;
-                drop(b);
-
-                self.entities.move_entity(oldpos, newpos, entity_id);
-                if walkable {
-                    self.entities.move_walkable(oldpos, newpos);
+                let pos = entity.bind().pos;
+                let rejection = if x.abs_diff(pos.x) > 1 || y.abs_diff(pos.y) > 1 {
+                    Some(MoveRejection::NotAdjacent)
+                }
+                else if !(1..=MAX_MOVE_SPEED).contains(speed) {
+                    Some(MoveRejection::InvalidSpeed)
+                }
+                else {
+                    self.check_entity_destination((pos.x, pos.y), *x, *y)
+                };
+                match rejection {
+                    Some(rejection) => self.reject_entity_move(&entity, *x, *y, rejection),
+                    None => self.move_entity(&mut entity, *x, *y, *speed),
+                }
+            },
+            ResponseType::TeleportSelf{x, y} => {
+                let pos = entity.bind().pos;
+                match self.check_entity_destination((pos.x, pos.y), *x, *y) {
+                    Some(rejection) => self.reject_entity_move(&entity, *x, *y, rejection),
+                    None => self.teleport_entity(&mut entity, *x, *y),
                 }
             },
             ResponseType::GiveItem{item, net_id} => {
                 if let Some(player) = self.players.get(net_id) {
                     let mut b = player.borrow_mut();
-                    if b.data.insert_item(item.bind().to_item()) {
+                    let item = item.bind().to_item();
+                    if !item.is_valid() {
+                        godot_error!("Tried to give item {} with invalid count {}", item.id_string(), item.count);
+                    }
+                    else if b.data.insert_item(item) {
                         b.set_private_change();
                     }
                     else {
@@ -348,19 +912,39 @@ impl Instance {
                     }
                 }
             },
+            ResponseType::GiveLoot{table, net_id} => {
+                if let Some(player) = self.players.get(net_id) {
+                    let table = table.bind().to_table();
+                    let mut b = player.borrow_mut();
+                    let drops = {
+                        let data = &b.data;
+                        table.roll(&mut self.rng, &|skill| data.effective_level(skill).unwrap_or(0))
+                    };
+                    if !drops.is_empty() {
+                        if b.data.give_items(&drops) {
+                            b.set_private_change();
+                        }
+                        else {
+                            godot_print!("Player {} had no room for loot {:?}, none of it was given", b.data.name, drops);
+                            self.equeue.push_server(
+                                ServerEvent::PlayerChat{text: "Your inventory is too full to hold the loot.".into(), from: "".into(), from_pid: -1, is_dm: false, net_id: *net_id}
+                            );
+                        }
+                    }
+                }
+            },
             ResponseType::GiveXp{skill, amount, net_id} => {
                 if let Some(player) = self.players.get(net_id) {
                     godot_print!("Attempting to give skill {}, xp: {}", skill, amount);
                     let skillstr = String::from(&*skill);
-                    if let Some(skill) = Skill::try_from_str(&skillstr) {
-                        let mut b = player.borrow_mut();
-                        let level_delta = b.data.add_xp(skill, *amount);
-                        if level_delta > 0 {
+                    let mut b = player.borrow_mut();
+                    match b.data.add_xp(&skillstr, *amount) {
+                        Some(level_up) if level_up.leveled_up() => {
                             b.set_public_change();
-                        }
-                        else {
-                            b.set_private_change();
-                        }
+                            self.deferred_level_ups.push((*net_id, level_up));
+                        },
+                        Some(_) => b.set_private_change(),
+                        None => godot_error!("Tried to give xp in nonexistent skill {}", skillstr),
                     }
                 }
             },
@@ -381,8 +965,12 @@ impl Instance {
                 if let Some(player) = self.players.get(net_id) {
                     let mut b = player.borrow_mut();
                     
-                    b.data.gold += *amount;
-                    b.set_private_change();
+                    if b.data.change_gold(*amount) {
+                        b.set_private_change();
+                    }
+                    else {
+                        godot_print!("Couldn't change gold by {}", amount);
+                    }
                 }
             },
             ResponseType::DespawnSelf{} => {
@@ -391,16 +979,113 @@ impl Instance {
                 let entity_id = b.entity_id;
                 drop(b);
                 entity.queue_free();
+                self.scheduler.cancel_all(entity.instance_id());
                 self.deferred_entity_despawns.push((x, y, entity_id));
                 self.entities.remove_entity((x, y), entity_id);
             },
             ResponseType::RegisterEntity{entity} => {
-                entity.connect("entity_response", &Callable::from_object_method(&self.to_gd(), "handle_entity_response"));
-                self.entities.register_entity(entity.clone());
+                self.register_entity(entity.clone());
             }
             ResponseType::SystemChatMessage{text, net_id} => {
                 self.equeue.push_server(ServerEvent::PlayerChat{text: text.clone(), from: "".into(), from_pid: -1, is_dm: false, net_id: *net_id});
             }
+            ResponseType::OpenBank{net_id} => {
+                if let Some(player) = self.players.get(net_id) {
+                    let mut b = player.borrow_mut();
+                    b.bank_open = true;
+                    self.send_bank_contents(&b.data, *net_id);
+                }
+            }
+            ResponseType::StartAction{ticks, on_complete, net_id} => {
+                if let Some(player) = self.players.get(net_id) {
+                    let ticks_total = (*ticks).max(1);
+                    player.borrow_mut().action = Some(Action {
+                        entity: entity.clone(),
+                        ticks_done: 0,
+                        ticks_total,
+                        on_complete: on_complete.iter_shared().collect(),
+                    });
+                    Self::send_action_progress(&self.equeue, 0, ticks_total, *net_id);
+                }
+            },
+            ResponseType::SetCollision{x, y, blocked} => {
+                if !self.col_array.in_bounds(*x, *y) {
+                    self.reject_entity_move(&entity, *x, *y, MoveRejection::OutOfBounds);
+                }
+                else {
+                    self.col_array.set_at(*x, *y, *blocked);
+                    self.collision_changes.insert((*x, *y), *blocked);
+                    let response = GenericServerResponse::SetCollision{x: *x, y: *y, blocked: *blocked}.to_bytearray();
+                    for net_id in self.players.keys() {
+                        self.equeue.push_server(ServerEvent::GenericResponse{response: response.clone(), net_id: *net_id});
+                    }
+                }
+            },
+            ResponseType::StartTimer{name, ticks, repeating, pause_when_empty} => {
+                self.scheduler.start(entity.clone(), name.clone(), *ticks, *repeating, *pause_when_empty);
+            },
+            ResponseType::CancelTimer{name} => {
+                self.scheduler.cancel(entity.instance_id(), name);
+            },
+            ResponseType::SendMessage{entity_id, message} => {
+                if let Some(target) = self.entities.get_by_id(*entity_id) {
+                    self.deliver_message(&entity, target, message);
+                }
+            },
+            ResponseType::SendMessageToTag{tag, message} => {
+                let from_id = entity.bind().entity_id;
+                for target in self.entities.get_with_tag(tag) {
+                    if target.bind().entity_id != from_id {
+                        self.deliver_message(&entity, target, message);
+                    }
+                }
+            },
+            ResponseType::OpenDialogue{dialogue, net_id} => {
+                let dialogue = dialogue.bind().to_dialogue();
+                self.open_dialogue(entity.clone(), dialogue, *net_id);
+            },
+            ResponseType::StartQuest{quest_id, net_id} => {
+                if let Some(player) = self.players.get(net_id) {
+                    let id = quest_id.to_string();
+                    let Some(def) = quest::get(&id) else {
+                        godot_error!("Entity {} tried to start nonexistent quest {}", entity.get_name(), id);
+                        return;
+                    };
+                    let mut b = player.borrow_mut();
+                    if b.data.start_quest(&id) {
+                        b.set_private_change();
+                        let description = def.steps.first().map(|step| step.description.as_str()).unwrap_or("");
+                        self.equeue.push_server(ServerEvent::PlayerChat{
+                            text: format!("Quest started: {} - {}", def.name, description).into(),
+                            from: "".into(),
+                            from_pid: -1,
+                            is_dm: false,
+                            net_id: *net_id
+                        });
+                    }
+                }
+            },
+            ResponseType::SetPlayerFlag{key, value, net_id} => {
+                if let Some(player) = self.players.get(net_id) {
+                    let Some(value) = ItemValue::from_variant(value) else {
+                        godot_error!("Entity {} tried to set player flag {} to an unsupported value", entity.get_name(), key);
+                        return;
+                    };
+                    let mut b = player.borrow_mut();
+                    match b.data.set_flag(&key.to_string(), &value) {
+                        Ok(()) => b.set_private_change(),
+                        Err(e) => godot_error!("Entity {} couldn't set player flag {}: {}", entity.get_name(), key, e.message()),
+                    }
+                }
+            },
+            ResponseType::ClearPlayerFlag{key, net_id} => {
+                if let Some(player) = self.players.get(net_id) {
+                    let mut b = player.borrow_mut();
+                    if b.data.clear_flag(&key.to_string()) {
+                        b.set_private_change();
+                    }
+                }
+            },
             ResponseType::Null => {},
         }
     }
@@ -421,27 +1106,47 @@ impl Instance {
                     godot_print!("Player {} attempted to interact with invalid entity_id {}", player.borrow().data.name, entity_id);
                     return
                 }
+                // Interacting again with the entity whose action is in progress does nothing
+                if pb.action.as_ref().is_some_and(|action| action.entity == *interactable) {
+                    return
+                }
                 let (px, py) = pb.get_pos();
                 let max_dist = b.interactable_distance;
+                let requirements = b.interaction_requirements();
                 drop(b); 
                 let dist = x.abs_diff(px).max(y.abs_diff(py)) as i32;
     
-                if dist <= max_dist {
+                if dist > max_dist {
+                    self.equeue.push_server(
+                        ServerEvent::PlayerChat{text: "Too far!".into(), from: "".into(), from_pid: -1, is_dm: false, net_id}
+                    );
+                }
+                else if let Some(unmet) = requirements::first_unmet(&requirements, &pb.data) {
+                    self.equeue.push_server(
+                        ServerEvent::PlayerChat{text: unmet.failure_message().into(), from: "".into(), from_pid: -1, is_dm: false, net_id}
+                    );
+                }
+                else {
                     // let item = pb.data.equipped_item.as_ref().map(|i| i.to_resource());
-                    let container = PlayerContainer::from_data(pb.data.clone());
-                    let responses = GenericScriptedEntity::on_player_interaction(interactable.clone(), container, net_id);
                     drop(pb);
+                    let tags: Vec<String> = interactable.bind().tags.as_slice().iter().map(|tag| tag.to_string()).collect();
+                    let mut p = player.borrow_mut();
+                    // Interacting with another entity cancels the action in progress
+                    if p.action.take().is_some() {
+                        Self::send_action_progress(&self.equeue, 0, 0, net_id);
+                    }
+                    if p.data.quest_event(&QuestEvent::Interacted{tags: &tags}) {
+                        p.set_private_change();
+                    }
+                    let container = PlayerContainer::from_data(p.data.clone());
+                    drop(p);
+                    let responses = GenericScriptedEntity::on_player_interaction(interactable.clone(), container, net_id);
                     let interactable = interactable.clone();
         
                     for response in responses.iter_shared() {
                         self.handle_entity_response(interactable.clone(), response);
                     }
                 }
-                else {
-                    self.equeue.push_server(
-                        ServerEvent::PlayerChat{text: "Too far!".into(), from: "".into(), from_pid: -1, is_dm: false, net_id}
-                    );
-                }
             }
         }
     }
@@ -467,25 +1172,37 @@ impl Instance {
                 }
                 if let Some(player) = self.players.get(&net_id) {
                     let mut b = player.borrow_mut();
-                    let d = &mut b.data;
-                    std::mem::swap(&mut d.items[from], &mut d.equipped_item);
-                    b.set_public_change(); // Because equipped items are public
+                    if b.data.equip_item(from) {
+                        b.set_public_change(); // Because equipped items are public
+                    }
+                    else {
+                        // Resending the data so the client can undo whatever it predicted
+                        b.set_private_change();
+                        self.equeue.push_server(
+                            ServerEvent::PlayerChat{text: "That item can't be equipped.".into(), from: "".into(), from_pid: -1, is_dm: false, net_id}
+                        );
+                    }
+                }
+            },
+            GenericPlayerEvent::UnequipItem{slot} => {
+                if let Some(player) = self.players.get(&net_id) {
+                    let mut b = player.borrow_mut();
+                    if b.data.unequip_item(slot) {
+                        b.set_public_change();
+                    }
+                    else {
+                        b.set_private_change();
+                    }
                 }
             },
             GenericPlayerEvent::SplitItem{from, to, new_count} => {
-                if to >= MAX_ITEMS {
+                if from >= MAX_ITEMS || to >= MAX_ITEMS {
                     return;
                 }
                 if let Some(player) = self.players.get(&net_id) {
                     let mut b = player.borrow_mut();
 
-                    if b.data.items[to].is_some() {
-                        return;
-                    }
-                    if let Some(from_item) = &mut b.data.items[from] {
-                        let new_item = from_item.try_split(new_count);
-
-                        b.data.items[to] = new_item;
+                    if b.data.split_item(from, to, new_count) {
                         b.set_private_change();
                     }
                 }
@@ -500,10 +1217,41 @@ impl Instance {
                     b.set_private_change();
                 }
             },
+            GenericPlayerEvent::DepositItem{from} => {
+                self.bank_action(net_id, |data| data.deposit_item(from));
+            },
+            GenericPlayerEvent::WithdrawItem{from} => {
+                self.bank_action(net_id, |data| data.withdraw_item(from));
+            },
+            GenericPlayerEvent::SortBank => {
+                self.bank_action(net_id, |data| {data.sort_bank(); true});
+            },
+            GenericPlayerEvent::CloseBank => {
+                if let Some(player) = self.players.get(&net_id) {
+                    player.borrow_mut().bank_open = false;
+                }
+            },
+            GenericPlayerEvent::Craft{recipe_id} => {
+                self.handle_craft(&recipe_id, net_id);
+            },
+            GenericPlayerEvent::Attack{target_pid} => {
+                self.handle_attack(target_pid, net_id);
+            },
+            GenericPlayerEvent::AttackEntity{x, y, entity_id} => {
+                self.handle_attack_entity(x, y, entity_id, net_id);
+            },
+            GenericPlayerEvent::ChooseDialogueOption{index} => {
+                self.handle_dialogue_choice(index, net_id);
+            },
+            GenericPlayerEvent::CloseDialogue => {
+                if let Some(player) = self.players.get(&net_id) {
+                    player.borrow_mut().dialogue = None;
+                }
+            },
             GenericPlayerEvent::DropGold{count} => {
                 if let Some(player) = self.players.get(&net_id) {
                     let mut b = player.borrow_mut();
-                    if b.data.gold < count {
+                    if count <= 0 || b.data.gold < count {
                         return;
                     }
 
@@ -516,6 +1264,49 @@ impl Instance {
         }
     }
 
+    fn handle_craft(&mut self, recipe_id: &str, net_id: i32) {
+        let Some(player) = self.players.get(&net_id) else {return};
+        let Some(recipe) = recipe::get(recipe_id) else {
+            godot_print!("Player {} attempted to craft nonexistent recipe {}", player.borrow().data.name, recipe_id);
+            return
+        };
+
+        let mut b = player.borrow_mut();
+        if !recipe.station.is_empty() {
+            let (px, py) = b.get_pos();
+            let mut station_nearby = false;
+            self.entities.get_hash().for_each_adjacent((px, py), |(_, entity)| {
+                let e = entity.bind();
+                let dist = e.pos.x.abs_diff(px).max(e.pos.y.abs_diff(py)) as i32;
+                if e.station.to_string() == recipe.station && dist <= e.interactable_distance {
+                    station_nearby = true;
+                }
+            });
+            if !station_nearby {
+                self.equeue.push_server(
+                    ServerEvent::PlayerChat{text: "You need to be next to the right station to craft that.".into(), from: "".into(), from_pid: -1, is_dm: false, net_id}
+                );
+                return;
+            }
+        }
+
+        match b.data.craft(&recipe) {
+            Ok(Some(level_up)) if level_up.leveled_up() => {
+                b.set_public_change();
+                self.deferred_level_ups.push((net_id, level_up));
+            },
+            Ok(_) => b.set_private_change(),
+            Err(err) => {
+                if let CraftError::UnknownSkill(skill) = &err {
+                    godot_error!("Recipe {} gives xp in nonexistent skill {}", recipe.id, skill);
+                }
+                self.equeue.push_server(
+                    ServerEvent::PlayerChat{text: err.message().into(), from: "".into(), from_pid: -1, is_dm: false, net_id}
+                );
+            },
+        }
+    }
+
     /// Pushes response with empty dictionary if invalid entity targeted
     pub fn get_entity_data(&self, x: i32, y: i32, entity_id: i32, net_id: i32) {
         if let Some(entity) = self.entities.get_hash().get((x, y), entity_id) {
@@ -551,7 +1342,25 @@ impl Instance {
         }
     }
 
+    /// Applies the action to the player's data if they have the bank open, action should return true if anything changed
+    fn bank_action(&self, net_id: i32, action: impl FnOnce(&mut PlayerData) -> bool) {
+        if let Some(player) = self.players.get(&net_id) {
+            let mut b = player.borrow_mut();
+            if b.bank_open && action(&mut b.data) {
+                b.set_private_change();
+                self.send_bank_contents(&b.data, net_id);
+            }
+        }
+    }
+
+    fn send_bank_contents(&self, data: &PlayerData, net_id: i32) {
+        let response = GenericServerResponse::BankContents{items: data.bank.clone()};
+        self.equeue.push_server(
+            ServerEvent::GenericResponse{response: response.to_bytearray(), net_id}
+        );
+    }
+
     pub fn get_net_id_playerdata(&self, net_id: i32) -> Option<Rc<RefCell<Player>>> {
-        self.players.get(&net_id).map(|p| p.clone())
+        self.players.get(&net_id).cloned()
     }
 }
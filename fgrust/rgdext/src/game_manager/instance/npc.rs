@@ -0,0 +1,318 @@
+use godot::prelude::*;
+use crate::eventqueue::ServerEvent;
+use rgdext_shared::{genericevent::{Combatant, GenericServerResponse}, playerdata::{combat::{roll_damage, ATTACK_COOLDOWN_TICKS}, playercontainer::PlayerContainer}};
+
+use super::{distance, entity::GenericScriptedEntity, Instance};
+
+#[derive(GodotClass)]
+#[class(base=Resource)]
+/// Makes the entity it's set on a hostile NPC, ticked server-side. Distances are Chebyshev distances in tiles.
+///
+/// The NPC attacks the closest player within aggro_radius, chases them as long as they stay within leash_distance
+/// of its home and walks back home when they don't, fully healing once there.
+pub struct NpcBehavior {
+    #[export]
+    aggro_radius: i32,
+    #[export]
+    /// Max distance from home, both for the NPC and for players it chases.
+    leash_distance: i32,
+    #[export]
+    /// Max distance from home the NPC wanders to while idle, 0 to stay put.
+    wander_radius: i32,
+    #[export]
+    /// Ticks between moves, also used as the move speed.
+    move_ticks: i32,
+    #[export]
+    attack_range: i32,
+    #[export]
+    /// Ticks between attacks.
+    attack_cooldown: i32,
+    #[export]
+    attack_level: i32,
+    #[export]
+    /// Added to the max hit like a weapon's damage.
+    damage: i32,
+    #[export]
+    /// Compared against the attacker's level, like a player's agility.
+    defence_level: i32,
+    #[export]
+    max_hp: i32,
+
+    base: Base<Resource>,
+}
+
+#[godot_api]
+impl IResource for NpcBehavior {
+    fn init(base: Base<Resource>) -> Self {
+        Self {
+            aggro_radius: 4,
+            leash_distance: 8,
+            wander_radius: 2,
+            move_ticks: 3,
+            attack_range: 1,
+            attack_cooldown: 8,
+            attack_level: 1,
+            damage: 0,
+            defence_level: 1,
+            max_hp: 20,
+            base,
+        }
+    }
+}
+
+/// Server-side state of an entity with an [NpcBehavior]
+pub struct NpcState {
+    pub home: (i32, i32),
+    pub hp: i32,
+    /// net_id of the chased player
+    target: Option<i32>,
+    /// Walking back home after losing its target
+    returning: bool,
+    attack_cooldown: i32,
+    ticks_since_move: i32,
+    ticks_till_wander: i32,
+}
+
+impl NpcState {
+    pub fn new(behavior: &NpcBehavior, home: (i32, i32)) -> Self {
+        Self {
+            home,
+            hp: behavior.max_hp,
+            target: None,
+            returning: false,
+            attack_cooldown: 0,
+            ticks_since_move: 0,
+            ticks_till_wander: 0,
+        }
+    }
+}
+
+/// What an NPC decided to do this tick
+enum NpcAction {
+    Attack{target_net_id: i32},
+    Step{x: i32, y: i32, speed: i32},
+    /// Teleport home, for when no path leads there
+    GoHome,
+    Idle,
+}
+
+impl Instance {
+    pub(super) fn tick_npcs(&mut self) {
+        let npcs: Vec<Gd<GenericScriptedEntity>> = self.entities.iter_visibles()
+            .filter(|entity| entity.bind().npc_state.is_some())
+            .cloned()
+            .collect();
+        for entity in npcs {
+            self.tick_npc(entity);
+        }
+    }
+
+    fn tick_npc(&mut self, mut entity: Gd<GenericScriptedEntity>) {
+        let Some(behavior) = entity.bind().npc.clone() else {return};
+        let behavior = behavior.bind();
+        let mut e = entity.bind_mut();
+        let pos = (e.pos.x, e.pos.y);
+        let Some(state) = e.npc_state.as_mut() else {return};
+        state.attack_cooldown = (state.attack_cooldown - 1).max(0);
+        state.ticks_since_move += 1;
+        let home = state.home;
+        let leash = behavior.leash_distance;
+
+        // Losing targets that left, or got too far from home
+        if let Some(target_net_id) = state.target {
+            let in_leash = self.players.get(&target_net_id).is_some_and(|p| distance(p.borrow().get_pos(), home) <= leash);
+            if !in_leash {
+                state.target = None;
+                state.returning = true;
+            }
+        }
+
+        if state.target.is_none() && !state.returning {
+            let mut closest: Option<(i32, i32)> = None;
+            self.spatial_hash.for_each_adjacent(pos, |(net_id, p)| {
+                let ppos = p.borrow().get_pos();
+                let dist = distance(pos, ppos);
+                if dist <= behavior.aggro_radius && distance(ppos, home) <= leash &&
+                    closest.is_none_or(|(_, closest_dist)| dist < closest_dist) &&
+                    self.col_array.has_line_of_sight(pos, ppos)
+                {
+                    closest = Some((*net_id, dist));
+                }
+            });
+            state.target = closest.map(|(net_id, _)| net_id);
+        }
+
+        let can_move = state.ticks_since_move >= behavior.move_ticks;
+        let speed = behavior.move_ticks;
+        let action = if let Some(target_net_id) = state.target {
+            let tpos = self.players[&target_net_id].borrow().get_pos();
+            if distance(pos, tpos) <= behavior.attack_range.max(1) && self.col_array.has_line_of_sight(pos, tpos) {
+                if state.attack_cooldown == 0 {
+                    state.attack_cooldown = behavior.attack_cooldown;
+                    NpcAction::Attack{target_net_id}
+                }
+                else {NpcAction::Idle}
+            }
+            else if can_move {
+                // Never stepping onto the player itself, nor past the leash
+                match self.col_array.next_step_towards(pos, tpos, |tile| distance(tile, home) <= leash && !self.entities.is_occupied(tile)) {
+                    Some((x, y)) if (x, y) != tpos => NpcAction::Step{x, y, speed},
+                    _ => NpcAction::Idle,
+                }
+            }
+            else {NpcAction::Idle}
+        }
+        else if state.returning {
+            if pos == home {
+                state.returning = false;
+                state.hp = behavior.max_hp;
+                NpcAction::Idle
+            }
+            else if can_move {
+                match self.col_array.next_step_towards(pos, home, |tile| !self.entities.is_occupied(tile)) {
+                    Some((x, y)) => NpcAction::Step{x, y, speed},
+                    // Stuck, so giving up on walking
+                    None => NpcAction::GoHome,
+                }
+            }
+            else {NpcAction::Idle}
+        }
+        else {
+            state.ticks_till_wander -= 1;
+            if state.ticks_till_wander <= 0 && behavior.wander_radius > 0 {
+                state.ticks_till_wander = self.rng.range(15, 60);
+                let next = (pos.0 + self.rng.range(-1, 1), pos.1 + self.rng.range(-1, 1));
+                if next != pos && distance(next, home) <= behavior.wander_radius && self.check_entity_destination(pos, next.0, next.1).is_none() {
+                    NpcAction::Step{x: next.0, y: next.1, speed}
+                }
+                else {NpcAction::Idle}
+            }
+            else {NpcAction::Idle}
+        };
+        let entity_id = e.entity_id;
+        drop(e);
+
+        match action {
+            NpcAction::Attack{target_net_id} => {
+                let target = self.players[&target_net_id].clone();
+                let mut t = target.borrow_mut();
+                let damage = roll_damage(behavior.attack_level, behavior.damage, t.data.defence_level(), &mut self.rng);
+                let died = t.data.take_damage(damage);
+                t.set_private_change();
+                self.broadcast_hit(Combatant::Entity{entity_id}, Combatant::Player{pid: t.pid()}, damage, t.data.hp, t.data.max_hp(), t.get_pos());
+                drop(t);
+
+                if died {
+                    if let Some(state) = entity.bind_mut().npc_state.as_mut() {
+                        state.target = None;
+                        state.returning = true;
+                    }
+                    self.kill_player(target_net_id);
+                }
+            },
+            NpcAction::Step{x, y, speed} => {
+                if let Some(state) = entity.bind_mut().npc_state.as_mut() {
+                    state.ticks_since_move = 0;
+                }
+                self.move_entity(&mut entity, x, y, speed);
+            },
+            NpcAction::GoHome => {
+                if let Some(state) = entity.bind_mut().npc_state.as_mut() {
+                    state.ticks_since_move = 0;
+                }
+                // Still returning if something stands at home, so trying again on the next move
+                self.teleport_npc_home(&mut entity, home);
+            },
+            NpcAction::Idle => {},
+        }
+    }
+
+    pub(super) fn handle_attack_entity(&mut self, x: i32, y: i32, entity_id: i32, net_id: i32) {
+        let Some(entity) = self.entities.get_at(x, y, entity_id) else {return};
+        let mut entity = entity.clone();
+        let Some(behavior) = entity.bind().npc.clone() else {return};
+        let behavior = behavior.bind();
+        let Some(attacker) = self.players.get(&net_id) else {return};
+
+        let mut a = attacker.borrow_mut();
+        if a.attack_cooldown > 0 {
+            return;
+        }
+        let style = a.data.attack_style();
+        if !self.check_attack_reach(a.get_pos(), (x, y), style.range(), net_id) {
+            return;
+        }
+
+        // Attacking cancels the action in progress, like interacting with another entity
+        if a.action.take().is_some() {
+            Self::send_action_progress(&self.equeue, 0, 0, net_id);
+        }
+        a.attack_cooldown = ATTACK_COOLDOWN_TICKS;
+        let damage = a.data.roll_attack(behavior.defence_level, &mut self.rng);
+        Self::give_combat_xp(&mut a, style.skill(), damage, net_id, &mut self.deferred_level_ups);
+
+        let mut e = entity.bind_mut();
+        let Some(state) = e.npc_state.as_mut() else {return};
+        state.hp = (state.hp - damage.max(0)).max(0);
+        // Fighting back, unless already busy with someone else
+        if state.target.is_none() {
+            state.target = Some(net_id);
+            state.returning = false;
+        }
+        let hp = state.hp;
+        let home = state.home;
+        drop(e);
+        self.broadcast_hit(Combatant::Player{pid: a.pid()}, Combatant::Entity{entity_id}, damage, hp, behavior.max_hp, (x, y));
+
+        if hp == 0 {
+            let container = PlayerContainer::from_data(a.data.clone());
+            drop(a);
+            let responses = GenericScriptedEntity::on_npc_death(entity.clone(), container, net_id);
+            for response in responses.iter_shared() {
+                self.deferred_responses.push((entity.clone(), response));
+            }
+
+            // Starting over at home, or walking back there first if something stands on it
+            let at_home = self.teleport_npc_home(&mut entity, home);
+            if let Some(state) = entity.bind_mut().npc_state.as_mut() {
+                *state = NpcState::new(&behavior, home);
+                state.returning = !at_home;
+            }
+        }
+    }
+
+    /// Teleports the NPC home, returns false and leaves it in place if home is blocked or occupied
+    fn teleport_npc_home(&mut self, entity: &mut Gd<GenericScriptedEntity>, home: (i32, i32)) -> bool {
+        let pos = entity.bind().pos;
+        if self.check_entity_destination((pos.x, pos.y), home.0, home.1).is_some() {
+            return false;
+        }
+        self.teleport_entity(entity, home.0, home.1);
+        true
+    }
+
+    /// Sends the attacker a chat message and returns false if the target is out of range or sight
+    pub(super) fn check_attack_reach(&self, from: (i32, i32), to: (i32, i32), range: i32, net_id: i32) -> bool {
+        if distance(from, to) > range {
+            self.equeue.push_server(
+                ServerEvent::PlayerChat{text: "You're too far away to attack them.".into(), from: "".into(), from_pid: -1, is_dm: false, net_id}
+            );
+            return false;
+        }
+        if !self.col_array.has_line_of_sight(from, to) {
+            self.equeue.push_server(
+                ServerEvent::PlayerChat{text: "You can't see them from there.".into(), from: "".into(), from_pid: -1, is_dm: false, net_id}
+            );
+            return false;
+        }
+        true
+    }
+
+    /// Sends the hit to everyone around the target's position
+    pub(super) fn broadcast_hit(&self, attacker: Combatant, target: Combatant, damage: i32, hp: i32, max_hp: i32, pos: (i32, i32)) {
+        let response = GenericServerResponse::Hit{attacker, target, damage, hp, max_hp}.to_bytearray();
+        self.spatial_hash.for_each_adjacent(pos, |adjacent| {
+            self.equeue.push_server(ServerEvent::GenericResponse{response: response.clone(), net_id: adjacent.0});
+        });
+    }
+}
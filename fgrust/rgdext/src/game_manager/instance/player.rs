@@ -1,12 +1,46 @@
 use std::{cell::RefCell, rc::Rc};
 
-use rgdext_shared::playerdata::PlayerData;
+use godot::prelude::*;
+use rgdext_shared::playerdata::{combat::HP_REGEN_TICKS, dialogue::Dialogue, PlayerData};
 
+use super::entity::{GenericScriptedEntity, ScriptResponse};
+
+
+/// Action that takes a number of ticks to complete, e.g. cutting down a tree
+pub struct Action {
+    /// Entity that started the action, gets its on_action_completed hook called on completion
+    pub entity: Gd<GenericScriptedEntity>,
+    pub ticks_done: i32,
+    pub ticks_total: i32,
+    /// Responses handled as if sent by the entity once the action completes
+    pub on_complete: Vec<Gd<ScriptResponse>>,
+}
+
+/// Dialogue the player has open with an entity
+pub struct OpenDialogue {
+    /// Entity that opened the dialogue, gets its on_dialogue_choice hook called on choices
+    pub entity: Gd<GenericScriptedEntity>,
+    pub dialogue: Dialogue,
+    /// id of the node currently shown
+    pub node: String,
+    /// Indices of the node's options shown to the player, in the order they were shown
+    pub shown: Vec<usize>,
+}
 
 pub struct Player {
     pub ticks_since_move: i32,
     pub data_just_updated: bool,
     pub private_data_just_updated: bool,
+    /// Whether the player currently has the bank open, closed on moving
+    pub bank_open: bool,
+    /// Action in progress, cancelled on moving or interacting with another entity
+    pub action: Option<Action>,
+    /// Dialogue in progress, closed on moving
+    pub dialogue: Option<OpenDialogue>,
+    /// Ticks until the player can attack again
+    pub attack_cooldown: i32,
+    /// Ticks until the next hp regenerates
+    pub regen_ticks: i32,
     speed: i32,
     // These two essentially make a 2-move buffer
     nextmove: Option<(i32, i32, i32)>,
@@ -42,6 +76,11 @@ impl Player {
         self.nextmove = self.nextnextmove.take();
     }
 
+    pub fn clear_moves(&mut self) {
+        self.nextmove = None;
+        self.nextnextmove = None;
+    }
+
     pub fn get_full_pos(&self) -> (i32, i32, i32) {
         let b = &self.data;
         (b.x, b.y, self.speed)
@@ -94,6 +133,11 @@ impl Player {
                 data_version: 0,
                 data_just_updated: false,
                 private_data_just_updated: false,
+                bank_open: false,
+                action: None,
+                dialogue: None,
+                attack_cooldown: 0,
+                regen_ticks: HP_REGEN_TICKS,
             }
         ))
     }
@@ -0,0 +1,74 @@
+use godot::prelude::*;
+
+use super::entity::GenericScriptedEntity;
+
+struct Timer {
+    entity: Gd<GenericScriptedEntity>,
+    name: GString,
+    ticks_left: i32,
+    /// Ticks between firings of a repeating timer, None for one-shot timers
+    interval: Option<i32>,
+    pause_when_empty: bool,
+}
+
+/// Named timers of scripted entities, counted in instance ticks.
+/// An entity has at most one timer of each name, starting it again restarts it.
+#[derive(Default)]
+pub struct Scheduler {
+    timers: Vec<Timer>,
+}
+
+impl Scheduler {
+    pub fn start(&mut self, entity: Gd<GenericScriptedEntity>, name: GString, ticks: i32, repeating: bool, pause_when_empty: bool) {
+        self.cancel(entity.instance_id(), &name);
+        let ticks = ticks.max(1);
+        self.timers.push(Timer{
+            entity,
+            name,
+            ticks_left: ticks,
+            interval: repeating.then_some(ticks),
+            pause_when_empty,
+        });
+    }
+
+    pub fn cancel(&mut self, entity: InstanceId, name: &GString) {
+        self.timers.retain(|timer| timer.entity.instance_id() != entity || &timer.name != name);
+    }
+
+    /// Drops all timers of the entity, for when it despawns
+    pub fn cancel_all(&mut self, entity: InstanceId) {
+        self.timers.retain(|timer| timer.entity.instance_id() != entity);
+    }
+
+    pub fn clear(&mut self) {
+        self.timers.clear();
+    }
+
+    /// Counts down the timers, returning the ones that fired in the order they were started.
+    /// Timers that pause when empty don't count down while the instance has no players.
+    pub fn tick(&mut self, instance_empty: bool) -> Vec<(Gd<GenericScriptedEntity>, GString)> {
+        let mut fired = Vec::new();
+        self.timers.retain_mut(|timer| {
+            // The entity may have been freed without despawning through a response
+            if !timer.entity.is_instance_valid() {
+                return false;
+            }
+            if instance_empty && timer.pause_when_empty {
+                return true;
+            }
+            timer.ticks_left -= 1;
+            if timer.ticks_left > 0 {
+                return true;
+            }
+            fired.push((timer.entity.clone(), timer.name.clone()));
+            match timer.interval {
+                Some(interval) => {
+                    timer.ticks_left = interval;
+                    true
+                },
+                None => false,
+            }
+        });
+        fired
+    }
+}
@@ -11,7 +11,7 @@ const PLAYER_DATA_TIMEOUT: f64 = 45.;
 const PLAYER_SAVE_TIMEOUT: f64 = 90.;
 
 pub enum PlayerDataEntry {
-    RawData{data: PlayerData, age: f64},
+    RawData{data: Box<PlayerData>, age: f64},
     ActivePlayer{player: Rc<RefCell<Player>>, net_id: i32, age: f64}
 }
 
@@ -21,7 +21,7 @@ impl PlayerDataEntry {
     }
 
     pub fn new_inactive(data: PlayerData) -> Self {
-        PlayerDataEntry::RawData{data, age: 0.}
+        PlayerDataEntry::RawData{data: Box::new(data), age: 0.}
     }
 
     /// Returns true on timeout
@@ -41,14 +41,11 @@ impl PlayerDataEntry {
                 }
             },
         }
-        return DataTickResult::Idle;
+        DataTickResult::Idle
     }
 
     pub fn is_active(&self) -> bool {
-        match self {
-            PlayerDataEntry::ActivePlayer{player: _, net_id: _, age: _} => true,
-            _ => false,
-        }
+        matches!(self, PlayerDataEntry::ActivePlayer{..})
     }
 
     /// Returns none if player not online on this server
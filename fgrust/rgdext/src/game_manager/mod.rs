@@ -1,7 +1,7 @@
 use std::{cell::RefCell, collections::{BTreeMap, HashMap}, rc::Rc, sync::{atomic::{AtomicBool, Ordering}, Arc}};
 
 use godot::prelude::*;
-use rgdext_shared::{genericevent::{GenericPlayerEvent, GenericServerResponse}, playerdata::PlayerData};
+use rgdext_shared::{genericevent::{GenericPlayerEvent, GenericServerResponse}, playerdata::{itemregistry, quest, recipe, skillregistry, PlayerData}};
 
 use crate::eventqueue::{EQueue, GameEvent, ServerEvent};
 use instance::{player::Player, Instance};
@@ -11,6 +11,14 @@ mod instance;
 mod misc;
 
 const FRIEND_REQUEST_TIMEOUT: f64 = 120.;
+/// Directory with the ItemResource definitions of all items
+const ITEMS_DIR: &str = "res://items";
+/// Config file with the definitions of all skills
+const SKILLS_FILE: &str = "res://skills.cfg";
+/// Directory with the RecipeResource definitions of all crafting recipes
+const RECIPES_DIR: &str = "res://recipes";
+/// Directory with the QuestResource definitions of all quests
+const QUESTS_DIR: &str = "res://quests";
 
 #[derive(GodotClass)]
 #[class(base=Node)]
@@ -26,6 +34,11 @@ pub struct GameManager {
 
     /// mapname -> list of instances
     instances: HashMap<String, Vec<Gd<instance::Instance>>>,
+    /// Private instances of all maps, closed once they've been empty for their map's lifetime
+    private_instances: Vec<Gd<instance::Instance>>,
+    /// Instance key -> entity node path -> persistent entity state, loaded from the database on connecting
+    /// and updated whenever a public instance unloads
+    entity_states: HashMap<String, Dictionary>,
 
     /// pid -> Vec<net_id>
     /// 
@@ -52,6 +65,8 @@ impl INode for GameManager {
             player_locations: HashMap::new(),
             player_datas: HashMap::new(),
             instances: HashMap::new(),
+            private_instances: Vec::new(),
+            entity_states: HashMap::new(),
 
             datagets: BTreeMap::new(),
             full_datagets: BTreeMap::new(),
@@ -70,6 +85,15 @@ impl INode for GameManager {
             got_sigint_clone.store(true, Ordering::Relaxed);
         }).unwrap();
 
+        let item_count = itemregistry::load_directory(ITEMS_DIR);
+        godot_print!("Loaded {} item definitions.", item_count);
+        let skill_count = skillregistry::load_file(SKILLS_FILE);
+        godot_print!("Loaded {} skill definitions.", skill_count);
+        let recipe_count = recipe::load_directory(RECIPES_DIR);
+        godot_print!("Loaded {} crafting recipes.", recipe_count);
+        let quest_count = quest::load_directory(QUESTS_DIR);
+        godot_print!("Loaded {} quests.", quest_count);
+
         let mut db_server: Gd<Node> = self.base().get_node_as("/root/DbServer");
         db_server.connect("retrieved", &Callable::from_object_method(&self.to_gd(), "_on_db_retrieved"));
         db_server.connect("request_save", &Callable::from_object_method(&self.to_gd(), "_on_save_request"));
@@ -77,6 +101,10 @@ impl INode for GameManager {
         self.base_mut().connect("save", &Callable::from_object_method(&db_server, "save"));
         self.base_mut().connect("retrieve", &Callable::from_object_method(&db_server, "retrieve"));
         self.base_mut().connect("relay_dm", &Callable::from_object_method(&db_server, "relay_dm"));
+        db_server.connect("connected", &Callable::from_object_method(&self.to_gd(), "_on_db_connected"));
+        db_server.connect("entity_states_retrieved", &Callable::from_object_method(&self.to_gd(), "_on_entity_states_retrieved"));
+        self.base_mut().connect("save_entity_states", &Callable::from_object_method(&db_server, "save_entity_states"));
+        self.base_mut().connect("retrieve_entity_states", &Callable::from_object_method(&db_server, "retrieve_entity_states"));
 
         godot_print!("Game manager node ready.\n");
     }
@@ -114,6 +142,22 @@ impl INode for GameManager {
             self.save_dataentry(pid_to_save);
         }
 
+        // Unloading instances that have been empty for long enough, public ones get started again on the next join
+        let mut idle = Vec::new();
+        for instances in self.instances.values_mut().chain(std::iter::once(&mut self.private_instances)) {
+            instances.retain_mut(|instance| {
+                let is_idle = instance.bind_mut().tick_idle(delta);
+                if is_idle {
+                    idle.push(instance.clone());
+                }
+                !is_idle
+            });
+        }
+        self.instances.retain(|_, instances| !instances.is_empty());
+        for instance in idle {
+            self.unload_instance(instance);
+        }
+
         // Ticking and timeouting friend invites
         self.friend_invites.retain(|_pids, time| {
             *time += delta;
@@ -126,6 +170,9 @@ impl INode for GameManager {
                 GameEvent::PlayerJoined{net_id, pid} => self.player_joined(net_id, pid),
                 GameEvent::PlayerDisconnected{net_id} => self.player_despawn(net_id),
                 GameEvent::PlayerJoinInstance{mapname, x, y, net_id} => self.player_join_instance(&mapname, x, y, net_id),
+                GameEvent::PlayerJoinPrivateInstance{mapname, x, y, members, net_id} => {
+                    self.player_join_private_instance(&mapname, x, y, members, net_id);
+                },
                 GameEvent::PlayerChat{text, target_pid, net_id} => self.broadcast_chat(text, target_pid, net_id),
                 GameEvent::PlayerDm{text, from, from_pid, target_pid} => {
                     self.signals().relay_dm().emit(&text, &from, from_pid, target_pid);
@@ -149,6 +196,12 @@ impl GameManager {
     #[signal]
     fn relay_dm(text: GString, from: GString, from_pid: i32, target_pid: i32);
 
+    #[signal]
+    fn save_entity_states(key: GString, data: PackedByteArray);
+
+    #[signal]
+    fn retrieve_entity_states(prefix: GString);
+
     pub fn set_equeue(&mut self, e: EQueue) {
         self.equeue = e;
     }
@@ -161,16 +214,26 @@ impl GameManager {
     #[func]
     fn _on_save_request(&mut self, pid: i32) {
         self.save_dataentry(pid);
+
+        // Busy instances may not unload for a long time, so their entity states get saved along with the players in them
+        let net_id = self.player_datas.get(&pid).and_then(|dataentry| dataentry.get_net_id());
+        if let Some(instance) = net_id.and_then(|net_id| self.player_locations.get(&net_id)).cloned() {
+            let states = instance.bind().persistent_states();
+            self.save_instance_states(&instance, states);
+        }
     }
 
     /// Does unlocking saves for all playerdatas, consuming them, then disables process for itself and queue frees all instances
     fn full_save(&mut self) {
         self.player_locations.clear();
         for instances in std::mem::take(&mut self.instances).into_values() {
-            for mut instance in instances {
-                instance.queue_free();
+            for instance in instances {
+                self.unload_instance(instance);
             }
         }
+        for instance in std::mem::take(&mut self.private_instances) {
+            self.unload_instance(instance);
+        }
         
         for dataentry in std::mem::take(&mut self.player_datas).into_values() {
             if let PlayerDataEntry::ActivePlayer{player, net_id, age: _} = dataentry {
@@ -187,40 +250,54 @@ impl GameManager {
 
     #[func]
     fn _on_db_retrieved(&mut self, pid: i32, data: PackedByteArray) {
-        let data = PlayerData::from_bytes(data.as_slice());
+        let mut data = match PlayerData::from_bytes(data.as_slice()) {
+            Ok(data) => data,
+            Err(err) => {
+                godot_error!("Received invalid data for pid {pid}: {err}");
+                self.datagets.remove(&pid);
+                // Rejecting the login rather than starting the player over and overwriting their save
+                if let Some(net_id) = self.full_datagets.remove(&pid) {
+                    self.equeue.push_server(
+                        ServerEvent::PlayerChat{text: "Your player data couldn't be loaded.".into(), from: "".into(), from_pid: -1, is_dm: false, net_id}
+                    );
+                    self.equeue.push_server(ServerEvent::PlayerForceDisconnect{net_id});
+                }
+                return;
+            }
+        };
 
         if let Some(dataget) = self.datagets.remove(&pid) {
-            if let Ok(data) = &data {
-                let minimal_data = data.get_minimal().to_bytearray();
-            
-                for net_id in dataget {
-                    // Safe to clone PackedByteArray because it's CoW
-                    self.equeue.push_server(ServerEvent::PlayerDataResponse{data: minimal_data.clone(), net_id: net_id});
-                }
+            let minimal_data = data.get_minimal().to_bytearray();
+
+            for net_id in dataget {
+                // Safe to clone PackedByteArray because it's CoW
+                self.equeue.push_server(ServerEvent::PlayerDataResponse{data: minimal_data.clone(), net_id});
             }
-            
         }
 
         // This is where the player truly joins the server and the Player object is created
         let new_entry = if let Some(net_id) = self.full_datagets.remove(&pid) {
-            if let Ok(mut data) = data {
-                self.equeue.push_server(ServerEvent::PlayerDataResponse{data: data.to_bytearray(), net_id});
-            
-                let mut instance = self.get_instance(&data.location);
-                data.server_name = self.server_name.clone();
-                let player = Player::new_rc(data);
-                instance.bind_mut().spawn_player(player.clone(), net_id);
-                self.player_locations.insert(net_id, instance);
-    
-                PlayerDataEntry::new_active(player, net_id)
-            }
-            else {
-                self.equeue.push_server(ServerEvent::PlayerForceDisconnect{net_id});
-                panic!("Received invalid data for pid {pid}");
+            let unknown_items = data.unknown_item_ids();
+            if !unknown_items.is_empty() {
+                godot_warn!("Data for pid {pid} holds items missing from the item registry: {}", unknown_items.join(", "));
             }
+
+            self.equeue.push_server(ServerEvent::PlayerDataResponse{data: data.get_private().to_bytearray(), net_id});
+        
+            // Players who logged out in a private instance that's still open get back in
+            let mut instance = match self.find_private_instance(&data.location, pid) {
+                Some(instance) => instance,
+                None => self.get_instance(&data.location, &data.friends),
+            };
+            data.server_name = self.server_name.clone();
+            let player = Player::new_rc(data);
+            instance.bind_mut().spawn_player(player.clone(), net_id);
+            self.player_locations.insert(net_id, instance);
+
+            PlayerDataEntry::new_active(player, net_id)
         }
         else {
-            PlayerDataEntry::new_inactive(data.unwrap())
+            PlayerDataEntry::new_inactive(data)
         };
 
         // Store the data entry
@@ -234,12 +311,12 @@ impl GameManager {
 
     #[func]
     fn _on_dm_received(&self, text: GString, from: GString, from_pid: i32, target_pid: i32) {
-        if let Some(dataentry) = self.player_datas.get(&target_pid) {
-            if let PlayerDataEntry::ActivePlayer{player: _, net_id, age: _} = dataentry {
-                self.equeue.push_server(
-                    ServerEvent::PlayerChat{text, from, from_pid, is_dm: true, net_id: *net_id}
-                );
-            }
+        if let Some(dataentry) = self.player_datas.get(&target_pid)
+        && let PlayerDataEntry::ActivePlayer{player: _, net_id, age: _} = dataentry
+        {
+            self.equeue.push_server(
+                ServerEvent::PlayerChat{text, from, from_pid, is_dm: true, net_id: *net_id}
+            );
         }
     }
 
@@ -270,8 +347,8 @@ impl GameManager {
     fn player_retrieve_data(&mut self, pid: i32, net_id: i32) {
         if let Some(pdataentry) = self.player_datas.get(&pid) {
             let data = match pdataentry {
-                PlayerDataEntry::RawData{data, age: _} => data.to_bytearray(),
-                PlayerDataEntry::ActivePlayer{player, net_id: _, age: _} => player.borrow().data.to_bytearray(),
+                PlayerDataEntry::RawData{data, age: _} => data.get_private().to_bytearray(),
+                PlayerDataEntry::ActivePlayer{player, net_id: _, age: _} => player.borrow().data.get_private().to_bytearray(),
             };
             self.equeue.push_server(ServerEvent::PlayerDataResponse{data, net_id});
         }
@@ -296,8 +373,18 @@ impl GameManager {
 
     // Opens a new instance with the given mapname, adds it to instance repository, returns a pointer to it
     fn start_instance(&mut self, mapname: &str) -> Gd<Instance> {
-        let mut inst = instance::Instance::new(mapname, self.equeue.clone());
+        // Lowest channel number not in use, which closed instances may have freed up
+        let mut channel = 1;
+        if let Some(instances) = self.instances.get(mapname) {
+            while instances.iter().any(|i| i.bind().channel == channel) {
+                channel += 1;
+            }
+        }
+        let mut inst = instance::Instance::new(mapname, channel, self.equeue.clone());
         inst.bind_mut().mapname = mapname.to_string();
+        if let Some(states) = self.entity_states.get(&self.instance_key(mapname, channel)) {
+            inst.bind_mut().saved_states = states.clone();
+        }
         
         // Map is loaded on node's _ready
         self.base_mut().add_child(&inst);
@@ -311,7 +398,74 @@ impl GameManager {
             self.instances.insert(mapname.to_string(), vec![inst.clone()]);
         }
 
-        return inst;
+        inst
+    }
+
+    /// Frees the instance, saving the persistent entity states of public ones
+    fn unload_instance(&mut self, mut instance: Gd<Instance>) {
+        let mut b = instance.bind_mut();
+        godot_print!("Unloading instance {} of {}", b.channel, b.mapname);
+        let states = b.unload();
+        drop(b);
+        self.save_instance_states(&instance, states);
+        instance.queue_free();
+    }
+
+    /// Saves the persistent entity states of the instance if it's public, private instances being gone for good once closed
+    fn save_instance_states(&mut self, instance: &Gd<Instance>, states: Dictionary) {
+        let b = instance.bind();
+        if b.members.is_none() {
+            let key = self.instance_key(&b.mapname, b.channel);
+            let data = godot::global::var_to_bytes(&states.to_variant());
+            self.signals().save_entity_states().emit(&GString::from(&key), &data);
+            self.entity_states.insert(key, states);
+        }
+    }
+
+    /// Persistent entity states are saved under this key
+    fn instance_key(&self, mapname: &str, channel: i32) -> String {
+        format!("{}/{}/{}", self.server_name, mapname, channel)
+    }
+
+    #[func]
+    fn _on_db_connected(&mut self) {
+        let prefix = format!("{}/", self.server_name);
+        self.signals().retrieve_entity_states().emit(&GString::from(&prefix));
+    }
+
+    #[func]
+    /// states maps instance keys to the persistent states of its entities encoded with var_to_bytes
+    fn _on_entity_states_retrieved(&mut self, states: Dictionary) {
+        for (key, data) in states.iter_shared() {
+            let Ok(data) = data.try_to::<PackedByteArray>() else {continue};
+            match godot::global::bytes_to_var(&data).try_to::<Dictionary>() {
+                // Instances unloaded since starting up have newer states than the database had
+                Ok(instance_states) => {self.entity_states.entry(key.to_string()).or_insert(instance_states);},
+                Err(_) => godot_error!("Invalid persistent entity states for {}", key),
+            }
+        }
+        godot_print!("Loaded persistent entity states of {} instances.", self.entity_states.len());
+    }
+
+    /// Opens a private instance of the map that only the given pids can join
+    fn start_private_instance(&mut self, mapname: &str, members: Vec<i32>) -> Gd<Instance> {
+        let mut inst = instance::Instance::new(mapname, 0, self.equeue.clone());
+        inst.bind_mut().members = Some(members);
+
+        self.base_mut().add_child(&inst);
+        self.private_instances.push(inst.clone());
+
+        inst
+    }
+
+    /// Returns the open private instance of the map the pid is allowed in, if any
+    fn find_private_instance(&self, mapname: &str, pid: i32) -> Option<Gd<Instance>> {
+        self.private_instances.iter()
+            .find(|i| {
+                let b = i.bind();
+                b.mapname == mapname && b.allows(pid)
+            })
+            .cloned()
     }
 
     /// Directly tied to GameEvent::PlayerMove
@@ -344,44 +498,117 @@ impl GameManager {
             self.save_unlocking(player);
             
             self.player_locations.remove(&net_id);
-            if let Some(pdataentry) = self.player_datas.remove(&pid) {
-                if let PlayerDataEntry::ActivePlayer{player, net_id: _, age: _} = pdataentry {
-                    let data = match std::rc::Rc::try_unwrap(player) {
-                        Ok(p) => p.into_inner().into_data(),
-                        Err(rc) => {
-                            godot_error!("Couldn't unwrap player rc for {net_id}! refcount: {}", std::rc::Rc::strong_count(&rc));
-                            rc.borrow().data.clone()
-                        },
-                    };
-                    self.player_datas.insert(pid, PlayerDataEntry::new_inactive(data));
+            if let Some(pdataentry) = self.player_datas.remove(&pid)
+            && let PlayerDataEntry::ActivePlayer{player, net_id: _, age: _} = pdataentry
+            {
+                let data = match std::rc::Rc::try_unwrap(player) {
+                    Ok(p) => p.into_inner().into_data(),
+                    Err(rc) => {
+                        godot_error!("Couldn't unwrap player rc for {net_id}! refcount: {}", std::rc::Rc::strong_count(&rc));
+                        rc.borrow().data.clone()
+                    },
                 };
-            };
+                self.player_datas.insert(pid, PlayerDataEntry::new_inactive(data));
+            }
         }
     }
 
-    /// Gets the best instance for given map name.
+    /// Gets the best instance for given map name out of the ones that aren't full,
+    /// preferring the ones with the most friends in them and then the emptiest ones.
     /// 
     /// Will open a new instance if needed.
-    fn get_instance(&mut self, mapname: &str) -> Gd<Instance> {
+    fn get_instance(&mut self, mapname: &str, friends: &[i32]) -> Gd<Instance> {
         if let Some(instances) = self.instances.get(mapname) {
-            // Here is where checks whether an instance should be joinable should happen
-            // If no instances are valid, we start a new one
-            for instance in instances {
-                // This will just return the first open instance for now
+            let best = instances.iter()
+                .filter(|i| !i.bind().is_full())
+                .max_by(|a, b| {
+                    let (a, b) = (a.bind(), b.bind());
+                    a.count_present(friends).cmp(&b.count_present(friends))
+                        .then(b.fill_level().total_cmp(&a.fill_level()))
+                        .then(b.channel.cmp(&a.channel))
+                });
+            if let Some(instance) = best {
                 return instance.clone();
             }
         }
         
-        return self.start_instance(mapname);
+        self.start_instance(mapname)
+    }
+
+    /// Moves the player to the instance with the given channel on their current map, keeping their position.
+    /// Channel 0 only tells the player which channel they're on and which ones are open.
+    fn switch_channel(&mut self, channel: i32, net_id: i32) {
+        let Some(current) = self.player_locations.get(&net_id) else {return};
+        let (mapname, current_channel) = {
+            let b = current.bind();
+            if b.members.is_some() {
+                self.equeue.push_server(
+                    ServerEvent::PlayerChat{text: "There are no channels in private instances.".into(), from: "".into(), from_pid: -1, is_dm: false, net_id}
+                );
+                return;
+            }
+            (b.mapname.clone(), b.channel)
+        };
+        let instances = self.instances.get(&mapname).map_or(&[][..], |i| i.as_slice());
+        let target = instances.iter().find(|i| i.bind().channel == channel).cloned();
+        let text = if channel == 0 || channel == current_channel {
+            // Closed instances can leave gaps in the channel numbers
+            let mut channels: Vec<i32> = instances.iter().map(|i| i.bind().channel).collect();
+            channels.sort();
+            let channels: Vec<String> = channels.iter().map(|c| c.to_string()).collect();
+            format!("You're on channel {}, open channels are {}.", current_channel, channels.join(", "))
+        }
+        else {
+            match target {
+                None => format!("Channel {channel} doesn't exist."),
+                Some(target) if target.bind().is_full() => format!("Channel {channel} is full."),
+                Some(target) => {
+                    self.move_to_instance(target, None, net_id);
+                    format!("Switched to channel {channel}.")
+                },
+            }
+        };
+        self.equeue.push_server(
+            ServerEvent::PlayerChat{text: text.into(), from: "".into(), from_pid: -1, is_dm: false, net_id}
+        );
     }
 
     // Requires the player to already be in an instance
     fn player_join_instance(&mut self, mapname: &str, x: i32, y: i32, net_id: i32) {
-        let mut new_instance = self.get_instance(mapname);
-        
+        let Some(old_instance) = self.player_locations.get(&net_id) else {return};
+        // Staying on the same channel when moving within the map
+        let new_instance = if old_instance.bind().mapname == mapname {
+            old_instance.clone()
+        }
+        else {
+            let friends = self.get_net_id_playerdata(net_id).map(|p| p.borrow().data.friends.clone()).unwrap_or_default();
+            self.get_instance(mapname, &friends)
+        };
+        self.move_to_instance(new_instance, Some((x, y)), net_id);
+    }
+
+    /// Requires the player to already be in an instance
+    fn player_join_private_instance(&mut self, mapname: &str, x: i32, y: i32, mut members: Vec<i32>, net_id: i32) {
+        let Some(player) = self.get_net_id_playerdata(net_id) else {return};
+        let pid = player.borrow().pid();
+        let new_instance = match self.find_private_instance(mapname, pid) {
+            Some(instance) => instance,
+            None => {
+                if !members.contains(&pid) {
+                    members.push(pid);
+                }
+                self.start_private_instance(mapname, members)
+            },
+        };
+        self.move_to_instance(new_instance, Some((x, y)), net_id);
+    }
+
+    /// Moves the player from their current instance to the given one, to the given position or keeping their own
+    fn move_to_instance(&mut self, mut new_instance: Gd<Instance>, pos: Option<(i32, i32)>, net_id: i32) {
         self.player_locations.entry(net_id).and_modify(|old_instance| {
             let player = old_instance.bind_mut().despawn_player(net_id);
             let mut b = player.borrow_mut();
+            let (x, y) = pos.unwrap_or(b.get_pos());
             b.set_full_pos(x, y, 0);
             drop(b);
             new_instance.bind_mut().spawn_player(player, net_id);
@@ -446,6 +673,9 @@ impl GameManager {
                     }
                 }
             },
+            GenericPlayerEvent::SwitchChannel{channel} => {
+                self.switch_channel(*channel, from_net_id);
+            },
             _ => {
                 if let Some(instance) = self.player_locations.get_mut(&from_net_id) {
                     instance.bind_mut().handle_generic_event(event, from_net_id);
@@ -71,7 +71,7 @@ impl INode for Server {
                 godot_print!("Token for pid {} timed out", pending_token.1);
                 return false;
             }
-            return true;
+            true
         });
 
         // let mut equeue = self.get_queue_mut();
@@ -179,9 +179,9 @@ impl Server {
 
     #[func]
     fn peer_disconnected(&mut self, net_id: i32) {
-        {self.equeue.push_game(
+        self.equeue.push_game(
             GameEvent::PlayerDisconnected{net_id}
-        )};
+        );
 
         self.current_players -= 1;
         godot_print!("disconnected: {net_id}");
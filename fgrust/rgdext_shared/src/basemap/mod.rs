@@ -1,12 +1,37 @@
+use std::collections::{HashMap, VecDeque};
+
 use godot::{classes::TileMapLayer, prelude::*};
 use bitcode::{Encode, Decode};
 
 pub mod spatialhash;
 
+/// Players per instance of a map unless the map sets its own capacity
+pub const DEFAULT_INSTANCE_CAPACITY: i32 = 50;
+/// Seconds a public instance of a map stays loaded after its last player leaves, unless the map sets its own
+pub const DEFAULT_IDLE_TIMEOUT: f64 = 60.;
+/// Seconds a private instance of a map stays open after its last player leaves, unless the map sets its own
+pub const DEFAULT_PRIVATE_LIFETIME: f64 = 300.;
+/// Max amount of tiles searched by [CollisionArray::next_step_towards]
+pub const MAX_PATH_SEARCH: usize = 1024;
+/// Orthogonal steps first so that straight paths are preferred
+const STEPS: [(i32, i32); 8] = [(0, -1), (1, 0), (0, 1), (-1, 0), (1, -1), (1, 1), (-1, 1), (-1, -1)];
+
 #[derive(GodotClass)]
 #[class(tool, base=Node2D)]
 pub struct BaseMap {
     col_array: CollisionArray,
+    #[export]
+    /// Tile where players who die on this map respawn, stored as metadata of the server's Entities node on export
+    respawn_point: Vector2i,
+    #[export]
+    /// Max players per instance of this map before more get opened, stored as metadata like respawn_point
+    capacity: i32,
+    #[export]
+    /// Seconds public instances of this map stay loaded after emptying, stored as metadata like respawn_point
+    idle_timeout: f64,
+    #[export]
+    /// Seconds private instances of this map stay open after emptying, stored as metadata like respawn_point
+    private_lifetime: f64,
     
     base: Base<Node2D>
 }
@@ -16,6 +41,10 @@ impl INode2D for BaseMap {
     fn init(base: Base<Node2D>) -> Self {
         Self {
             col_array: CollisionArray::new(),
+            respawn_point: Vector2i::ZERO,
+            capacity: DEFAULT_INSTANCE_CAPACITY,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            private_lifetime: DEFAULT_PRIVATE_LIFETIME,
             
             base
         }
@@ -84,6 +113,12 @@ pub struct CollisionArray {
     mapsize: i32,
 }
 
+impl Default for CollisionArray {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CollisionArray {
     pub fn new() -> Self {
         Self {
@@ -120,8 +155,7 @@ impl CollisionArray {
         let height = rect.size.y + 1;
         let mapsize = width * height;
 
-        let mut map = Vec::with_capacity(mapsize as usize);
-        for _ in 0..mapsize as usize {map.push(false);}
+        let map = vec![false; mapsize as usize];
 
         Self {
             map,
@@ -150,7 +184,12 @@ impl CollisionArray {
 
     fn to_index(&self, mut x: i32, mut y: i32) -> i32 {
         x -= self.topleftx; y -= self.toplefty;
-        return x + y*self.width;
+        x + y*self.width
+    }
+
+    /// Whether the tile is inside the map's rectangle, unlike [CollisionArray::get_at] which only checks the index
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= self.topleftx && y >= self.toplefty && x < self.topleftx + self.width && y < self.toplefty + self.height
     }
 
     pub fn get_at(&self, x: i32, y: i32) -> bool {
@@ -161,11 +200,116 @@ impl CollisionArray {
         else {self.map[at as usize]}
     }
 
+    /// Returns true if no tile strictly between the two points blocks the straight line between them
+    pub fn has_line_of_sight(&self, from: (i32, i32), to: (i32, i32)) -> bool {
+        // Bresenham's line, skipping both ends
+        let (dx, dy) = ((to.0 - from.0).abs(), -(to.1 - from.1).abs());
+        let (sx, sy) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
+        let (mut x, mut y) = from;
+        let mut err = dx + dy;
+        loop {
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+            if (x, y) == to || (x, y) == from {
+                return true;
+            }
+            if self.get_at(x, y) {
+                return false;
+            }
+        }
+    }
+
+    /// First step of a shortest 8-directional path between the tiles, only going through free tiles that are allowed.
+    /// The goal tile itself only has to be allowed.
+    /// 
+    /// Returns None if already at the goal or no path was found within [MAX_PATH_SEARCH] tiles.
+    pub fn next_step_towards(&self, from: (i32, i32), to: (i32, i32), allowed: impl Fn((i32, i32)) -> bool) -> Option<(i32, i32)> {
+        if from == to {
+            return None;
+        }
+        // Tile -> first step of the path that reached it
+        let mut first_steps = HashMap::from([(from, from)]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(tile) = queue.pop_front() {
+            for (dx, dy) in STEPS {
+                let next = (tile.0 + dx, tile.1 + dy);
+                if first_steps.contains_key(&next) || !allowed(next) {
+                    continue;
+                }
+                let first = if tile == from {next} else {first_steps[&tile]};
+                if next == to {
+                    return Some(first);
+                }
+                if self.get_at(next.0, next.1) {
+                    continue;
+                }
+                first_steps.insert(next, first);
+                if first_steps.len() >= MAX_PATH_SEARCH {
+                    return None;
+                }
+                queue.push_back(next);
+            }
+        }
+        None
+    }
+
     pub fn set_at(&mut self, x: i32, y: i32, to: bool) {
         let at = self.to_index(x, y);
-        if at < 0 || at >= self.mapsize {
-            return;
+        if (0..self.mapsize).contains(&at) {
+            self.map[at as usize] = to;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_of_sight() {
+        let mut col_array = CollisionArray::from_used_rect(&Rect2i::new(Vector2i::new(0, 0), Vector2i::new(9, 9)));
+        for y in 0..5 {
+            col_array.set_at(3, y, true);
+        }
+
+        assert!(!col_array.has_line_of_sight((0, 0), (5, 2)));
+        assert!(!col_array.has_line_of_sight((2, 0), (4, 0)));
+        assert!(col_array.has_line_of_sight((0, 6), (6, 7)));
+        // The ends themselves don't block
+        assert!(col_array.has_line_of_sight((2, 0), (3, 0)));
+        assert!(col_array.has_line_of_sight((3, 3), (3, 3)));
+    }
+
+    #[test]
+    fn test_next_step_towards() {
+        let mut col_array = CollisionArray::from_used_rect(&Rect2i::new(Vector2i::new(0, 0), Vector2i::new(9, 9)));
+        for y in 0..9 {
+            col_array.set_at(3, y, true);
         }
-        else {self.map[at as usize] = to;}
+
+        assert_eq!(col_array.next_step_towards((0, 0), (2, 0), |_| true), Some((1, 0)));
+        assert_eq!(col_array.next_step_towards((0, 0), (0, 0), |_| true), None);
+        // Diagonally around the end of the wall
+        assert_eq!(col_array.next_step_towards((2, 8), (4, 8), |_| true), Some((3, 9)));
+        assert_eq!(col_array.next_step_towards((2, 8), (4, 8), |(_, y)| y < 9), None);
+    }
+
+    #[test]
+    fn test_in_bounds() {
+        let col_array = CollisionArray::from_used_rect(&Rect2i::new(Vector2i::new(-2, 0), Vector2i::new(4, 4)));
+
+        assert!(col_array.in_bounds(-2, 0));
+        assert!(col_array.in_bounds(2, 4));
+        assert!(!col_array.in_bounds(3, 0));
+        assert!(!col_array.in_bounds(0, -1));
+        // Would wrap around to the next row by index alone
+        assert!(!col_array.in_bounds(3, 1));
     }
 }
@@ -79,7 +79,7 @@ impl<I: Eq + Copy, T> SpatialHash<I, T> {
             return None;
         }
 
-        return Some(xpos + ypos * self.width);
+        Some(xpos + ypos * self.width)
     }
 
     fn pos_to_index(&self, pos: (i32, i32)) -> Option<usize> {
@@ -93,7 +93,7 @@ impl<I: Eq + Copy, T> SpatialHash<I, T> {
         }
     }
 
-    pub fn for_each_adjacent<F: FnMut(&(I, T)) -> ()>(&self, pos: (i32, i32), mut closure: F) {
+    pub fn for_each_adjacent<F: FnMut(&(I, T))>(&self, pos: (i32, i32), mut closure: F) {
         let smallpos = self.get_smallpos(pos);
 
         for xdelta in -self.check_radius..=self.check_radius {
@@ -119,10 +119,10 @@ impl<I: Eq + Copy, T> SpatialHash<I, T> {
                 return Some(objects.remove(found_i));
             }
         }
-        return None;
+        None
     }
 
-    pub fn update_pos<'a>(&'a mut self, id: I, oldpos: (i32, i32), newpos: (i32, i32)) -> MoveDelta<I> {
+    pub fn update_pos(&mut self, id: I, oldpos: (i32, i32), newpos: (i32, i32)) -> MoveDelta<I> {
         let oldsmallpos = self.get_smallpos(oldpos);
         let newsmallpos = self.get_smallpos(newpos);
 
@@ -131,15 +131,15 @@ impl<I: Eq + Copy, T> SpatialHash<I, T> {
         let oldi_maybe = self.smallpos_to_index(oldsmallpos);
         let newi_maybe = self.smallpos_to_index(newsmallpos);
 
-        if let Some(oldi) = oldi_maybe && let Some(newi) = newi_maybe {
-            if let Some(remove_index) = self.map[oldi].iter().position(|_id| _id.0 == id) {
-                let object = self.map[oldi].remove(remove_index);
-                let id = object.0;
-                self.map[newi].push(object);
-                return MoveDelta::Delta{from: oldsmallpos, to: newsmallpos, check_radius: self.check_radius, exclude_id: id};
-            }
+        if let Some(oldi) = oldi_maybe && let Some(newi) = newi_maybe
+        && let Some(remove_index) = self.map[oldi].iter().position(|_id| _id.0 == id)
+        {
+            let object = self.map[oldi].remove(remove_index);
+            let id = object.0;
+            self.map[newi].push(object);
+            return MoveDelta::Delta{from: oldsmallpos, to: newsmallpos, check_radius: self.check_radius, exclude_id: id};
         }
-        return MoveDelta::NoMove;
+        MoveDelta::NoMove
     }
 
     pub fn get(&self, pos: (i32, i32), id: I) -> Option<&T> {
@@ -150,7 +150,7 @@ impl<I: Eq + Copy, T> SpatialHash<I, T> {
                 }
             }
         }
-        return None;
+        None
     }
 
     pub fn get_mut(&mut self, pos: (i32, i32), id: I) -> Option<&mut T> {
@@ -161,12 +161,12 @@ impl<I: Eq + Copy, T> SpatialHash<I, T> {
                 }
             }
         }
-        return None;
+        None
     }
 }
 
 impl<I: Eq + Copy> MoveDelta<I> {
-    pub fn for_each_with<'a, T, F: FnMut(&(I, T)) -> ()>(&self, hash: &'a SpatialHash<I, T>, mut closure: F) {
+    pub fn for_each_with<T, F: FnMut(&(I, T))>(&self, hash: &SpatialHash<I, T>, mut closure: F) {
         let (from, to, radius, exclude_id) = match self {
             MoveDelta::Delta{from, to, check_radius, exclude_id} => (*from, *to, *check_radius, *exclude_id),
             MoveDelta::NoMove => return,
@@ -176,12 +176,12 @@ impl<I: Eq + Copy> MoveDelta<I> {
             for ydelta in -radius..=radius {
                 let checkpos = (to.0 + xdelta, to.1 + ydelta);
                 let from_distance = from.0.abs_diff(checkpos.0).max(from.1.abs_diff(checkpos.1)) as i32;
-                if from_distance > radius {
-                    if let Some(index) = hash.smallpos_to_index(checkpos) {
-                        for o in hash.map[index].iter() {
-                            if o.0 != exclude_id {
-                                closure(o);
-                            }
+                if from_distance > radius
+                && let Some(index) = hash.smallpos_to_index(checkpos)
+                {
+                    for o in hash.map[index].iter() {
+                        if o.0 != exclude_id {
+                            closure(o);
                         }
                     }
                 }
@@ -1,6 +1,8 @@
 use bitcode::{Decode, Encode};
 use godot::prelude::*;
 
+use crate::playerdata::{equipment::EquipSlot, item::{Item, ItemResource}};
+
 
 #[derive(GodotClass)]
 #[class(no_init, base=RefCounted)]
@@ -36,6 +38,17 @@ impl GenericEvent {
         })
     }
 
+    #[func]
+    /// Moves the item in the given equipment slot back into the inventory.
+    /// 
+    /// Returns null if the slot name is invalid.
+    fn unequip_item(slot: GString) -> Option<Gd<Self>> {
+        let slot = EquipSlot::try_from_str(&slot.to_string())?;
+        Some(Gd::from_init_fn(|base| {
+            Self{event: GenericPlayerEvent::UnequipItem{slot}, base}
+        }))
+    }
+
     #[func]
     fn split_item(from_index: i32, to_index: i32, new_count: i32) -> Gd<Self> {
         Gd::from_init_fn(|base| {
@@ -57,6 +70,83 @@ impl GenericEvent {
         })
     }
 
+    #[func]
+    /// Only works while the bank is open
+    fn deposit_item(from_index: i32) -> Gd<Self> {
+        Gd::from_init_fn(|base| {
+            Self{event: GenericPlayerEvent::DepositItem{from: from_index as usize}, base}
+        })
+    }
+
+    #[func]
+    /// Only works while the bank is open, from_index is the bank slot
+    fn withdraw_item(from_index: i32) -> Gd<Self> {
+        Gd::from_init_fn(|base| {
+            Self{event: GenericPlayerEvent::WithdrawItem{from: from_index as usize}, base}
+        })
+    }
+
+    #[func]
+    fn sort_bank() -> Gd<Self> {
+        Gd::from_init_fn(|base| {
+            Self{event: GenericPlayerEvent::SortBank, base}
+        })
+    }
+
+    #[func]
+    fn close_bank() -> Gd<Self> {
+        Gd::from_init_fn(|base| {
+            Self{event: GenericPlayerEvent::CloseBank, base}
+        })
+    }
+
+    #[func]
+    /// Crafts the recipe with the given id, the server checks for a nearby station if the recipe needs one
+    fn craft(recipe_id: GString) -> Gd<Self> {
+        Gd::from_init_fn(|base| {
+            Self{event: GenericPlayerEvent::Craft{recipe_id: recipe_id.to_string()}, base}
+        })
+    }
+
+    #[func]
+    /// Attacks the player with the given pid, using the attack style of the equipped main hand item
+    fn attack(target_pid: i32) -> Gd<Self> {
+        Gd::from_init_fn(|base| {
+            Self{event: GenericPlayerEvent::Attack{target_pid}, base}
+        })
+    }
+
+    #[func]
+    /// Attacks the hostile NPC entity at the given position
+    fn attack_entity(x: i32, y: i32, entity_id: i32) -> Gd<Self> {
+        Gd::from_init_fn(|base| {
+            Self{event: GenericPlayerEvent::AttackEntity{x, y, entity_id}, base}
+        })
+    }
+
+    #[func]
+    /// Moves to another instance of the same map, keeping the position. Channel 0 just asks which one the player is on.
+    fn switch_channel(channel: i32) -> Gd<Self> {
+        Gd::from_init_fn(|base| {
+            Self{event: GenericPlayerEvent::SwitchChannel{channel}, base}
+        })
+    }
+
+    #[func]
+    /// Chooses an option of the open dialogue, index being the position among the options shown
+    fn choose_dialogue_option(index: i32) -> Gd<Self> {
+        Gd::from_init_fn(|base| {
+            Self{event: GenericPlayerEvent::ChooseDialogueOption{index: index as usize}, base}
+        })
+    }
+
+    #[func]
+    fn close_dialogue() -> Gd<Self> {
+        Gd::from_init_fn(|base| {
+            Self{event: GenericPlayerEvent::CloseDialogue, base}
+        })
+    }
+
     #[func]
     fn friend_request(pid: i32) -> Gd<Self> {
         Gd::from_init_fn(|base| {
@@ -82,9 +172,21 @@ pub enum GenericPlayerEvent {
     Interaction{x: i32, y: i32, entity_id: i32},
     SwapItems{from: usize, to: usize},
     EquipItem{from: usize},
+    UnequipItem{slot: EquipSlot},
     SplitItem{from: usize, to: usize, new_count: i32},
     DropItem{from: usize},
     DropGold{count: i32},
+    DepositItem{from: usize},
+    WithdrawItem{from: usize},
+    SortBank,
+    CloseBank,
+    Craft{recipe_id: String},
+    Attack{target_pid: i32},
+    AttackEntity{x: i32, y: i32, entity_id: i32},
+    /// Channel 0 just asks for the current channel
+    SwitchChannel{channel: i32},
+    ChooseDialogueOption{index: usize},
+    CloseDialogue,
     FriendRequest{pid: i32},
     FriendAccept{pid: i32},
     Err
@@ -140,6 +242,24 @@ impl GenericResponse {
     const RESPONSE_DESPAWN_PLAYER: i32 = 2;
     #[constant]
     const RESPONSE_DATA_UPDATE: i32 = 3;
+    #[constant]
+    const RESPONSE_BANK_CONTENTS: i32 = 4;
+    #[constant]
+    const RESPONSE_BANK_CLOSED: i32 = 5;
+    #[constant]
+    const RESPONSE_LEVEL_UP: i32 = 6;
+    #[constant]
+    const RESPONSE_ACTION_PROGRESS: i32 = 7;
+    #[constant]
+    const RESPONSE_HIT: i32 = 8;
+    #[constant]
+    const RESPONSE_DEATH: i32 = 9;
+    #[constant]
+    const RESPONSE_SET_COLLISION: i32 = 10;
+    #[constant]
+    const RESPONSE_DIALOGUE: i32 = 11;
+    #[constant]
+    const RESPONSE_DIALOGUE_CLOSED: i32 = 12;
 
     #[func]
     pub fn response_type(&self) -> i32 {
@@ -148,6 +268,15 @@ impl GenericResponse {
             GenericServerResponse::GotFriendRequest{pid: _, name: _} => Self::RESPONSE_GOT_FRIEND_REQUEST,
             GenericServerResponse::DespawnPlayer{pid: _} => Self::RESPONSE_DESPAWN_PLAYER,
             GenericServerResponse::DataUpdate{pid: _, data_version: _} => Self::RESPONSE_DATA_UPDATE,
+            GenericServerResponse::BankContents{items: _} => Self::RESPONSE_BANK_CONTENTS,
+            GenericServerResponse::BankClosed => Self::RESPONSE_BANK_CLOSED,
+            GenericServerResponse::LevelUp{pid: _, skill: _, old_level: _, new_level: _} => Self::RESPONSE_LEVEL_UP,
+            GenericServerResponse::ActionProgress{ticks_done: _, ticks_total: _} => Self::RESPONSE_ACTION_PROGRESS,
+            GenericServerResponse::Hit{attacker: _, target: _, damage: _, hp: _, max_hp: _} => Self::RESPONSE_HIT,
+            GenericServerResponse::Death{pid: _} => Self::RESPONSE_DEATH,
+            GenericServerResponse::SetCollision{x: _, y: _, blocked: _} => Self::RESPONSE_SET_COLLISION,
+            GenericServerResponse::Dialogue{entity_id: _, text: _, options: _} => Self::RESPONSE_DIALOGUE,
+            GenericServerResponse::DialogueClosed => Self::RESPONSE_DIALOGUE_CLOSED,
             GenericServerResponse::Err => Self::RESPONSE_ERR,
         }
     }
@@ -188,6 +317,113 @@ impl GenericResponse {
         }
     }
 
+    #[func]
+    /// Returns an array of bank slots, with nulls for empty slots
+    pub fn as_bank_contents(&self) -> Array<Option<Gd<ItemResource>>> {
+        let mut array = Array::new();
+        if let GenericServerResponse::BankContents{items} = &self.response {
+            for item in items {
+                array.push(
+                    &item.as_ref().map(|i| i.to_resource())
+                );
+            }
+        }
+        array
+    }
+
+    #[func]
+    /// Returns a dictionary with fields "pid": int, "skill": String, "old_level": int and "new_level": int
+    pub fn as_level_up(&self) -> Dictionary {
+        match &self.response {
+            GenericServerResponse::LevelUp{pid, skill, old_level, new_level} => {
+                let mut dict = Dictionary::new();
+                dict.set("pid", *pid);
+                dict.set("skill", GString::from(skill));
+                dict.set("old_level", *old_level);
+                dict.set("new_level", *new_level);
+                dict
+            },
+            _ => Dictionary::new(),
+        }
+    }
+
+    #[func]
+    /// Returns a dictionary with fields "ticks_done": int and "ticks_total": int
+    /// 
+    /// A ticks_total of 0 means the action was cancelled, ticks_done equal to ticks_total means it completed.
+    pub fn as_action_progress(&self) -> Dictionary {
+        match &self.response {
+            GenericServerResponse::ActionProgress{ticks_done, ticks_total} => {
+                let mut dict = Dictionary::new();
+                dict.set("ticks_done", *ticks_done);
+                dict.set("ticks_total", *ticks_total);
+                dict
+            },
+            _ => Dictionary::new(),
+        }
+    }
+
+    #[func]
+    /// Returns a dictionary with fields "attacker_pid": int, "attacker_entity_id": int, "target_pid": int,
+    /// "target_entity_id": int, "damage": int, "hp": int and "max_hp": int
+    /// 
+    /// Each side is either a player or an entity, with -1 for the other id. A damage of 0 means the attack missed.
+    pub fn as_hit(&self) -> Dictionary {
+        match &self.response {
+            GenericServerResponse::Hit{attacker, target, damage, hp, max_hp} => {
+                let mut dict = Dictionary::new();
+                dict.set("attacker_pid", attacker.pid());
+                dict.set("attacker_entity_id", attacker.entity_id());
+                dict.set("target_pid", target.pid());
+                dict.set("target_entity_id", target.entity_id());
+                dict.set("damage", *damage);
+                dict.set("hp", *hp);
+                dict.set("max_hp", *max_hp);
+                dict
+            },
+            _ => Dictionary::new(),
+        }
+    }
+
+    #[func]
+    /// Returns the pid of the player who died, -1 if not a death response
+    fn as_death(&self) -> i32 {
+        match &self.response {
+            GenericServerResponse::Death{pid} => *pid,
+            _ => -1,
+        }
+    }
+
+    #[func]
+    /// Returns a dictionary with fields "x": int, "y": int and "blocked": bool
+    fn as_set_collision(&self) -> Dictionary {
+        match &self.response {
+            GenericServerResponse::SetCollision{x, y, blocked} => {
+                let mut dict = Dictionary::new();
+                dict.set("x", *x);
+                dict.set("y", *y);
+                dict.set("blocked", *blocked);
+                dict
+            },
+            _ => Dictionary::new(),
+        }
+    }
+
+    #[func]
+    /// Returns a dictionary with fields "entity_id": int, "text": String and "options": PackedStringArray
+    fn as_dialogue(&self) -> Dictionary {
+        match &self.response {
+            GenericServerResponse::Dialogue{entity_id, text, options} => {
+                let mut dict = Dictionary::new();
+                dict.set("entity_id", *entity_id);
+                dict.set("text", GString::from(text));
+                dict.set("options", options.iter().map(GString::from).collect::<PackedStringArray>());
+                dict
+            },
+            _ => Dictionary::new(),
+        }
+    }
+
     #[func]
     fn as_despawn_player(&self) -> i32 {
         match &self.response {
@@ -203,9 +439,51 @@ pub enum GenericServerResponse {
     GotFriendRequest{pid: i32, name: String},
     DespawnPlayer{pid: i32},
     DataUpdate{pid: i32, data_version: i32},
+    /// Sent when the bank is opened and after every change to it while open
+    BankContents{items: Vec<Option<Item>>},
+    BankClosed,
+    /// Sent to the player and everyone around them when one of their skills levels up
+    LevelUp{pid: i32, skill: String, old_level: i32, new_level: i32},
+    /// Sent when a timed action starts, completes or gets cancelled (with a ticks_total of 0)
+    ActionProgress{ticks_done: i32, ticks_total: i32},
+    /// Sent to everyone around the target of an attack, damage of 0 being a miss
+    Hit{attacker: Combatant, target: Combatant, damage: i32, hp: i32, max_hp: i32},
+    /// Sent to everyone around a player when they die, before they respawn
+    Death{pid: i32},
+    /// Sent to everyone in the instance when a script changes a collision tile, and to players joining it for every changed tile
+    SetCollision{x: i32, y: i32, blocked: bool},
+    /// Sent when a dialogue opens or moves to another node, with the options the player can choose from
+    Dialogue{entity_id: i32, text: String, options: Vec<String>},
+    /// Sent when the dialogue closes server-side, e.g. by choosing an option that ends it or moving
+    DialogueClosed,
     Err,
 }
 
+/// Either side of an attack
+#[derive(Decode, Encode, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Combatant {
+    Player{pid: i32},
+    Entity{entity_id: i32},
+}
+
+impl Combatant {
+    /// -1 if not a player
+    pub fn pid(&self) -> i32 {
+        match self {
+            Combatant::Player{pid} => *pid,
+            Combatant::Entity{entity_id: _} => -1,
+        }
+    }
+
+    /// -1 if not an entity
+    pub fn entity_id(&self) -> i32 {
+        match self {
+            Combatant::Player{pid: _} => -1,
+            Combatant::Entity{entity_id} => *entity_id,
+        }
+    }
+}
+
 impl GenericServerResponse {
     pub fn from_bytes(b: &[u8]) -> Self {
         match bitcode::decode(b) {
@@ -0,0 +1,190 @@
+use super::{equipment::EquipSlot, rng::SeededRng, PlayerData};
+
+
+/// Max hp at endurance level 0
+pub const BASE_HP: i32 = 50;
+pub const HP_PER_ENDURANCE_LEVEL: i32 = 10;
+/// Ticks a player has to wait between attacks
+pub const ATTACK_COOLDOWN_TICKS: i32 = 6;
+/// Ticks between regenerating one hp
+pub const HP_REGEN_TICKS: i32 = 30;
+/// Xp given in the attack style's skill per point of damage dealt
+pub const XP_PER_DAMAGE: i32 = 4;
+
+/// Decided by the "attack_style" custom string of the main hand item, melee if missing
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AttackStyle {
+    Melee,
+    Ranged,
+    Magic,
+}
+
+impl AttackStyle {
+    pub fn try_from_str(s: &str) -> Option<Self> {
+        match s {
+            "melee" => Some(Self::Melee),
+            "ranged" => Some(Self::Ranged),
+            "magic" => Some(Self::Magic),
+            _ => None,
+        }
+    }
+
+    /// Skill that decides the max hit and gets the xp
+    pub fn skill(&self) -> &'static str {
+        match self {
+            Self::Melee => "strength",
+            Self::Ranged => "ranged",
+            Self::Magic => "magic",
+        }
+    }
+
+    /// Max distance in tiles, diagonals counting as one
+    pub fn range(&self) -> i32 {
+        match self {
+            Self::Melee => 1,
+            Self::Ranged => 7,
+            Self::Magic => 5,
+        }
+    }
+}
+
+pub fn max_hp(endurance: i32) -> i32 {
+    BASE_HP.saturating_add(HP_PER_ENDURANCE_LEVEL.saturating_mul(endurance.max(0)))
+}
+
+pub fn max_hit(attack_level: i32, weapon_damage: i32) -> i32 {
+    1 + attack_level.max(0) / 3 + weapon_damage.max(0)
+}
+
+/// Chance of an attack dealing any damage, defence_level being the defender's agility
+pub fn hit_chance(attack_level: i32, defence_level: i32) -> f64 {
+    (0.6 + 0.02 * (attack_level as f64 - defence_level as f64)).clamp(0.2, 0.95)
+}
+
+/// Damage of a single attack, 0 for a miss
+pub fn roll_damage(attack_level: i32, weapon_damage: i32, defence_level: i32, rng: &mut SeededRng) -> i32 {
+    if rng.next_f64() >= hit_chance(attack_level, defence_level) {
+        return 0;
+    }
+    rng.range(1, max_hit(attack_level, weapon_damage))
+}
+
+impl PlayerData {
+    pub fn max_hp(&self) -> i32 {
+        max_hp(self.effective_level("endurance").unwrap_or(1))
+    }
+
+    pub fn attack_style(&self) -> AttackStyle {
+        self.equipment[EquipSlot::MainHand].as_ref()
+            .and_then(|item| item.custom_data().get("attack_style"))
+            .and_then(|style| style.as_str().and_then(AttackStyle::try_from_str))
+            .unwrap_or(AttackStyle::Melee)
+    }
+
+    /// The "damage" custom int of the main hand item, 0 if missing
+    pub fn weapon_damage(&self) -> i32 {
+        self.equipment[EquipSlot::MainHand].as_ref()
+            .and_then(|item| item.custom_data().get("damage"))
+            .and_then(|damage| damage.as_int())
+            .map_or(0, |damage| damage.clamp(0, i32::MAX as i64) as i32)
+    }
+
+    /// Level players defend with against attacks
+    pub fn defence_level(&self) -> i32 {
+        self.effective_level("agility").unwrap_or(1)
+    }
+
+    /// Rolls the damage of an attack by this player on a defender with the given defence level
+    pub fn roll_attack(&self, defence_level: i32, rng: &mut SeededRng) -> i32 {
+        let attack_level = self.effective_level(self.attack_style().skill()).unwrap_or(1);
+        roll_damage(attack_level, self.weapon_damage(), defence_level, rng)
+    }
+
+    /// Returns true if the damage killed the player, hp then being 0
+    pub fn take_damage(&mut self, amount: i32) -> bool {
+        self.hp = self.hp.min(self.max_hp()).saturating_sub(amount.max(0)).max(0);
+        self.hp == 0
+    }
+
+    /// Returns true if any hp was restored, never going past max hp
+    pub fn heal(&mut self, amount: i32) -> bool {
+        let max_hp = self.max_hp();
+        if amount <= 0 || self.hp >= max_hp {
+            return false;
+        }
+        self.hp = self.hp.saturating_add(amount).min(max_hp);
+        true
+    }
+
+    pub fn restore_hp(&mut self) {
+        self.hp = self.max_hp();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playerdata::{item::Item, itemvalue::ItemValue, skillregistry::{self, SkillDef}};
+
+    fn register_combat_skills() {
+        for skill in ["strength", "ranged", "magic", "agility", "endurance"] {
+            skillregistry::register(SkillDef::with_curve(skill, skill, 99, 100., 2.));
+        }
+    }
+
+    fn bow(damage: i64) -> Item {
+        let mut item = Item::new("bow", 1);
+        item.custom_data_mut().set("attack_style", &ItemValue::String("ranged".into()));
+        item.custom_data_mut().set("damage", &ItemValue::Int(damage));
+        item
+    }
+
+    #[test]
+    fn test_attack_style_from_weapon() {
+        register_combat_skills();
+        let mut data = PlayerData::default();
+        assert_eq!(data.attack_style(), AttackStyle::Melee);
+        assert_eq!(data.weapon_damage(), 0);
+
+        data.equipment[EquipSlot::MainHand] = Some(bow(3));
+        assert_eq!(data.attack_style(), AttackStyle::Ranged);
+        assert_eq!(data.attack_style().range(), 7);
+        assert_eq!(data.weapon_damage(), 3);
+    }
+
+    #[test]
+    fn test_damage_rolls() {
+        let mut rng = SeededRng::new(5);
+        let mut hits = 0;
+        for _ in 0..10000 {
+            let damage = roll_damage(9, 2, 9, &mut rng);
+            assert!((0..=6).contains(&damage));
+            if damage > 0 {hits += 1;}
+        }
+        // 60% at equal levels
+        assert!((5700..6300).contains(&hits), "{hits}");
+
+        assert_eq!(hit_chance(99, 1), 0.95);
+        assert_eq!(hit_chance(1, 99), 0.2);
+    }
+
+    #[test]
+    fn test_damage_and_healing() {
+        register_combat_skills();
+        let mut data = PlayerData::default();
+        data.skills.set_level("endurance", 5);
+        assert_eq!(data.max_hp(), 100);
+        data.restore_hp();
+
+        assert!(!data.take_damage(30));
+        assert_eq!(data.hp, 70);
+        assert!(!data.take_damage(-10));
+        assert_eq!(data.hp, 70);
+        assert!(data.heal(50));
+        assert_eq!(data.hp, 100);
+        assert!(!data.heal(1));
+
+        assert!(data.take_damage(500));
+        assert_eq!(data.hp, 0);
+    }
+}
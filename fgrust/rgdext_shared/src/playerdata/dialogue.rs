@@ -0,0 +1,268 @@
+use godot::prelude::*;
+
+use super::{itemvalue::ItemValue, quest::QuestState, requirements::{self, Requirement}, PlayerData};
+
+
+/// A dialogue graph, shown to a player one node at a time
+#[derive(Clone, Debug, Default)]
+pub struct Dialogue {
+    /// id of the node the dialogue opens at
+    pub start: String,
+    pub nodes: Vec<DialogueNode>,
+}
+
+impl Dialogue {
+    pub fn node(&self, id: &str) -> Option<&DialogueNode> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DialogueNode {
+    pub id: String,
+    pub text: String,
+    pub options: Vec<DialogueOption>,
+}
+
+impl DialogueNode {
+    /// Indices of the options shown to the player, the ones whose requirements they meet
+    pub fn available_options(&self, data: &PlayerData) -> Vec<usize> {
+        (0..self.options.len())
+            .filter(|&i| requirements::first_unmet(&self.options[i].requirements, data).is_none())
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DialogueOption {
+    pub text: String,
+    /// id of the node the option leads to, empty to close the dialogue
+    pub next: String,
+    /// Passed to the entity's on_dialogue_choice hook when the option gets chosen, empty for none
+    pub action: String,
+    /// Options are hidden from players who don't meet these, and checked again when chosen
+    pub requirements: Vec<Requirement>,
+}
+
+#[derive(GodotClass)]
+#[class(base=Resource)]
+/// Dialogue graph opened with [method ScriptResponse.open_dialogue]. The server shows the player one node at a time
+/// and validates their choices.
+pub struct DialogueResource {
+    #[export]
+    /// id of the node the dialogue opens at.
+    start: GString,
+    #[export]
+    nodes: Array<Gd<DialogueNodeResource>>,
+
+    base: Base<Resource>,
+}
+
+#[godot_api]
+impl IResource for DialogueResource {
+    fn init(base: Base<Resource>) -> Self {
+        Self {
+            start: "start".into(),
+            nodes: Array::new(),
+            base,
+        }
+    }
+}
+
+impl DialogueResource {
+    pub fn to_dialogue(&self) -> Dialogue {
+        Dialogue {
+            start: self.start.to_string(),
+            nodes: self.nodes.iter_shared().map(|node| node.bind().to_node()).collect(),
+        }
+    }
+}
+
+#[derive(GodotClass)]
+#[class(base=Resource)]
+/// Text shown to the player along with the options they can answer with.
+pub struct DialogueNodeResource {
+    #[export]
+    /// Unique within the dialogue, options lead to nodes by it.
+    id: GString,
+    #[export(multiline)]
+    text: GString,
+    #[export]
+    /// A node without options shows a single option that closes the dialogue.
+    options: Array<Gd<DialogueOptionResource>>,
+
+    base: Base<Resource>,
+}
+
+#[godot_api]
+impl IResource for DialogueNodeResource {
+    fn init(base: Base<Resource>) -> Self {
+        Self {
+            id: "".into(),
+            text: "".into(),
+            options: Array::new(),
+            base,
+        }
+    }
+}
+
+impl DialogueNodeResource {
+    fn to_node(&self) -> DialogueNode {
+        let mut options: Vec<DialogueOption> = self.options.iter_shared().map(|option| option.bind().to_option()).collect();
+        if options.is_empty() {
+            options.push(DialogueOption{text: "Goodbye.".into(), ..Default::default()});
+        }
+        DialogueNode {
+            id: self.id.to_string(),
+            text: self.text.to_string(),
+            options,
+        }
+    }
+}
+
+#[derive(GodotClass)]
+#[class(base=Resource)]
+/// Answer the player can choose. Options whose requirements the player doesn't meet are hidden from them.
+pub struct DialogueOptionResource {
+    #[export]
+    text: GString,
+    #[export]
+    /// id of the node the option leads to, leave empty to close the dialogue.
+    next: GString,
+    #[export]
+    /// Passed to [method GenericScriptedEntity.on_dialogue_choice] when chosen, leave empty for none.
+    action: GString,
+    #[export]
+    /// Skill name -> minimum level (including equipment bonuses) needed to see the option.
+    required_skills: Dictionary,
+    #[export]
+    /// id_string of an item that has to be equipped to see the option, leave empty for none.
+    required_equipped: GString,
+    #[export]
+    /// Item id_string -> count of items the player has to have to see the option.
+    required_items: Dictionary,
+    #[export]
+    /// Gold the player has to have to see the option. Isn't taken away.
+    required_gold: i32,
+    #[export]
+    /// Quest id -> "not_started", "in_progress" or "completed", the state the quest has to be in to see the option.
+    required_quests: Dictionary,
+    #[export]
+    /// Player flag key -> value the flag has to have to see the option, null for the flag not being set.
+    required_flags: Dictionary,
+
+    base: Base<Resource>,
+}
+
+#[godot_api]
+impl IResource for DialogueOptionResource {
+    fn init(base: Base<Resource>) -> Self {
+        Self {
+            text: "".into(),
+            next: "".into(),
+            action: "".into(),
+            required_skills: Dictionary::new(),
+            required_equipped: "".into(),
+            required_items: Dictionary::new(),
+            required_gold: 0,
+            required_quests: Dictionary::new(),
+            required_flags: Dictionary::new(),
+            base,
+        }
+    }
+}
+
+impl DialogueOptionResource {
+    fn to_option(&self) -> DialogueOption {
+        let mut requirements = Vec::new();
+        for (k, v) in self.required_skills.iter_shared() {
+            match v.try_to_relaxed::<i32>() {
+                Ok(level) => requirements.push(Requirement::SkillLevel{skill: k.to_string(), level}),
+                Err(_) => godot_error!("Required skills of dialogue option {} should map skill names to ints!", self.text),
+            }
+        }
+        if !self.required_equipped.is_empty() {
+            requirements.push(Requirement::Equipped{id_string: self.required_equipped.to_string()});
+        }
+        for (k, v) in self.required_items.iter_shared() {
+            match v.try_to_relaxed::<i32>() {
+                Ok(count) => requirements.push(Requirement::HasItem{id_string: k.to_string(), count}),
+                Err(_) => godot_error!("Required items of dialogue option {} should map item ids to ints!", self.text),
+            }
+        }
+        if self.required_gold > 0 {
+            requirements.push(Requirement::Gold{amount: self.required_gold});
+        }
+        for (k, v) in self.required_quests.iter_shared() {
+            match QuestState::try_from_str(&v.to_string()) {
+                Some(state) => requirements.push(Requirement::Quest{id: k.to_string(), state}),
+                None => godot_error!("Required quests of dialogue option {} should map quest ids to \"not_started\", \"in_progress\" or \"completed\"!", self.text),
+            }
+        }
+        for (k, v) in self.required_flags.iter_shared() {
+            if v.is_nil() {
+                requirements.push(Requirement::Flag{key: k.to_string(), value: None});
+                continue;
+            }
+            match ItemValue::from_variant(&v) {
+                Some(value) => requirements.push(Requirement::Flag{key: k.to_string(), value: Some(value)}),
+                None => godot_error!("Required flag {} of dialogue option {} has an unsupported value type!", k, self.text),
+            }
+        }
+
+        DialogueOption {
+            text: self.text.to_string(),
+            next: self.next.to_string(),
+            action: self.action.to_string(),
+            requirements,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn option(text: &str, requirements: Vec<Requirement>) -> DialogueOption {
+        DialogueOption{text: text.into(), requirements, ..Default::default()}
+    }
+
+    fn shop_dialogue() -> Dialogue {
+        Dialogue {
+            start: "start".into(),
+            nodes: vec![
+                DialogueNode {
+                    id: "start".into(),
+                    text: "Want to buy something?".into(),
+                    options: vec![
+                        option("Sure.", vec![Requirement::Gold{amount: 10}]),
+                        option("I'm broke.", vec![]),
+                        option("I'm rich.", vec![Requirement::Gold{amount: 1000}]),
+                    ],
+                },
+                DialogueNode{id: "bye".into(), text: "Bye!".into(), options: vec![option("Bye.", vec![])]},
+            ],
+        }
+    }
+
+    #[test]
+    fn test_node_lookup() {
+        let dialogue = shop_dialogue();
+        assert_eq!(dialogue.node(&dialogue.start).unwrap().text, "Want to buy something?");
+        assert_eq!(dialogue.node("bye").unwrap().options.len(), 1);
+        assert!(dialogue.node("missing").is_none());
+    }
+
+    #[test]
+    fn test_available_options_hide_unmet() {
+        let dialogue = shop_dialogue();
+        let node = dialogue.node("start").unwrap();
+        let mut data = PlayerData::default();
+
+        assert_eq!(node.available_options(&data), vec![1]);
+        data.gold = 10;
+        assert_eq!(node.available_options(&data), vec![0, 1]);
+        data.gold = 1000;
+        assert_eq!(node.available_options(&data), vec![0, 1, 2]);
+    }
+}
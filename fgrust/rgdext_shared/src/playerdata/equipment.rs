@@ -0,0 +1,147 @@
+use bitcode::{Decode, Encode};
+
+use super::item::Item;
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Encode, Decode, Debug)]
+#[repr(usize)]
+pub enum EquipSlot {
+    MainHand,
+    OffHand,
+    Head,
+    Body,
+    Legs,
+    Feet,
+    Hands,
+    Neck,
+}
+pub const NUM_EQUIP_SLOTS: usize = 8;
+
+impl EquipSlot {
+    pub fn try_from_str(s: &str) -> Option<EquipSlot> {
+        match s {
+            "main_hand" => Some(EquipSlot::MainHand),
+            "off_hand" => Some(EquipSlot::OffHand),
+            "head" => Some(EquipSlot::Head),
+            "body" => Some(EquipSlot::Body),
+            "legs" => Some(EquipSlot::Legs),
+            "feet" => Some(EquipSlot::Feet),
+            "hands" => Some(EquipSlot::Hands),
+            "neck" => Some(EquipSlot::Neck),
+            _ => None,
+        }
+    }
+
+    pub fn slot_strs() -> [&'static str; NUM_EQUIP_SLOTS] {
+        [
+            "main_hand",
+            "off_hand",
+            "head",
+            "body",
+            "legs",
+            "feet",
+            "hands",
+            "neck",
+        ]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        Self::slot_strs()[*self as usize]
+    }
+}
+
+/// Items currently worn by the player, one per [EquipSlot]
+#[derive(Clone, Default, Encode, Decode, Debug)]
+pub struct Equipment {
+    slots: [Option<Item>; NUM_EQUIP_SLOTS],
+}
+
+impl Equipment {
+    pub fn iter(&self) -> impl Iterator<Item = &Option<Item>> {
+        self.slots.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Option<Item>> {
+        self.slots.iter_mut()
+    }
+
+    /// Sum of the bonuses all equipped items give to the given stat
+    pub fn stat_bonus(&self, stat: &str) -> i32 {
+        self.slots.iter()
+            .flatten()
+            .fold(0i32, |total, item| total.saturating_add(item.stat_bonus(stat)))
+    }
+
+    /// Whether an item with the given id_string is equipped in any slot
+    pub fn has_equipped(&self, id_string: &str) -> bool {
+        self.slots.iter().flatten().any(|item| item.id_string() == id_string)
+    }
+}
+
+impl std::ops::Index<EquipSlot> for Equipment {
+    type Output = Option<Item>;
+
+    fn index(&self, index: EquipSlot) -> &Option<Item> {
+        &self.slots[index as usize]
+    }
+}
+
+impl std::ops::IndexMut<EquipSlot> for Equipment {
+    fn index_mut(&mut self, index: EquipSlot) -> &mut Option<Item> {
+        &mut self.slots[index as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playerdata::{testutil::{axe, register_test_items, register_test_skills}, PlayerData, MAX_ITEMS};
+
+    #[test]
+    fn test_equip_and_unequip() {
+        register_test_items();
+        register_test_skills();
+        let mut data = PlayerData::default();
+        data.items[0] = Some(axe());
+        data.items[1] = Some(Item::new("oak_wood", 5));
+
+        assert!(!data.equip_item(1));
+        assert!(!data.equip_item(2));
+        assert!(!data.equip_item(MAX_ITEMS));
+        assert!(data.equip_item(0));
+        assert!(data.items[0].is_none());
+        assert_eq!(data.equipment[EquipSlot::MainHand].as_ref().unwrap().id_string(), "axe");
+        assert_eq!(data.effective_level("woodcutting"), Some(3));
+
+        assert!(data.unequip_item(EquipSlot::MainHand));
+        assert!(!data.unequip_item(EquipSlot::MainHand));
+        assert!(data.equipment[EquipSlot::MainHand].is_none());
+        assert_eq!(data.effective_level("woodcutting"), Some(1));
+        assert_eq!(data.effective_level("nonexistent"), None);
+    }
+
+    #[test]
+    fn test_equip_swaps_with_equipped() {
+        register_test_items();
+        let mut data = PlayerData::default();
+        data.equipment[EquipSlot::MainHand] = Some(Item::new("axe_super", 1));
+        data.items[3] = Some(axe());
+
+        assert!(data.equip_item(3));
+        assert_eq!(data.items[3].as_ref().unwrap().id_string(), "axe_super");
+        assert_eq!(data.equipment[EquipSlot::MainHand].as_ref().unwrap().id_string(), "axe");
+    }
+
+    #[test]
+    fn test_unequip_full_inventory() {
+        register_test_items();
+        let mut data = PlayerData::default();
+        data.equipment[EquipSlot::MainHand] = Some(axe());
+        for slot in data.items.iter_mut() {
+            *slot = Some(Item::new("oak_wood", 1));
+        }
+
+        assert!(!data.unequip_item(EquipSlot::MainHand));
+        assert!(data.equipment[EquipSlot::MainHand].is_some());
+    }
+}
@@ -0,0 +1,101 @@
+use super::{itemvalue::ItemValue, PlayerData};
+
+
+/// Most flags a player can have set at once
+pub const MAX_FLAGS: usize = 128;
+/// Longest flag key, in bytes
+pub const MAX_FLAG_KEY_LEN: usize = 64;
+/// Longest string flag value, in bytes
+pub const MAX_FLAG_STRING_LEN: usize = 256;
+
+/// Reason a flag couldn't be set
+#[derive(Clone, PartialEq, Debug)]
+pub enum FlagError {
+    TooManyFlags,
+    KeyTooLong,
+    EmptyKey,
+    StringTooLong,
+    /// Arrays and dictionaries can't be stored as flags
+    UnsupportedValue,
+}
+
+impl FlagError {
+    /// Message reported to the script that tried to set the flag
+    pub fn message(&self) -> String {
+        match self {
+            FlagError::TooManyFlags => format!("player already has the maximum of {MAX_FLAGS} flags set"),
+            FlagError::KeyTooLong => format!("flag keys can be at most {MAX_FLAG_KEY_LEN} bytes long"),
+            FlagError::EmptyKey => "flag keys can't be empty".to_string(),
+            FlagError::StringTooLong => format!("string flag values can be at most {MAX_FLAG_STRING_LEN} bytes long"),
+            FlagError::UnsupportedValue => "flag values can't be arrays or dictionaries".to_string(),
+        }
+    }
+}
+
+impl PlayerData {
+    pub fn flag(&self, key: &str) -> Option<ItemValue> {
+        self.flags.get(key)
+    }
+
+    /// Sets the flag, replacing its previous value. Nothing changes if the key or value is out of bounds.
+    pub fn set_flag(&mut self, key: &str, value: &ItemValue) -> Result<(), FlagError> {
+        if key.is_empty() {
+            return Err(FlagError::EmptyKey);
+        }
+        if key.len() > MAX_FLAG_KEY_LEN {
+            return Err(FlagError::KeyTooLong);
+        }
+        match value {
+            ItemValue::Array(_) | ItemValue::Dictionary(_) => return Err(FlagError::UnsupportedValue),
+            ItemValue::String(s) if s.len() > MAX_FLAG_STRING_LEN => return Err(FlagError::StringTooLong),
+            _ => {},
+        }
+        if self.flags.len() >= MAX_FLAGS && !self.flags.contains_key(key) {
+            return Err(FlagError::TooManyFlags);
+        }
+        self.flags.set(key, value);
+        Ok(())
+    }
+
+    /// Returns true if the flag was set
+    pub fn clear_flag(&mut self, key: &str) -> bool {
+        self.flags.remove(key).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_clear_flag() {
+        let mut data = PlayerData::default();
+        assert_eq!(data.flag("talked_to_bob"), None);
+        assert_eq!(data.set_flag("talked_to_bob", &ItemValue::Bool(true)), Ok(()));
+        assert_eq!(data.set_flag("talked_to_bob", &ItemValue::Int(2)), Ok(()));
+        assert_eq!(data.flag("talked_to_bob"), Some(ItemValue::Int(2)));
+        assert!(data.clear_flag("talked_to_bob"));
+        assert!(!data.clear_flag("talked_to_bob"));
+        assert_eq!(data.flag("talked_to_bob"), None);
+    }
+
+    #[test]
+    fn test_flags_are_bounded() {
+        let mut data = PlayerData::default();
+        assert_eq!(data.set_flag("", &ItemValue::Bool(true)), Err(FlagError::EmptyKey));
+        assert_eq!(data.set_flag(&"k".repeat(MAX_FLAG_KEY_LEN + 1), &ItemValue::Bool(true)), Err(FlagError::KeyTooLong));
+        assert_eq!(
+            data.set_flag("name", &ItemValue::String("s".repeat(MAX_FLAG_STRING_LEN + 1))),
+            Err(FlagError::StringTooLong)
+        );
+        assert_eq!(data.set_flag("list", &ItemValue::Array(vec![])), Err(FlagError::UnsupportedValue));
+
+        for i in 0..MAX_FLAGS {
+            assert_eq!(data.set_flag(&format!("flag{i}"), &ItemValue::Int(i as i64)), Ok(()));
+        }
+        assert_eq!(data.set_flag("one_more", &ItemValue::Bool(true)), Err(FlagError::TooManyFlags));
+        // Existing flags can still be overwritten when full
+        assert_eq!(data.set_flag("flag0", &ItemValue::Bool(true)), Ok(()));
+        assert!(data.flag("one_more").is_none());
+    }
+}
@@ -0,0 +1,474 @@
+use super::item::{Item, MAX_STACK_SIZE};
+
+
+/// Total count of items with the given id_string across the given slots
+pub fn count_item<'a>(slots: impl IntoIterator<Item = &'a Option<Item>>, id_string: &str) -> i32 {
+    slots.into_iter()
+        .flatten()
+        .filter(|item| item.id_string() == id_string)
+        .fold(0i32, |total, item| total.saturating_add(item.count))
+}
+
+/// Returns true if the whole item would fit into the given slots
+pub fn can_insert(slots: &[Option<Item>], item: &Item) -> bool {
+    if !item.is_valid() {
+        return false;
+    }
+    if !item.stackable() {
+        return slots.iter().any(|slot| slot.is_none());
+    }
+
+    let mut space: i64 = 0;
+    for slot in slots {
+        space += match slot {
+            Some(existing) if existing.stacks_with(item) => (MAX_STACK_SIZE - existing.count) as i64,
+            Some(_) => 0,
+            None => MAX_STACK_SIZE as i64,
+        };
+    }
+    space >= item.count as i64
+}
+
+/// Inserts the item into the given slots, topping up existing stacks first and then filling empty slots.
+///
+/// Either the whole item is inserted or nothing is changed. Returns true if item successfully inserted
+pub fn insert_item(slots: &mut [Option<Item>], item: Item) -> bool {
+    if !can_insert(slots, &item) {
+        return false;
+    }
+    if !item.stackable() {
+        // can_insert guarantees there is an empty slot
+        if let Some(slot) = slots.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(item);
+        }
+        return true;
+    }
+
+    let mut remaining = item.count;
+    for slot in slots.iter_mut() {
+        if remaining == 0 {break;}
+        if let Some(existing) = slot && existing.stacks_with(&item) {
+            let moved = remaining.min(MAX_STACK_SIZE - existing.count);
+            existing.count += moved;
+            remaining -= moved;
+        }
+    }
+    for slot in slots.iter_mut() {
+        if remaining == 0 {break;}
+        if slot.is_none() {
+            let moved = remaining.min(MAX_STACK_SIZE);
+            let mut new_stack = item.clone();
+            new_stack.count = moved;
+            *slot = Some(new_stack);
+            remaining -= moved;
+        }
+    }
+    true
+}
+
+/// Inserts count new items with the given id_string, unstackable items each getting a slot of their own.
+///
+/// Like insert_item, either all of them are inserted or nothing is changed. Returns true if they were inserted
+pub fn insert_new(slots: &mut [Option<Item>], id_string: &str, count: i32) -> bool {
+    let item = Item::new(id_string, count);
+    if item.stackable() {
+        return insert_item(slots, item);
+    }
+
+    let free = slots.iter().filter(|slot| slot.is_none()).count();
+    if count <= 0 || count as usize > free {
+        return false;
+    }
+    (0..count).all(|_| insert_item(slots, Item::new(id_string, 1)))
+}
+
+/// Removes the given amount of items with the given id_string, going through the slots in order
+/// and emptying slots whose stacks run out.
+///
+/// Either the whole amount is removed or nothing is changed. Returns true if the amount was removed
+pub fn remove_item<'a>(slots: impl IntoIterator<Item = &'a mut Option<Item>>, id_string: &str, amount: i32) -> bool {
+    if amount <= 0 {
+        return false;
+    }
+
+    let matching: Vec<&mut Option<Item>> = slots.into_iter()
+        .filter(|slot| slot.as_ref().is_some_and(|item| item.id_string() == id_string))
+        .collect();
+    if count_item(matching.iter().map(|slot| &**slot), id_string) < amount {
+        return false;
+    }
+
+    let mut remaining = amount;
+    for slot in matching {
+        if remaining == 0 {break;}
+        if let Some(item) = slot {
+            let taken = remaining.min(item.count);
+            item.count -= taken;
+            remaining -= taken;
+            if item.count <= 0 {
+                *slot = None;
+            }
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playerdata::{equipment::EquipSlot, itemvalue::ItemValue, testutil::{axe, register_test_items}, PlayerData, BANK_SIZE, MAX_ITEMS};
+
+    /// Small xorshift generator so the randomized tests are reproducible without extra dependencies
+    struct TestRng(u64);
+
+    impl TestRng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn range(&mut self, min: i32, max: i32) -> i32 {
+            let span = (max as i64 - min as i64 + 1) as u64;
+            (min as i64 + (self.next() % span) as i64) as i32
+        }
+    }
+
+    const CASES: u64 = 100;
+    const OPS_PER_CASE: usize = 200;
+
+    fn random_item(rng: &mut TestRng) -> Item {
+        match rng.range(0, 4) {
+            0 => Item::new("oak_wood", rng.range(-5, MAX_STACK_SIZE + 5)),
+            1 => Item::new("fir_wood", rng.range(1, 50)),
+            2 => Item::new("fairy", rng.range(i32::MAX - 5, i32::MAX)),
+            3 => {
+                let mut item = Item::new("oak_wood", rng.range(1, 50));
+                item.custom_data_mut().set("quality", &ItemValue::Int(rng.range(0, 1) as i64));
+                item
+            },
+            _ => axe(),
+        }
+    }
+
+    /// Count of the items that stack with the given one, so same id and custom data
+    fn count_stacking(data: &PlayerData, item: &Item) -> i32 {
+        data.items.iter().flatten()
+            .filter(|i| i.id_string() == item.id_string() && i.custom_data() == item.custom_data())
+            .map(|i| i.count)
+            .sum()
+    }
+
+    fn random_id(rng: &mut TestRng) -> &'static str {
+        ["oak_wood", "fir_wood", "fairy", "axe", "missing"][rng.range(0, 4) as usize]
+    }
+
+    fn all_slots(data: &PlayerData) -> impl Iterator<Item = &Item> {
+        data.items.iter().chain(data.equipment.iter()).flatten()
+    }
+
+    fn assert_invariants(data: &PlayerData) {
+        assert!(data.gold >= 0);
+        for item in all_slots(data) {
+            assert!(item.count > 0, "empty stack left in inventory");
+            assert!(item.count <= MAX_STACK_SIZE, "stack over max size");
+            if !item.stackable() {
+                assert_eq!(item.count, 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_inventory_operations_keep_invariants() {
+        register_test_items();
+        for seed in 1..=CASES {
+            let mut rng = TestRng(seed.wrapping_mul(0x9E3779B97F4A7C15));
+            let mut data = PlayerData::default();
+
+            for _ in 0..OPS_PER_CASE {
+                let before = data.to_bytes();
+                match rng.range(0, 4) {
+                    0 => {
+                        let item = random_item(&mut rng);
+                        let (id, count) = (item.id_string().to_string(), item.count);
+                        let held = data.count_item(&id);
+                        let held_stacking = count_stacking(&data, &item);
+                        if data.insert_item(item.clone()) {
+                            assert_eq!(data.count_item(&id), held + count);
+                            assert_eq!(count_stacking(&data, &item), held_stacking + count, "custom data lost on insert");
+                        }
+                        else {
+                            assert_eq!(data.to_bytes(), before, "failed insert changed data");
+                        }
+                    },
+                    1 => {
+                        let id = random_id(&mut rng);
+                        let amount = rng.range(-5, 3 * MAX_STACK_SIZE);
+                        let held = data.count_item(id);
+                        if data.remove_item(id, amount) {
+                            assert!(amount > 0);
+                            assert_eq!(data.count_item(id), held - amount);
+                        }
+                        else {
+                            assert!(amount <= 0 || held < amount);
+                            assert_eq!(data.to_bytes(), before, "failed remove changed data");
+                        }
+                    },
+                    2 => {
+                        let from = rng.range(0, MAX_ITEMS as i32 + 2) as usize;
+                        let to = rng.range(0, MAX_ITEMS as i32 + 2) as usize;
+                        let new_count = rng.range(-2, MAX_STACK_SIZE);
+                        let id = data.items.get(from).and_then(|i| i.as_ref()).map(|i| i.id_string().to_string());
+                        let held = id.as_ref().map(|id| data.count_item(id));
+                        if data.split_item(from, to, new_count) {
+                            let id = id.unwrap();
+                            assert_eq!(data.count_item(&id), held.unwrap());
+                            assert_eq!(data.items[to].as_ref().unwrap().count, new_count);
+                        }
+                        else {
+                            assert_eq!(data.to_bytes(), before, "failed split changed data");
+                        }
+                    },
+                    3 => {
+                        let axes = data.count_item("axe");
+                        if rng.range(0, 1) == 0 {
+                            let from = rng.range(0, MAX_ITEMS as i32) as usize;
+                            let equippable = data.get_item(from).is_some_and(|i| i.equip_slot().is_some());
+                            assert_eq!(data.equip_item(from), equippable);
+                        }
+                        else if !data.unequip_item(EquipSlot::MainHand) {
+                            assert_eq!(data.to_bytes(), before, "failed unequip changed data");
+                        }
+                        assert_eq!(data.count_item("axe"), axes);
+                    },
+                    _ => {
+                        let amount = rng.range(-1000, 1000);
+                        let gold = data.gold;
+                        if data.change_gold(amount) {
+                            assert_eq!(data.gold, gold + amount);
+                        }
+                        else {
+                            assert_eq!(data.gold, gold);
+                        }
+                    },
+                }
+                assert_invariants(&data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_combine_never_overflows() {
+        register_test_items();
+        let mut rng = TestRng(0xDEADBEEF);
+        for _ in 0..CASES * 50 {
+            let mut item = Item::new("oak_wood", rng.range(1, MAX_STACK_SIZE));
+            let other = Item::new("oak_wood", rng.range(i32::MIN, i32::MAX));
+            let (count, other_count) = (item.count, other.count);
+
+            match item.try_combine(other) {
+                None => {
+                    assert!(other_count > 0);
+                    assert_eq!(item.count, count + other_count);
+                    assert!(item.count <= MAX_STACK_SIZE);
+                },
+                Some(returned) => {
+                    assert_eq!(item.count, count);
+                    assert_eq!(returned.count, other_count);
+                },
+            }
+        }
+    }
+
+    #[test]
+    fn test_remove_item_spans_stacks() {
+        register_test_items();
+        let mut data = PlayerData::default();
+        data.equipment[EquipSlot::MainHand] = Some(axe());
+        data.items[0] = Some(Item::new("oak_wood", 3));
+        data.items[5] = Some(Item::new("oak_wood", 4));
+
+        assert!(!data.remove_item("oak_wood", 8));
+        assert_eq!(data.count_item("oak_wood"), 7);
+
+        assert!(data.remove_item("oak_wood", 5));
+        assert!(data.items[0].is_none());
+        assert_eq!(data.items[5].as_ref().unwrap().count, 2);
+    }
+
+    #[test]
+    fn test_remove_item_rejects_non_positive_amount() {
+        register_test_items();
+        let mut data = PlayerData::default();
+        data.items[0] = Some(Item::new("oak_wood", 3));
+
+        assert!(!data.remove_item("oak_wood", 0));
+        assert!(!data.remove_item("oak_wood", -3));
+        assert_eq!(data.count_item("oak_wood"), 3);
+    }
+
+    #[test]
+    fn test_insert_item_overflows_into_new_stack() {
+        register_test_items();
+        let mut data = PlayerData::default();
+        data.items[0] = Some(Item::new("oak_wood", MAX_STACK_SIZE - 1));
+
+        assert!(data.insert_item(Item::new("oak_wood", 3)));
+        assert_eq!(data.items[0].as_ref().unwrap().count, MAX_STACK_SIZE);
+        assert_eq!(data.items[1].as_ref().unwrap().count, 2);
+    }
+
+    #[test]
+    fn test_insert_item_keeps_custom_data_apart() {
+        register_test_items();
+        let mut data = PlayerData::default();
+        let mut fine = Item::new("oak_wood", 4);
+        fine.custom_data_mut().set("quality", &ItemValue::Int(1));
+        data.items[0] = Some(fine.clone());
+
+        assert!(data.insert_item(Item::new("oak_wood", 2)));
+        assert!(data.insert_item(fine));
+        assert_eq!(data.items[0].as_ref().unwrap().count, 8);
+        assert_eq!(data.items[0].as_ref().unwrap().custom_data().get("quality"), Some(ItemValue::Int(1)));
+        assert_eq!(data.items[1].as_ref().unwrap().count, 2);
+        assert_eq!(data.items[1].as_ref().unwrap().custom_data().get("quality"), None);
+    }
+
+    #[test]
+    fn test_insert_item_full_inventory_changes_nothing() {
+        register_test_items();
+        let mut data = PlayerData::default();
+        for slot in data.items.iter_mut() {
+            *slot = Some(Item::new("oak_wood", MAX_STACK_SIZE - 1));
+        }
+        let before = data.to_bytes();
+
+        assert!(!data.insert_item(Item::new("oak_wood", MAX_ITEMS as i32 + 1)));
+        assert!(!data.insert_item(axe()));
+        assert_eq!(data.to_bytes(), before);
+        assert!(data.insert_item(Item::new("oak_wood", MAX_ITEMS as i32)));
+    }
+
+    #[test]
+    fn test_insert_item_rejects_invalid_items() {
+        register_test_items();
+        let mut data = PlayerData::default();
+
+        assert!(!Item::new("axe", 2).is_valid());
+        assert!(!Item::new("oak_wood", 0).is_valid());
+        assert!(!data.insert_item(Item::new("axe", 2)));
+        assert!(!data.insert_item(Item::new("removed_item", 2)));
+        assert!(!data.insert_item(Item::new("oak_wood", MAX_STACK_SIZE + 1)));
+        assert!(data.items.iter().all(|slot| slot.is_none()));
+    }
+
+    #[test]
+    fn test_split_item_bounds() {
+        register_test_items();
+        let mut data = PlayerData::default();
+        data.items[0] = Some(Item::new("oak_wood", 10));
+
+        assert!(!data.split_item(MAX_ITEMS, 1, 5));
+        assert!(!data.split_item(0, MAX_ITEMS, 5));
+        assert!(!data.split_item(0, 0, 5));
+        assert!(!data.split_item(0, 1, 0));
+        assert!(!data.split_item(0, 1, 10));
+        assert!(data.split_item(0, 1, 4));
+        assert_eq!(data.items[0].as_ref().unwrap().count, 6);
+        assert_eq!(data.items[1].as_ref().unwrap().count, 4);
+    }
+
+    #[test]
+    fn test_change_gold_never_negative() {
+        register_test_items();
+        let mut data = PlayerData::default();
+
+        assert!(!data.change_gold(-1));
+        assert!(data.change_gold(i32::MAX));
+        assert!(!data.change_gold(1));
+        assert!(data.change_gold(-i32::MAX));
+        assert_eq!(data.gold, 0);
+    }
+
+    #[test]
+    fn test_deposit_and_withdraw() {
+        register_test_items();
+        let mut data = PlayerData::default();
+        data.items[0] = Some(Item::new("oak_wood", 5));
+        data.items[1] = Some(Item::new("oak_wood", 7));
+        data.bank[10] = Some(Item::new("oak_wood", 1));
+
+        assert!(data.deposit_item(0));
+        assert!(data.deposit_item(1));
+        assert!(!data.deposit_item(1));
+        assert!(!data.deposit_item(MAX_ITEMS));
+        assert_eq!(data.bank[10].as_ref().unwrap().count, 13);
+        assert_eq!(data.count_item("oak_wood"), 0);
+
+        assert!(!data.withdraw_item(0));
+        assert!(!data.withdraw_item(BANK_SIZE));
+        assert!(data.withdraw_item(10));
+        assert_eq!(data.count_item("oak_wood"), 13);
+        assert!(data.bank.iter().all(|slot| slot.is_none()));
+    }
+
+    #[test]
+    fn test_deposit_into_full_bank_changes_nothing() {
+        register_test_items();
+        let mut data = PlayerData::default();
+        for slot in data.bank.iter_mut() {
+            *slot = Some(axe());
+        }
+        data.items[4] = Some(Item::new("oak_wood", 5));
+        let before = data.to_bytes();
+
+        assert!(!data.deposit_item(4));
+        assert_eq!(data.to_bytes(), before);
+    }
+
+    #[test]
+    fn test_sort_bank_merges_stacks() {
+        register_test_items();
+        let mut data = PlayerData::default();
+        data.bank[3] = Some(Item::new("oak_wood", 5));
+        data.bank[7] = Some(axe());
+        data.bank[9] = Some(Item::new("fir_wood", 2));
+        data.bank[100] = Some(Item::new("oak_wood", MAX_STACK_SIZE));
+        data.bank[150] = Some(axe());
+
+        data.sort_bank();
+
+        let ids: Vec<(&str, i32)> = data.bank.iter().flatten().map(|i| (i.id_string(), i.count)).collect();
+        assert_eq!(ids, vec![("axe", 1), ("axe", 1), ("fir_wood", 2), ("oak_wood", MAX_STACK_SIZE), ("oak_wood", 5)]);
+        assert!(data.bank[..5].iter().all(|slot| slot.is_some()));
+        assert_eq!(data.bank.len(), BANK_SIZE);
+    }
+
+    #[test]
+    fn test_sort_bank_keeps_invalid_stacks() {
+        register_test_items();
+        let mut data = PlayerData::default();
+        data.bank[4] = Some(Item::new("removed_item", 3));
+        data.bank[8] = Some(Item::new("oak_wood", 2));
+        data.bank[9] = Some(Item::new("removed_item", 2));
+
+        data.sort_bank();
+
+        let ids: Vec<(&str, i32)> = data.bank.iter().flatten().map(|i| (i.id_string(), i.count)).collect();
+        assert_eq!(ids, vec![("oak_wood", 2), ("removed_item", 3), ("removed_item", 2)]);
+    }
+
+    #[test]
+    fn test_minimal_and_private_data_exclude_bank() {
+        register_test_items();
+        let mut data = PlayerData::default();
+        data.bank[0] = Some(axe());
+
+        assert!(data.get_minimal().bank.is_empty());
+        assert!(data.get_private().bank.is_empty());
+
+        let decoded = PlayerData::from_bytes(&data.get_private().to_bytes()).unwrap();
+        assert_eq!(decoded.bank.len(), BANK_SIZE);
+    }
+}
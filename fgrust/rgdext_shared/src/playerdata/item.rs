@@ -1,14 +1,23 @@
 use godot::prelude::*;
 use bitcode::{Encode, Decode};
 
+use super::{equipment::EquipSlot, itemregistry::{self, ItemDef}, itemvalue::{CustomData, ItemValue}};
+
+
+/// Max amount of items a single stack can hold
+pub const MAX_STACK_SIZE: i32 = 9999;
 
 #[derive(GodotClass)]
 #[class(base=Resource)]
+/// Item definitions are saved as ItemResources in the items directory and loaded into the [ItemRegistry] at startup.
+/// 
+/// Only the id_string, count and custom data of an item get stored in player data,
+/// the other fields are always filled in from the registry.
 pub struct ItemResource {
     #[export]
     /// Unique string id that uniquely identifies this item, also used for the icon on client-side.
     /// 
-    /// Items of the same id_string get stacked together.
+    /// Items of the same id_string and custom_data get stacked together.
     id_string: GString,
     #[export]
     name: GString,
@@ -19,7 +28,18 @@ pub struct ItemResource {
     #[export]
     count: i32,
     #[export]
+    /// Per-item data, keys have to be strings.
+    /// 
+    /// Values can be bools, ints, floats, strings, Vector2s, Vector2is and arrays or dictionaries of those.
     custom_data: Dictionary,
+    #[export]
+    /// Which equipment slot the item goes into when equipped, leave empty if the item can't be equipped.
+    /// 
+    /// One of main_hand, off_hand, head, body, legs, feet, hands or neck.
+    equip_slot: GString,
+    #[export]
+    /// Skill name -> bonus to that skill while the item is equipped.
+    stat_bonuses: Dictionary,
 
     base: Base<Resource>,
 }
@@ -34,6 +54,8 @@ impl IResource for ItemResource {
             stackable: true,
             count: 1,
             custom_data: Dictionary::new(),
+            equip_slot: "".into(),
+            stat_bonuses: Dictionary::new(),
             base,
         }
     }
@@ -42,55 +64,123 @@ impl IResource for ItemResource {
 #[godot_api]
 impl ItemResource {
     pub fn to_item(&self) -> Item {
-        let mut custom_ints = Vec::new();
-        let mut custom_floats = Vec::new();
-        let mut custom_strings = Vec::new();
+        let mut custom_data = CustomData::default();
 
         for (k, v) in self.custom_data.iter_shared() {
-            if let Ok(skey) = k.try_to_relaxed::<String>() {
-                if let Ok(ivar) = v.try_to_relaxed::<i32>() {
-                    custom_ints.push((skey, ivar));
-                }
-                else if let Ok(fvar) = v.try_to_relaxed::<f32>() {
-                    custom_floats.push((skey, fvar));
-                }
-                else if let Ok(svar) = v.try_to_relaxed::<String>() {
-                    custom_strings.push((skey, svar));
-                }
-                else {
-                    godot_error!("Non-string, non-int value in ItemResource!");
-                }
+            if !matches!(k.get_type(), VariantType::STRING | VariantType::STRING_NAME) {
+                godot_error!("Non-string key {} in custom data of ItemResource {}!", k, self.id_string);
+                continue;
             }
-            else {
-                godot_error!("Non-string key in ItemResource!");
+            match ItemValue::from_variant(&v) {
+                Some(value) => custom_data.set(k.to::<String>(), &value),
+                None => godot_error!("Unsupported value {} for key {} in custom data of ItemResource {}!", v, k, self.id_string),
             }
         }
 
         Item {
+            id_string: self.id_string.to_string(),
+            count: self.count,
+            custom_data,
+        }
+    }
+
+    /// Returns the custom data value under the key if it exists and has the given type
+    fn get_custom_typed<T: FromGodot>(&self, key: &GString, ty: VariantType) -> Option<T> {
+        self.custom_data.get(key.clone())
+            .filter(|v| v.get_type() == ty)
+            .map(|v| v.to::<T>())
+    }
+
+    /// Converts the definition part of this resource, ignoring count and custom data
+    pub fn to_def(&self) -> ItemDef {
+        let equip_slot = if self.equip_slot.is_empty() {
+            None
+        }
+        else {
+            let slot = EquipSlot::try_from_str(&self.equip_slot.to_string());
+            if slot.is_none() {
+                godot_error!("Invalid equip slot {} in ItemResource {}!", self.equip_slot, self.id_string);
+            }
+            slot
+        };
+
+        let mut stat_bonuses = Vec::new();
+        for (k, v) in self.stat_bonuses.iter_shared() {
+            if let Ok(skey) = k.try_to_relaxed::<String>() && let Ok(bonus) = v.try_to_relaxed::<i32>() {
+                stat_bonuses.push((skey, bonus));
+            }
+            else {
+                godot_error!("Stat bonuses in ItemResource {} should map skill names to ints!", self.id_string);
+            }
+        }
+
+        ItemDef {
             id_string: self.id_string.to_string(),
             name: self.name.to_string(),
             description: self.description.to_string(),
             stackable: self.stackable,
-            count: self.count,
-            custom_ints,
-            custom_floats,
-            custom_strings
+            equip_slot,
+            stat_bonuses,
         }
     }
 
+    #[func]
+    fn has_custom(&self, key: GString) -> bool {
+        self.custom_data.contains_key(key)
+    }
+
+    #[func]
+    /// Returns the bool stored under the key, or the default if there is none or it's not a bool.
+    fn get_custom_bool(&self, key: GString, default: bool) -> bool {
+        self.get_custom_typed(&key, VariantType::BOOL).unwrap_or(default)
+    }
+
+    #[func]
+    /// Returns the int stored under the key, or the default if there is none or it's not an int.
+    fn get_custom_int(&self, key: GString, default: i64) -> i64 {
+        self.get_custom_typed(&key, VariantType::INT).unwrap_or(default)
+    }
+
+    #[func]
+    /// Returns the float stored under the key, or the default if there is none or it's not a float.
+    fn get_custom_float(&self, key: GString, default: f64) -> f64 {
+        self.get_custom_typed(&key, VariantType::FLOAT).unwrap_or(default)
+    }
+
+    #[func]
+    /// Returns the string stored under the key, or the default if there is none or it's not a string.
+    fn get_custom_string(&self, key: GString, default: GString) -> GString {
+        self.get_custom_typed(&key, VariantType::STRING).unwrap_or(default)
+    }
+
+    #[func]
+    /// Returns the Vector2 stored under the key, or the default if there is none or it's not a Vector2.
+    fn get_custom_vector2(&self, key: GString, default: Vector2) -> Vector2 {
+        self.get_custom_typed(&key, VariantType::VECTOR2).unwrap_or(default)
+    }
+
+    #[func]
+    /// Returns the Vector2i stored under the key, or the default if there is none or it's not a Vector2i.
+    fn get_custom_vector2i(&self, key: GString, default: Vector2i) -> Vector2i {
+        self.get_custom_typed(&key, VariantType::VECTOR2I).unwrap_or(default)
+    }
+
     #[func]
     /// If successfully combined, returns null.
     /// 
     /// Otherwise, returns the other item that was attempted to combine into this one.
     fn try_combine(&mut self, other: Gd<ItemResource>) -> Option<Gd<ItemResource>> {
         let ob = other.bind();
-        if self.id_string != ob.id_string || !self.stackable {
-            drop(ob);
-            return Some(other);
-        }
-        else {
-            self.count += ob.count;
-            return None;
+        let combined = self.count.checked_add(ob.count).filter(|c| *c <= MAX_STACK_SIZE);
+        match combined {
+            Some(count) if self.id_string == ob.id_string && self.custom_data == ob.custom_data && self.stackable => {
+                self.count = count;
+                None
+            },
+            _ => {
+                drop(ob);
+                Some(other)
+            },
         }
     }
 
@@ -99,8 +189,8 @@ impl ItemResource {
     /// 
     /// Otherwise, returns null. Can't reduce this item's count to zero.
     fn try_split(&mut self, new_count: i32) -> Option<Gd<ItemResource>> {
-        if self.count <= new_count || !self.stackable {
-            return None;
+        if self.count <= new_count || new_count <= 0 || !self.stackable {
+            None
         }
         else {
             let split_item: Gd<ItemResource> = Gd::from_init_fn(|base| {
@@ -108,56 +198,67 @@ impl ItemResource {
                     id_string: self.id_string.clone(),
                     name: self.name.clone(),
                     description: self.description.clone(),
-                    stackable: self.stackable.clone(),
+                    stackable: self.stackable,
                     count: new_count,
                     custom_data: self.custom_data.clone(),
+                    equip_slot: self.equip_slot.clone(),
+                    stat_bonuses: self.stat_bonuses.clone(),
                     base
                 }
             });
             self.count -= new_count;
 
-            return Some(split_item);
+            Some(split_item)
         }
     }
 }
 
+/// A stack of items as stored in player data.
+/// 
+/// Everything else about the item is looked up in the item registry by id_string.
 #[derive(Clone, Encode, Decode, Debug)]
 pub struct Item {
     id_string: String,
-    name: String,
-    description: String,
-    stackable: bool,
     pub count: i32,
-    custom_ints: Vec<(String, i32)>,
-    custom_floats: Vec<(String, f32)>,
-    custom_strings: Vec<(String, String)>,
+    custom_data: CustomData,
 }
 
 impl Item {
+    pub fn new(id_string: impl ToString, count: i32) -> Self {
+        Self {
+            id_string: id_string.to_string(),
+            count,
+            custom_data: CustomData::default(),
+        }
+    }
+
     pub fn to_resource(&self) -> Gd<ItemResource> {
         let mut custom_data = Dictionary::new();
 
-        for (key, value) in &self.custom_ints {
-            custom_data.set(key.as_str(), *value);
-        }
-        for (key, value) in &self.custom_floats {
-            custom_data.set(key.as_str(), *value);
-        }
-        for (key, value) in &self.custom_strings {
-            custom_data.set(key.as_str(), value.as_str());
+        for (key, value) in self.custom_data.iter() {
+            custom_data.set(key, value.to_variant());
         }
 
-        Gd::from_init_fn(|a| {
-            ItemResource {
-                id_string: GString::from(&self.id_string),
-                name: GString::from(&self.name),
-                description: GString::from(&self.description),
-                stackable: self.stackable,
-                count: self.count,
-                custom_data,
-
-                base: a
+        itemregistry::with_def(&self.id_string, |def| {
+            let mut stat_bonuses = Dictionary::new();
+            for (key, value) in &def.stat_bonuses {
+                stat_bonuses.set(key.as_str(), *value);
             }
+
+            Gd::from_init_fn(|a| {
+                ItemResource {
+                    id_string: GString::from(&self.id_string),
+                    name: GString::from(&def.name),
+                    description: GString::from(&def.description),
+                    stackable: def.stackable,
+                    count: self.count,
+                    custom_data,
+                    equip_slot: def.equip_slot.map(|s| s.as_str()).unwrap_or("").into(),
+                    stat_bonuses,
+
+                    base: a
+                }
+            })
         })
     }
 
@@ -165,30 +266,68 @@ impl Item {
         &self.id_string
     }
 
+    pub fn custom_data(&self) -> &CustomData {
+        &self.custom_data
+    }
+
+    pub fn custom_data_mut(&mut self) -> &mut CustomData {
+        &mut self.custom_data
+    }
+
+    /// Whether the item's id_string is in the item registry
+    pub fn is_known(&self) -> bool {
+        itemregistry::is_registered(&self.id_string)
+    }
+
     pub fn stackable(&self) -> bool {
-        self.stackable
+        itemregistry::with_def(&self.id_string, |def| def.stackable)
+    }
+
+    pub fn equip_slot(&self) -> Option<EquipSlot> {
+        itemregistry::with_def(&self.id_string, |def| def.equip_slot)
+    }
+
+    /// Bonus this item gives to the given stat while equipped
+    pub fn stat_bonus(&self, stat: &str) -> i32 {
+        itemregistry::with_def(&self.id_string, |def| {
+            def.stat_bonuses.iter()
+                .filter(|(s, _)| s == stat)
+                .fold(0i32, |total, (_, bonus)| total.saturating_add(*bonus))
+        })
+    }
+
+    /// Whether the count is positive and fits in a single stack, which is just one item for unstackable items
+    pub fn is_valid(&self) -> bool {
+        self.count > 0 && self.count <= MAX_STACK_SIZE && (self.count == 1 || self.stackable())
+    }
+
+    /// Whether the other item can be put into the same stack as this one, ignoring stack size.
+    /// Items with different custom data are kept apart so neither loses its data.
+    pub fn stacks_with(&self, other: &Item) -> bool {
+        self.id_string == other.id_string && self.custom_data == other.custom_data && self.stackable()
     }
 
+    /// Returns the other item back if it can't be combined into this one without going over [MAX_STACK_SIZE]
     pub fn try_combine(&mut self, other: Item) -> Option<Item> {
-        if self.id_string != other.id_string || !self.stackable {
-            return Some(other);
-        }
-        else {
-            self.count += other.count;
-            return None;
+        match self.count.checked_add(other.count) {
+            Some(combined) if self.stacks_with(&other) && other.count > 0 && combined <= MAX_STACK_SIZE => {
+                self.count = combined;
+                None
+            },
+            _ => Some(other),
         }
     }
 
     pub fn try_split(&mut self, new_count: i32) -> Option<Item> {
-        if self.count <= new_count || !self.stackable {
-            return None;
+        if self.count <= new_count || new_count <= 0 || !self.stackable() {
+            None
         }
         else {
             let mut split_item = self.clone();
             split_item.count = new_count;
             self.count -= new_count;
 
-            return Some(split_item);
+            Some(split_item)
         }
     }
 }
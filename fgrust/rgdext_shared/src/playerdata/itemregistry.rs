@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use godot::{classes::{DirAccess, ResourceLoader}, prelude::*};
+
+use super::{equipment::EquipSlot, item::ItemResource};
+
+
+registry_storage!(
+    /// id_string -> definition of every item known to this server or client
+    item_defs: HashMap<String, ItemDef>
+);
+
+/// Everything about an item that is the same for all of its instances.
+///
+/// Only the id_string, count and custom data are stored in player data, the rest is looked up here.
+#[derive(Clone, Debug)]
+pub struct ItemDef {
+    pub id_string: String,
+    pub name: String,
+    pub description: String,
+    pub stackable: bool,
+    pub equip_slot: Option<EquipSlot>,
+    pub stat_bonuses: Vec<(String, i32)>,
+}
+
+impl ItemDef {
+    /// Definition used for items whose id_string isn't registered
+    fn unknown(id_string: &str) -> Self {
+        Self {
+            id_string: id_string.to_string(),
+            name: id_string.to_string(),
+            description: String::new(),
+            stackable: false,
+            equip_slot: None,
+            stat_bonuses: Vec::new(),
+        }
+    }
+}
+
+/// Adds the definition to the registry, replacing any previous definition with the same id_string
+pub fn register(def: ItemDef) {
+    item_defs().write().unwrap().insert(def.id_string.clone(), def);
+}
+
+pub fn is_registered(id_string: &str) -> bool {
+    item_defs().read().unwrap().contains_key(id_string)
+}
+
+/// Runs the closure with the definition of the given item, or a placeholder definition if it isn't registered
+pub fn with_def<R>(id_string: &str, f: impl FnOnce(&ItemDef) -> R) -> R {
+    let defs = item_defs().read().unwrap();
+    match defs.get(id_string) {
+        Some(def) => f(def),
+        None => f(&ItemDef::unknown(id_string)),
+    }
+}
+
+/// Registers every ItemResource saved as a .tres file directly in the given directory.
+///
+/// Returns the amount of items registered.
+pub fn load_directory(path: &str) -> i32 {
+    let mut loaded = 0;
+    for file in DirAccess::get_files_at(path).as_slice() {
+        // Exported projects only have the .remap files listed, but loading by the original name still works
+        let file = file.to_string();
+        let file = file.strip_suffix(".remap").unwrap_or(&file);
+        if !file.ends_with(".tres") {
+            continue;
+        }
+
+        let resource_path = format!("{}/{}", path.trim_end_matches('/'), file);
+        match ResourceLoader::singleton().load(&resource_path).and_then(|r| r.try_cast::<ItemResource>().ok()) {
+            Some(item) => {
+                let def = item.bind().to_def();
+                if is_registered(&def.id_string) {
+                    godot_error!("Item id {} registered twice, the definition in {} overrides the previous one", def.id_string, resource_path);
+                }
+                register(def);
+                loaded += 1;
+            },
+            None => godot_error!("{} is not an ItemResource", resource_path),
+        }
+    }
+    loaded
+}
+
+#[derive(GodotClass)]
+#[class(no_init, base=Object)]
+/// Registry of all item definitions, loaded at startup from the ItemResource files in a directory.
+///
+/// Display data of items (name, description, etc.) always comes from here.
+pub struct ItemRegistry {
+    base: Base<Object>
+}
+
+#[godot_api]
+impl ItemRegistry {
+    #[func]
+    /// Registers every ItemResource .tres file in the directory, returns the amount of items registered.
+    fn load_directory(path: GString) -> i32 {
+        load_directory(&path.to_string())
+    }
+
+    #[func]
+    fn has_item(id_string: GString) -> bool {
+        is_registered(&id_string.to_string())
+    }
+
+    #[func]
+    /// Returns a new ItemResource with a count of 1, or null if the id_string isn't registered.
+    fn create_item(id_string: GString) -> Option<Gd<ItemResource>> {
+        let id_string = id_string.to_string();
+        if !is_registered(&id_string) {
+            return None;
+        }
+        Some(super::item::Item::new(id_string, 1).to_resource())
+    }
+
+    #[func]
+    fn get_item_ids() -> Array<GString> {
+        let mut arr = Array::new();
+        for id_string in item_defs().read().unwrap().keys() {
+            arr.push(id_string.as_str());
+        }
+        arr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::playerdata::{item::Item, testutil::register_test_items, PlayerData};
+
+    #[test]
+    fn test_unknown_item_ids() {
+        register_test_items();
+        let mut data = PlayerData::default();
+        data.items[0] = Some(Item::new("oak_wood", 1));
+        data.items[1] = Some(Item::new("removed_item", 1));
+        data.items[2] = Some(Item::new("removed_item", 1));
+        data.bank[0] = Some(Item::new("old_bank_item", 1));
+
+        assert_eq!(data.unknown_item_ids(), vec!["old_bank_item".to_string(), "removed_item".to_string()]);
+        // Unknown items don't stack
+        assert!(!data.items[1].as_ref().unwrap().stacks_with(data.items[2].as_ref().unwrap()));
+    }
+}
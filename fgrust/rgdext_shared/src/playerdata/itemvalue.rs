@@ -0,0 +1,329 @@
+use godot::prelude::*;
+use bitcode::{Encode, Decode};
+
+
+/// A single value in an item's custom data.
+///
+/// Mirrors the Variant types that can be stored in ItemResource's custom_data, so that values
+/// keep their exact type when going from a Dictionary to player data and back.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ItemValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Vector2(f32, f32),
+    Vector2i(i32, i32),
+    Array(Vec<ItemValue>),
+    /// Key-value pairs in insertion order, keys can be any other ItemValue
+    Dictionary(Vec<(ItemValue, ItemValue)>),
+}
+
+/// Non-recursive form of [ItemValue] for encoding, since bitcode can't derive recursive types.
+///
+/// Values are stored in pre-order, containers are followed by their elements
+/// (or key-value pairs, one after the other for dictionaries).
+#[derive(Clone, PartialEq, Encode, Decode, Debug)]
+enum FlatValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Vector2(f32, f32),
+    Vector2i(i32, i32),
+    Array(u32),
+    Dictionary(u32),
+}
+
+/// Custom data of an item or flags of a player, string keys to [ItemValue]s in insertion order.
+#[derive(Clone, Default, PartialEq, Encode, Decode, Debug)]
+pub struct CustomData {
+    entries: Vec<(String, Vec<FlatValue>)>,
+}
+
+impl CustomData {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.entries.iter().any(|(k, _)| k == key)
+    }
+
+    pub fn get(&self, key: &str) -> Option<ItemValue> {
+        self.entries.iter()
+            .find(|(k, _)| k == key)
+            .and_then(|(_, flat)| ItemValue::unflatten(&mut flat.iter()))
+    }
+
+    /// Sets the value under the key, replacing the previous one
+    pub fn set(&mut self, key: impl ToString, value: &ItemValue) {
+        let key = key.to_string();
+        let mut flat = Vec::new();
+        value.flatten(&mut flat);
+        match self.entries.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = flat,
+            None => self.entries.push((key, flat)),
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<ItemValue> {
+        let index = self.entries.iter().position(|(k, _)| k == key)?;
+        ItemValue::unflatten(&mut self.entries.remove(index).1.iter())
+    }
+
+    /// Iterates over all key-value pairs, skipping values that fail to decode
+    pub fn iter(&self) -> impl Iterator<Item = (&str, ItemValue)> {
+        self.entries.iter()
+            .filter_map(|(k, flat)| Some((k.as_str(), ItemValue::unflatten(&mut flat.iter())?)))
+    }
+}
+
+impl ItemValue {
+    fn flatten(&self, out: &mut Vec<FlatValue>) {
+        match self {
+            ItemValue::Bool(b) => out.push(FlatValue::Bool(*b)),
+            ItemValue::Int(i) => out.push(FlatValue::Int(*i)),
+            ItemValue::Float(f) => out.push(FlatValue::Float(*f)),
+            ItemValue::String(s) => out.push(FlatValue::String(s.clone())),
+            ItemValue::Vector2(x, y) => out.push(FlatValue::Vector2(*x, *y)),
+            ItemValue::Vector2i(x, y) => out.push(FlatValue::Vector2i(*x, *y)),
+            ItemValue::Array(arr) => {
+                out.push(FlatValue::Array(arr.len() as u32));
+                for element in arr {
+                    element.flatten(out);
+                }
+            },
+            ItemValue::Dictionary(dict) => {
+                out.push(FlatValue::Dictionary(dict.len() as u32));
+                for (key, value) in dict {
+                    key.flatten(out);
+                    value.flatten(out);
+                }
+            },
+        }
+    }
+
+    /// Reads a single value from the flat values, returns None if they run out too early
+    fn unflatten<'a>(flat: &mut impl Iterator<Item = &'a FlatValue>) -> Option<ItemValue> {
+        let value = match flat.next()? {
+            FlatValue::Bool(b) => ItemValue::Bool(*b),
+            FlatValue::Int(i) => ItemValue::Int(*i),
+            FlatValue::Float(f) => ItemValue::Float(*f),
+            FlatValue::String(s) => ItemValue::String(s.clone()),
+            FlatValue::Vector2(x, y) => ItemValue::Vector2(*x, *y),
+            FlatValue::Vector2i(x, y) => ItemValue::Vector2i(*x, *y),
+            FlatValue::Array(len) => {
+                let mut arr = Vec::new();
+                for _ in 0..*len {
+                    arr.push(ItemValue::unflatten(flat)?);
+                }
+                ItemValue::Array(arr)
+            },
+            FlatValue::Dictionary(len) => {
+                let mut dict = Vec::new();
+                for _ in 0..*len {
+                    dict.push((ItemValue::unflatten(flat)?, ItemValue::unflatten(flat)?));
+                }
+                ItemValue::Dictionary(dict)
+            },
+        };
+        Some(value)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            ItemValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            ItemValue::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            ItemValue::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ItemValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_vector2(&self) -> Option<(f32, f32)> {
+        match self {
+            ItemValue::Vector2(x, y) => Some((*x, *y)),
+            _ => None,
+        }
+    }
+
+    pub fn as_vector2i(&self) -> Option<(i32, i32)> {
+        match self {
+            ItemValue::Vector2i(x, y) => Some((*x, *y)),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[ItemValue]> {
+        match self {
+            ItemValue::Array(arr) => Some(arr),
+            _ => None,
+        }
+    }
+
+    pub fn as_dictionary(&self) -> Option<&[(ItemValue, ItemValue)]> {
+        match self {
+            ItemValue::Dictionary(dict) => Some(dict),
+            _ => None,
+        }
+    }
+}
+
+impl ItemValue {
+    /// Converts the Variant, returning None if it (or anything nested in it) is of an unsupported type.
+    ///
+    /// StringNames are converted to Strings.
+    pub fn from_variant(v: &Variant) -> Option<ItemValue> {
+        let value = match v.get_type() {
+            VariantType::BOOL => ItemValue::Bool(v.to::<bool>()),
+            VariantType::INT => ItemValue::Int(v.to::<i64>()),
+            VariantType::FLOAT => ItemValue::Float(v.to::<f64>()),
+            VariantType::STRING | VariantType::STRING_NAME => ItemValue::String(v.to::<String>()),
+            VariantType::VECTOR2 => {
+                let vec = v.to::<Vector2>();
+                ItemValue::Vector2(vec.x, vec.y)
+            },
+            VariantType::VECTOR2I => {
+                let vec = v.to::<Vector2i>();
+                ItemValue::Vector2i(vec.x, vec.y)
+            },
+            VariantType::ARRAY => {
+                let mut arr = Vec::new();
+                for element in v.try_to::<VariantArray>().ok()?.iter_shared() {
+                    arr.push(ItemValue::from_variant(&element)?);
+                }
+                ItemValue::Array(arr)
+            },
+            VariantType::DICTIONARY => {
+                let mut dict = Vec::new();
+                for (key, value) in v.to::<Dictionary>().iter_shared() {
+                    dict.push((ItemValue::from_variant(&key)?, ItemValue::from_variant(&value)?));
+                }
+                ItemValue::Dictionary(dict)
+            },
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    pub fn to_variant(&self) -> Variant {
+        match self {
+            ItemValue::Bool(b) => b.to_variant(),
+            ItemValue::Int(i) => i.to_variant(),
+            ItemValue::Float(f) => f.to_variant(),
+            ItemValue::String(s) => GString::from(s).to_variant(),
+            ItemValue::Vector2(x, y) => Vector2::new(*x, *y).to_variant(),
+            ItemValue::Vector2i(x, y) => Vector2i::new(*x, *y).to_variant(),
+            ItemValue::Array(arr) => {
+                let mut varr = VariantArray::new();
+                for element in arr {
+                    varr.push(&element.to_variant());
+                }
+                varr.to_variant()
+            },
+            ItemValue::Dictionary(dict) => {
+                let mut vdict = Dictionary::new();
+                for (key, value) in dict {
+                    vdict.set(key.to_variant(), value.to_variant());
+                }
+                vdict.to_variant()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playerdata::{item::Item, testutil::register_test_items, PlayerData};
+
+    #[test]
+    fn test_bitcode_roundtrip() {
+        let value = ItemValue::Dictionary(vec![
+            (ItemValue::String("sharpened".into()), ItemValue::Bool(true)),
+            (ItemValue::Int(3), ItemValue::Float(0.1)),
+            (ItemValue::String("offset".into()), ItemValue::Vector2(1.5, -2.)),
+            (ItemValue::String("tile".into()), ItemValue::Vector2i(-7, 12)),
+            (ItemValue::String("history".into()), ItemValue::Array(vec![
+                ItemValue::Int(i64::MAX),
+                ItemValue::String("12".into()),
+                ItemValue::Array(vec![]),
+            ])),
+        ]);
+
+        let mut data = CustomData::default();
+        data.set("nested", &value);
+        data.set("float", &ItemValue::Float(2.5));
+        data.set("float", &ItemValue::Float(-0.25));
+
+        let decoded: CustomData = bitcode::decode(&bitcode::encode(&data)).unwrap();
+        assert_eq!(decoded.get("nested"), Some(value));
+        assert_eq!(decoded.get("float"), Some(ItemValue::Float(-0.25)));
+        assert_eq!(decoded.get("missing"), None);
+        assert_eq!(decoded.iter().map(|(k, _)| k).collect::<Vec<_>>(), vec!["nested", "float"]);
+    }
+
+    #[test]
+    fn test_truncated_value() {
+        let flat = [FlatValue::Array(2), FlatValue::Int(1)];
+        assert_eq!(ItemValue::unflatten(&mut flat.iter()), None);
+    }
+
+    #[test]
+    fn test_accessors_dont_coerce() {
+        assert_eq!(ItemValue::Int(1).as_int(), Some(1));
+        assert_eq!(ItemValue::Int(1).as_float(), None);
+        assert_eq!(ItemValue::Int(1).as_bool(), None);
+        assert_eq!(ItemValue::Float(1.).as_int(), None);
+        assert_eq!(ItemValue::String("1".into()).as_int(), None);
+        assert_eq!(ItemValue::String("1".into()).as_str(), Some("1"));
+        assert_eq!(ItemValue::Bool(false).as_bool(), Some(false));
+        assert_eq!(ItemValue::Vector2i(1, 2).as_vector2(), None);
+        assert_eq!(ItemValue::Vector2i(1, 2).as_vector2i(), Some((1, 2)));
+    }
+
+    #[test]
+    fn test_custom_data_survives_save() {
+        register_test_items();
+        let mut axe = Item::new("axe", 1);
+        let custom_data = axe.custom_data_mut();
+        custom_data.set("tool", &ItemValue::String("axe".into()));
+        custom_data.set("power", &ItemValue::Int(1));
+        custom_data.set("power", &ItemValue::Int(2));
+        custom_data.set("sharpened", &ItemValue::Bool(true));
+
+        let mut data = PlayerData::default();
+        data.items[0] = Some(axe);
+        let decoded = PlayerData::from_bytes(&data.to_bytes()).unwrap();
+        let axe = decoded.items[0].as_ref().unwrap();
+
+        let custom_data = axe.custom_data();
+        assert_eq!(custom_data.get("tool").as_ref().and_then(|v| v.as_str()), Some("axe"));
+        assert_eq!(custom_data.get("power").and_then(|v| v.as_int()), Some(2));
+        assert_eq!(custom_data.get("sharpened").and_then(|v| v.as_bool()), Some(true));
+        assert_eq!(custom_data.get("missing"), None);
+    }
+}
@@ -0,0 +1,160 @@
+//! Player data as it was stored before the encoding got a format version, kept around to migrate old saves.
+//!
+//! The structs mirror the old layout field for field, since bitcode only goes by field order and types.
+use bitcode::Decode;
+
+use super::{equipment::EquipSlot, item::Item, itemvalue::ItemValue, MAX_ITEMS, PlayerData};
+
+
+/// Skill ids in the order the old fixed-size skill arrays stored them
+const SKILLS: [&str; 10] = ["woodcutting", "mining", "smelting", "crafting", "farming", "strength", "agility", "endurance", "magic", "ranged"];
+
+#[derive(Decode)]
+struct LegacyItem {
+    id_string: String,
+    _name: String,
+    _description: String,
+    _stackable: bool,
+    count: i32,
+    custom_ints: Vec<(String, i32)>,
+    custom_floats: Vec<(String, f32)>,
+    custom_strings: Vec<(String, String)>,
+}
+
+impl LegacyItem {
+    fn migrate(self) -> Item {
+        let mut item = Item::new(self.id_string, self.count);
+        let custom_data = item.custom_data_mut();
+        for (key, value) in self.custom_ints {
+            custom_data.set(key, &ItemValue::Int(value as i64));
+        }
+        for (key, value) in self.custom_floats {
+            custom_data.set(key, &ItemValue::Float(value as f64));
+        }
+        for (key, value) in self.custom_strings {
+            custom_data.set(key, &ItemValue::String(value));
+        }
+        item
+    }
+}
+
+#[derive(Decode)]
+struct LegacySkills {
+    skills: [u8; SKILLS.len()],
+}
+
+#[derive(Decode)]
+struct LegacySkillProgress {
+    skill_progress: [i32; SKILLS.len()],
+}
+
+#[derive(Decode)]
+struct LegacyPlayerData {
+    name: String,
+    pid: i32,
+
+    server_name: String,
+    location: String,
+    x: i32,
+    y: i32,
+
+    skills: LegacySkills,
+    skill_progress: LegacySkillProgress,
+    gold: i32,
+    equipped_item: Option<LegacyItem>,
+    items: [Option<LegacyItem>; MAX_ITEMS],
+    friends: Vec<i32>,
+}
+
+/// Decodes player data in the old layout and carries it over to the current one.
+///
+/// The old single equipped item goes into the slot its definition declares, or the main hand if it's unknown.
+/// The player starts out at full health, as the old data didn't track it.
+pub(super) fn decode(b: &[u8]) -> Result<PlayerData, bitcode::Error> {
+    let old: LegacyPlayerData = bitcode::decode(b)?;
+
+    let mut data = PlayerData {
+        name: old.name,
+        pid: old.pid,
+        server_name: old.server_name,
+        location: old.location,
+        x: old.x,
+        y: old.y,
+        gold: old.gold,
+        friends: old.friends,
+        ..Default::default()
+    };
+    for (i, skill) in SKILLS.iter().enumerate() {
+        data.skills.set_level(skill, old.skills.skills[i] as i32);
+        data.skill_progress.set_xp(skill, old.skill_progress.skill_progress[i]);
+    }
+    for (slot, item) in data.items.iter_mut().zip(old.items) {
+        *slot = item.map(LegacyItem::migrate);
+    }
+    if let Some(item) = old.equipped_item {
+        let item = item.migrate();
+        let slot = item.equip_slot().unwrap_or(EquipSlot::MainHand);
+        data.equipment[slot] = Some(item);
+    }
+    data.hp = data.max_hp();
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playerdata::{skillregistry::{self, SkillDef}, testutil::register_test_items, BANK_SIZE, FORMAT_VERSION};
+
+    /// Encoded by the code from before versioning: "Alice" with pid 7 on map "docks" of server "main" at (12, -3),
+    /// 250 gold, woodcutting 4 with 900 xp, endurance 2 with 150 xp, an axe with power 2 equipped,
+    /// 17 oak_wood in slot 0, a fairy with age 30, glow 0.5 and owner "Bob" in slot 3, and friends 3 and 9.
+    const OLD_SAVE: [u8; 154] = [
+        5, 65, 108, 105, 99, 101, 4, 7, 4, 109, 97, 105, 110, 5, 100, 111, 99, 107, 115, 4, 12, 0, 253, 255, 255, 255,
+        5, 1, 3, 64, 0, 2, 132, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 150, 0, 0, 0, 0, 0, 4, 250, 1, 3, 97, 120, 101,
+        3, 97, 120, 101, 0, 0, 4, 1, 1, 5, 112, 111, 119, 101, 114, 4, 2, 0, 0, 9, 0, 0, 0, 0, 8, 5, 111, 97, 107, 95,
+        119, 111, 111, 100, 102, 97, 105, 114, 121, 8, 5, 111, 97, 107, 95, 119, 111, 111, 100, 102, 97, 105, 114, 121,
+        0, 0, 3, 4, 17, 1, 0, 1, 3, 97, 103, 101, 4, 30, 0, 1, 4, 103, 108, 111, 119, 0, 0, 0, 63, 0, 1, 5, 111, 119,
+        110, 101, 114, 3, 66, 111, 98, 2, 4, 3, 9,
+    ];
+
+    #[test]
+    fn test_decode_old_save() {
+        register_test_items();
+        skillregistry::register(SkillDef::with_curve("endurance", "Endurance", 99, 100., 2.));
+        let data = PlayerData::from_bytes(&OLD_SAVE).unwrap();
+
+        assert_eq!((data.name.as_str(), data.pid, data.server_name.as_str()), ("Alice", 7, "main"));
+        assert_eq!((data.location.as_str(), data.x, data.y), ("docks", 12, -3));
+        assert_eq!(data.gold, 250);
+        assert_eq!(data.friends, vec![3, 9]);
+        assert_eq!((data.skills.level("woodcutting"), data.skill_progress.xp("woodcutting")), (4, 900));
+        assert_eq!((data.skills.level("endurance"), data.skill_progress.xp("endurance")), (2, 150));
+        assert_eq!(data.skills.level("mining"), 1);
+        assert_eq!(data.hp, data.max_hp());
+        assert_eq!(data.bank.len(), BANK_SIZE);
+
+        let axe = data.equipment[EquipSlot::MainHand].as_ref().unwrap();
+        assert_eq!((axe.id_string(), axe.count), ("axe", 1));
+        assert_eq!(axe.custom_data().get("power"), Some(ItemValue::Int(2)));
+
+        let oak_wood = data.items[0].as_ref().unwrap();
+        assert_eq!((oak_wood.id_string(), oak_wood.count), ("oak_wood", 17));
+        let fairy = data.items[3].as_ref().unwrap();
+        assert_eq!(fairy.custom_data().get("age"), Some(ItemValue::Int(30)));
+        assert_eq!(fairy.custom_data().get("glow"), Some(ItemValue::Float(0.5)));
+        assert_eq!(fairy.custom_data().get("owner"), Some(ItemValue::String("Bob".into())));
+        assert_eq!(data.items.iter().flatten().count(), 2);
+
+        // Saved again in the current format
+        let bytes = data.to_bytes();
+        assert_eq!(bytes[0], FORMAT_VERSION);
+        assert_eq!(PlayerData::from_bytes(&bytes).unwrap().to_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_decode_garbage_fails() {
+        assert!(PlayerData::from_bytes(&[]).is_err());
+        assert!(PlayerData::from_bytes(&[FORMAT_VERSION, 1, 2, 3]).is_err());
+        assert!(PlayerData::from_bytes(&OLD_SAVE[..100]).is_err());
+    }
+}
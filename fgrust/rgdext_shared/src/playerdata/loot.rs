@@ -0,0 +1,364 @@
+use godot::prelude::*;
+
+use super::rng::SeededRng;
+
+
+/// Max depth of nested loot tables, deeper tables (e.g. ones that contain themselves) are cut off
+const MAX_TABLE_DEPTH: usize = 16;
+
+#[derive(Clone, Debug)]
+pub enum LootDrop {
+    Nothing,
+    Item(String),
+    /// The nested table is rolled, once per count
+    Table(LootTable),
+}
+
+#[derive(Clone, Debug)]
+pub struct LootEntry {
+    pub drop: LootDrop,
+    /// If true, the entry isn't part of the weighted rolls and instead drops with its own chance once per roll of the table
+    pub independent: bool,
+    pub weight: f64,
+    pub chance: f64,
+    pub min_count: i32,
+    pub max_count: i32,
+    /// Added to the weight per level of the table's skill
+    pub level_weight_bonus: f64,
+    /// Added to the chance per level of the table's skill
+    pub level_chance_bonus: f64,
+}
+
+impl LootEntry {
+    pub fn item(id_string: impl ToString, weight: f64, min_count: i32, max_count: i32) -> Self {
+        Self {
+            drop: LootDrop::Item(id_string.to_string()),
+            independent: false,
+            weight,
+            chance: 1.,
+            min_count,
+            max_count,
+            level_weight_bonus: 0.,
+            level_chance_bonus: 0.,
+        }
+    }
+
+    fn effective_weight(&self, level: i32) -> f64 {
+        (self.weight + self.level_weight_bonus * level as f64).max(0.)
+    }
+
+    fn effective_chance(&self, level: i32) -> f64 {
+        (self.chance + self.level_chance_bonus * level as f64).clamp(0., 1.)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LootTable {
+    pub min_rolls: i32,
+    pub max_rolls: i32,
+    /// Skill whose level modifies the weights and chances of the entries, empty for none
+    pub skill: String,
+    pub entries: Vec<LootEntry>,
+}
+
+impl LootTable {
+    /// Rolls the table, returning (id_string, count) pairs with one pair per item.
+    ///
+    /// level_of gives the player's level in a skill, for the level bonuses.
+    pub fn roll(&self, rng: &mut SeededRng, level_of: &impl Fn(&str) -> i32) -> Vec<(String, i32)> {
+        let mut drops = Vec::new();
+        self.roll_into(rng, level_of, &mut drops);
+        drops
+    }
+
+    fn roll_into(&self, rng: &mut SeededRng, level_of: &impl Fn(&str) -> i32, drops: &mut Vec<(String, i32)>) {
+        let level = if self.skill.is_empty() {0} else {level_of(&self.skill)};
+
+        for _ in 0..rng.range(self.min_rolls, self.max_rolls) {
+            let weighted = self.entries.iter().filter(|e| !e.independent);
+            let total: f64 = weighted.clone().map(|e| e.effective_weight(level)).sum();
+            if total > 0. {
+                let mut target = rng.next_f64() * total;
+                // Falls back to the last entry with weight in case of float rounding
+                let mut picked = None;
+                for entry in weighted {
+                    let weight = entry.effective_weight(level);
+                    if weight <= 0. {continue;}
+                    picked = Some(entry);
+                    if target < weight {break;}
+                    target -= weight;
+                }
+                if let Some(entry) = picked {
+                    Self::give_entry(entry, rng, level_of, drops);
+                }
+            }
+
+            for entry in self.entries.iter().filter(|e| e.independent) {
+                if rng.next_f64() < entry.effective_chance(level) {
+                    Self::give_entry(entry, rng, level_of, drops);
+                }
+            }
+        }
+    }
+
+    fn give_entry(entry: &LootEntry, rng: &mut SeededRng, level_of: &impl Fn(&str) -> i32, drops: &mut Vec<(String, i32)>) {
+        let count = rng.range(entry.min_count, entry.max_count);
+        if count <= 0 {
+            return;
+        }
+        match &entry.drop {
+            LootDrop::Nothing => {},
+            LootDrop::Item(id_string) => {
+                match drops.iter_mut().find(|(id, _)| id == id_string) {
+                    Some((_, total)) => *total = total.saturating_add(count),
+                    None => drops.push((id_string.clone(), count)),
+                }
+            },
+            LootDrop::Table(table) => {
+                for _ in 0..count {
+                    table.roll_into(rng, level_of, drops);
+                }
+            },
+        }
+    }
+}
+
+#[derive(GodotClass)]
+#[class(base=Resource)]
+/// A weighted list of drops, rolled server-side with [method ScriptResponse.give_loot].
+///
+/// Every roll picks one of the non-independent entries by weight, and gives each independent entry with its own chance.
+pub struct LootTableResource {
+    #[export]
+    min_rolls: i32,
+    #[export]
+    max_rolls: i32,
+    #[export]
+    /// Skill whose level adds the entries' level bonuses to their weights and chances, leave empty for none.
+    skill: GString,
+    #[export]
+    entries: Array<Gd<LootEntryResource>>,
+
+    base: Base<Resource>,
+}
+
+#[godot_api]
+impl IResource for LootTableResource {
+    fn init(base: Base<Resource>) -> Self {
+        Self {
+            min_rolls: 1,
+            max_rolls: 1,
+            skill: "".into(),
+            entries: Array::new(),
+            base,
+        }
+    }
+}
+
+impl LootTableResource {
+    pub fn to_table(&self) -> LootTable {
+        self.to_table_at_depth(0)
+    }
+
+    fn to_table_at_depth(&self, depth: usize) -> LootTable {
+        LootTable {
+            min_rolls: self.min_rolls,
+            max_rolls: self.max_rolls,
+            skill: self.skill.to_string(),
+            entries: self.entries.iter_shared().map(|e| e.bind().to_entry(depth)).collect(),
+        }
+    }
+}
+
+#[derive(GodotClass)]
+#[class(base=Resource)]
+/// Single entry of a LootTableResource, either an item or a nested table. Leave both empty to drop nothing.
+pub struct LootEntryResource {
+    #[export]
+    /// id_string of the dropped item.
+    item_id: GString,
+    #[export]
+    /// Nested table rolled instead of dropping an item, once per count.
+    table: Option<Gd<LootTableResource>>,
+    #[export]
+    /// If true, the entry isn't part of the weighted rolls and instead drops with its own chance once per roll.
+    independent: bool,
+    #[export]
+    weight: f64,
+    #[export]
+    /// Only used by independent entries, 1.0 means always.
+    chance: f64,
+    #[export]
+    min_count: i32,
+    #[export]
+    max_count: i32,
+    #[export]
+    /// Added to the weight per level of the table's skill.
+    level_weight_bonus: f64,
+    #[export]
+    /// Added to the chance per level of the table's skill.
+    level_chance_bonus: f64,
+
+    base: Base<Resource>,
+}
+
+#[godot_api]
+impl IResource for LootEntryResource {
+    fn init(base: Base<Resource>) -> Self {
+        Self {
+            item_id: "".into(),
+            table: None,
+            independent: false,
+            weight: 1.,
+            chance: 1.,
+            min_count: 1,
+            max_count: 1,
+            level_weight_bonus: 0.,
+            level_chance_bonus: 0.,
+            base,
+        }
+    }
+}
+
+impl LootEntryResource {
+    fn to_entry(&self, depth: usize) -> LootEntry {
+        let drop = match &self.table {
+            Some(_) if depth >= MAX_TABLE_DEPTH => {
+                godot_error!("Loot tables nested deeper than {} levels, cutting them off", MAX_TABLE_DEPTH);
+                LootDrop::Nothing
+            },
+            Some(table) => LootDrop::Table(table.bind().to_table_at_depth(depth + 1)),
+            None if self.item_id.is_empty() => LootDrop::Nothing,
+            None => LootDrop::Item(self.item_id.to_string()),
+        };
+        LootEntry {
+            drop,
+            independent: self.independent,
+            weight: self.weight,
+            chance: self.chance,
+            min_count: self.min_count,
+            max_count: self.max_count,
+            level_weight_bonus: self.level_weight_bonus,
+            level_chance_bonus: self.level_chance_bonus,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playerdata::{item::Item, testutil::register_test_items, PlayerData};
+
+    const ROLLS: i32 = 10000;
+
+    fn no_levels(_: &str) -> i32 {0}
+
+    /// Rolls the table many times, returning the total count of the given item
+    fn total_of(table: &LootTable, rng: &mut SeededRng, id_string: &str, level_of: &impl Fn(&str) -> i32) -> i32 {
+        (0..ROLLS)
+            .flat_map(|_| table.roll(rng, level_of))
+            .filter(|(id, _)| id == id_string)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    fn common_rare() -> LootTable {
+        LootTable {
+            min_rolls: 1,
+            max_rolls: 1,
+            skill: "magic".into(),
+            entries: vec![
+                LootEntry::item("common", 9., 1, 1),
+                LootEntry {level_weight_bonus: 1., ..LootEntry::item("rare", 1., 1, 1)},
+            ],
+        }
+    }
+
+    #[test]
+    fn test_same_seed_same_loot() {
+        let table = common_rare();
+        let mut a = SeededRng::new(1234);
+        let mut b = SeededRng::new(1234);
+        for _ in 0..100 {
+            assert_eq!(table.roll(&mut a, &no_levels), table.roll(&mut b, &no_levels));
+        }
+    }
+
+    #[test]
+    fn test_weights() {
+        let table = common_rare();
+        let mut rng = SeededRng::new(42);
+        let rare = total_of(&table, &mut rng, "rare", &no_levels);
+        // 10% expected
+        assert!((800..1200).contains(&rare), "{rare}");
+
+        // At level 9 the weights are 9:10
+        let rare = total_of(&table, &mut rng, "rare", &|_| 9);
+        assert!((5000..5500).contains(&rare), "{rare}");
+    }
+
+    #[test]
+    fn test_independent_chance_and_counts() {
+        let table = LootTable {
+            min_rolls: 2,
+            max_rolls: 2,
+            skill: String::new(),
+            entries: vec![
+                LootEntry::item("bones", 1., 1, 1),
+                LootEntry {independent: true, chance: 0.25, ..LootEntry::item("gem", 0., 2, 4)},
+            ],
+        };
+        let mut rng = SeededRng::new(7);
+        for _ in 0..100 {
+            let drops = table.roll(&mut rng, &no_levels);
+            assert_eq!(drops[0], ("bones".to_string(), 2));
+            if let Some((_, gems)) = drops.get(1) {
+                assert!((2..=8).contains(gems));
+            }
+        }
+        // 2 rolls * 25% * 3 gems on average
+        let gems = total_of(&table, &mut rng, "gem", &no_levels);
+        assert!((13500..16500).contains(&gems), "{gems}");
+    }
+
+    #[test]
+    fn test_nested_table() {
+        let table = LootTable {
+            min_rolls: 1,
+            max_rolls: 1,
+            skill: String::new(),
+            entries: vec![
+                LootEntry {drop: LootDrop::Table(common_rare()), ..LootEntry::item("", 1., 3, 3)},
+                LootEntry {drop: LootDrop::Nothing, ..LootEntry::item("", 1., 1, 1)},
+            ],
+        };
+        let mut rng = SeededRng::new(99);
+        let mut empty = 0;
+        for _ in 0..ROLLS {
+            let drops = table.roll(&mut rng, &no_levels);
+            let total: i32 = drops.iter().map(|(_, count)| count).sum();
+            assert!(total == 0 || total == 3);
+            if total == 0 {empty += 1;}
+        }
+        assert!((4500..5500).contains(&empty), "{empty}");
+    }
+
+    #[test]
+    fn test_give_items_all_or_nothing() {
+        register_test_items();
+        let mut data = PlayerData::default();
+        for slot in data.items.iter_mut().skip(2) {
+            *slot = Some(Item::new("oak_wood", 1));
+        }
+        let before = data.to_bytes();
+
+        // Three axes need three slots, only two are free
+        assert!(!data.give_items(&[("oak_wood".to_string(), 5), ("axe".to_string(), 3)]));
+        assert!(!data.give_items(&[("axe".to_string(), 0)]));
+        assert_eq!(data.to_bytes(), before);
+
+        assert!(data.give_items(&[("oak_wood".to_string(), 5), ("axe".to_string(), 2)]));
+        assert_eq!(data.count_item("oak_wood"), data.items.len() as i32 - 2 + 5);
+        assert_eq!(data.count_item("axe"), 2);
+    }
+}
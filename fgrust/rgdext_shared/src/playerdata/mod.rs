@@ -1,15 +1,38 @@
 use bitcode::{Decode, Encode};
 use godot::prelude::*;
+use equipment::{EquipSlot, Equipment};
 use item::Item;
-use skills::Skill;
 
+#[macro_use]
+mod registry;
 pub mod item;
 pub mod skills;
+pub mod skillregistry;
 pub mod playercontainer;
-
+pub mod inventory;
+pub mod equipment;
+pub mod itemregistry;
+pub mod itemvalue;
+pub mod requirements;
+pub mod recipe;
+pub mod loot;
+pub mod rng;
+pub mod combat;
+pub mod dialogue;
+pub mod quest;
+pub mod flags;
+mod legacy;
+#[cfg(test)]
+mod testutil;
 
 /// Corresponding to an 5x8 grid on the client
 pub const MAX_ITEMS: usize = 40;
+/// Amount of slots in the bank
+pub const BANK_SIZE: usize = 200;
+/// Leading byte of encoded player data, to be bumped whenever the layout changes.
+///
+/// Data from before there was a version is migrated by [legacy::decode].
+const FORMAT_VERSION: u8 = 1;
 
 #[derive(Clone, Encode, Decode, Debug)]
 pub struct PlayerData {
@@ -23,10 +46,18 @@ pub struct PlayerData {
 
     pub skills: skills::Skills,
     pub skill_progress: skills::SkillProgress,
+    /// Current health, at most [PlayerData::max_hp]
+    pub hp: i32,
     pub gold: i32,
-    pub equipped_item: Option<Item>,
+    pub equipment: Equipment,
     pub items: [Option<Item>; MAX_ITEMS],
+    /// Always at least [BANK_SIZE] long for full data, empty in minimal and private data
+    pub bank: Vec<Option<Item>>,
     pub friends: Vec<i32>,
+    /// Progress of every quest the player has started, completed ones included
+    pub quests: Vec<quest::QuestProgress>,
+    /// Key-value flags set by scripts, see [flags] for the bounds
+    pub flags: itemvalue::CustomData,
 }
 
 impl PlayerData {
@@ -41,12 +72,22 @@ impl PlayerData {
 
     pub fn from_bytes(b: &[u8]) -> Result<Self, bitcode::Error> {
         // bitcode::deserialize(b)
-        bitcode::decode(b)
+        let mut data: Self = match b.split_first() {
+            // Old data could happen to start with the version byte too
+            Some((&FORMAT_VERSION, rest)) => bitcode::decode(rest).or_else(|err| legacy::decode(b).map_err(|_| err))?,
+            _ => legacy::decode(b)?,
+        };
+        if data.bank.len() < BANK_SIZE {
+            data.bank.resize(BANK_SIZE, None);
+        }
+        Ok(data)
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
         // bitcode::serialize(self).unwrap()
-        bitcode::encode(self)
+        let mut bytes = vec![FORMAT_VERSION];
+        bytes.extend(bitcode::encode(self));
+        bytes
     }
 
     pub fn to_bytearray(&self) -> PackedByteArray {
@@ -60,68 +101,225 @@ impl PlayerData {
         }
     }
 
-    /// Returns true if item successfully inserted
+    /// Returns true if item successfully inserted into the inventory
+    ///
+    /// Either the whole item is inserted or nothing is changed.
     pub fn insert_item(&mut self, item: Item) -> bool {
-        match &mut self.equipped_item {
-            Some(existing_item) => {
-                if existing_item.stackable() && existing_item.id_string() == item.id_string() {
-                    existing_item.count += item.count;
-                    return true;
-                }
-            },
-            None => {
-                self.equipped_item = Some(item);
-                return true;
+        inventory::insert_item(&mut self.items, item)
+    }
+
+    /// Inserts new items by (id_string, count), e.g. rolled loot.
+    ///
+    /// Either all of them are inserted or nothing is changed. Returns true if they were inserted.
+    pub fn give_items(&mut self, items: &[(String, i32)]) -> bool {
+        let mut slots = self.items.clone();
+        for (id_string, count) in items {
+            if !inventory::insert_new(&mut slots, id_string, *count) {
+                return false;
+            }
+        }
+        self.items = slots;
+        true
+    }
+
+    /// Returns true if item and amount successfully removed.
+    ///
+    /// Goes through the inventory first, possibly across multiple stacks, then through equipped items.
+    /// Nothing is removed if the player doesn't hold enough of the item or the amount isn't positive.
+    pub fn remove_item(&mut self, id_string: &str, amount: i32) -> bool {
+        let slots = self.items.iter_mut().chain(self.equipment.iter_mut());
+        inventory::remove_item(slots, id_string, amount)
+    }
+
+    /// Total amount of the item held, equipped items included
+    pub fn count_item(&self, id_string: &str) -> i32 {
+        let slots = self.items.iter().chain(self.equipment.iter());
+        inventory::count_item(slots, id_string)
+    }
+
+    /// Equips the item at the given inventory index into the slot it declares.
+    ///
+    /// Whatever was equipped in that slot is put where the equipped item was. Returns false if the item can't be equipped.
+    pub fn equip_item(&mut self, from: usize) -> bool {
+        let slot = match self.items.get(from) {
+            Some(Some(item)) => match item.equip_slot() {
+                Some(slot) => slot,
+                None => return false,
             },
+            _ => return false,
+        };
+        std::mem::swap(&mut self.items[from], &mut self.equipment[slot]);
+        true
+    }
+
+    /// Checks that the player meets the recipe's requirements and has all of its inputs in the inventory,
+    /// and that the skill the recipe gives xp in exists
+    pub fn check_craft(&self, recipe: &recipe::RecipeDef) -> Result<(), CraftError> {
+        if !recipe.skill.is_empty() && !skillregistry::is_registered(&recipe.skill) {
+            return Err(CraftError::UnknownSkill(recipe.skill.clone()));
+        }
+        if let Some(unmet) = requirements::first_unmet(&recipe.requirements(), self) {
+            return Err(CraftError::Unmet(unmet.clone()));
         }
+        for (id_string, count) in &recipe.inputs {
+            if inventory::count_item(self.items.iter(), id_string) < *count {
+                return Err(CraftError::Unmet(requirements::Requirement::HasItem{id_string: id_string.clone(), count: *count}));
+            }
+        }
+        Ok(())
+    }
 
-        for item_slot in self.items.iter_mut() {
-            match item_slot {
-                Some(existing_item) => {
-                    if existing_item.stackable() && existing_item.id_string() == item.id_string() {
-                        existing_item.count += item.count;
-                        return true;
-                    }
-                },
-                None => {
-                    *item_slot = Some(item);
-                    return true;
-                },
+    /// Takes the recipe's inputs from the inventory, then gives its outputs and xp.
+    ///
+    /// Either all of it happens or nothing is changed. Returns the level up result if the recipe gives xp.
+    pub fn craft(&mut self, recipe: &recipe::RecipeDef) -> Result<Option<skills::LevelUp>, CraftError> {
+        self.check_craft(recipe)?;
+
+        let mut items = self.items.clone();
+        for (id_string, count) in &recipe.inputs {
+            if !inventory::remove_item(items.iter_mut(), id_string, *count) {
+                return Err(CraftError::Unmet(requirements::Requirement::HasItem{id_string: id_string.clone(), count: *count}));
             }
         }
-        return false;
+        for (id_string, count) in &recipe.outputs {
+            if !inventory::insert_new(&mut items, id_string, *count) {
+                return Err(CraftError::InventoryFull);
+            }
+        }
+        self.items = items;
+
+        if recipe.skill.is_empty() || recipe.xp == 0 {
+            return Ok(None);
+        }
+        Ok(self.add_xp(&recipe.skill, recipe.xp))
     }
 
-    /// Returns true if item and amount successfully removed. Assumes the amount to be removed is valid
+    /// Moves the item in the given equipment slot back into the inventory.
     ///
-    /// Checks equipped slot first, then goes through inventory
-    pub fn remove_item(&mut self, id_string: &str, amount: i32) -> bool {
-        if let Some(item) = self.equipped_item.as_mut() {
-            if item.id_string() == id_string {
-                item.count -= amount;
-                if item.count <= 0 {
-                    self.equipped_item = None;
+    /// Returns false if the slot is empty or the inventory has no space for the item.
+    pub fn unequip_item(&mut self, slot: EquipSlot) -> bool {
+        match self.equipment[slot].take() {
+            Some(item) => {
+                if inventory::can_insert(&self.items, &item) {
+                    inventory::insert_item(&mut self.items, item)
                 }
-                return true;
-            }
+                else {
+                    self.equipment[slot] = Some(item);
+                    false
+                }
+            },
+            None => false,
         }
-        
-        for item_slot in &mut self.items {
-            if let Some(item) = item_slot.as_mut() {
-                if item.id_string() == id_string {
-                    item.count -= amount;
-                    if item.count <= 0 {
-                        *item_slot = None;
-                    }
-                    return true;
+    }
+
+    /// Moves the whole stack at the given inventory index into the bank, stacking it with items already there.
+    ///
+    /// Returns false if the slot is empty or the bank has no space for it.
+    pub fn deposit_item(&mut self, from: usize) -> bool {
+        match self.items.get_mut(from) {
+            Some(slot @ Some(_)) => {
+                let item = slot.take().unwrap();
+                if inventory::can_insert(&self.bank, &item) {
+                    inventory::insert_item(&mut self.bank, item)
                 }
+                else {
+                    *slot = Some(item);
+                    false
+                }
+            },
+            _ => false,
+        }
+    }
+
+    /// Moves the whole stack at the given bank index into the inventory, stacking it with items already there.
+    ///
+    /// Returns false if the slot is empty or the inventory has no space for it.
+    pub fn withdraw_item(&mut self, from: usize) -> bool {
+        match self.bank.get_mut(from) {
+            Some(slot @ Some(_)) => {
+                let item = slot.take().unwrap();
+                if inventory::can_insert(&self.items, &item) {
+                    inventory::insert_item(&mut self.items, item)
+                }
+                else {
+                    *slot = Some(item);
+                    false
+                }
+            },
+            _ => false,
+        }
+    }
+
+    /// Merges split stacks in the bank and orders its items by id_string, leaving empty slots at the end
+    pub fn sort_bank(&mut self) {
+        let mut items: Vec<Item> = self.bank.iter_mut().filter_map(|slot| slot.take()).collect();
+        items.sort_by(|a, b| a.id_string().cmp(b.id_string()));
+        for item in items {
+            // Invalid stacks, e.g. unknown items with a count above 1, are kept as they are instead of merged.
+            // Either way, sorting never needs more slots than there were stacks.
+            if !item.is_valid() {
+                if let Some(slot) = self.bank.iter_mut().find(|slot| slot.is_none()) {
+                    *slot = Some(item);
+                }
+            }
+            else {
+                inventory::insert_item(&mut self.bank, item);
             }
         }
+    }
 
-        return false;
+    /// Ids of all held, equipped and banked items that aren't in the item registry
+    pub fn unknown_item_ids(&self) -> Vec<String> {
+        let mut unknown: Vec<String> = self.items.iter()
+            .chain(self.equipment.iter())
+            .chain(self.bank.iter())
+            .flatten()
+            .filter(|item| !item.is_known())
+            .map(|item| item.id_string().to_string())
+            .collect();
+        unknown.sort();
+        unknown.dedup();
+        unknown
+    }
+
+    /// Skill level plus bonuses from equipped items, or None if the skill doesn't exist
+    pub fn effective_level(&self, skill_name: &str) -> Option<i32> {
+        if !skillregistry::is_registered(skill_name) {
+            return None;
+        }
+        Some(self.skills.level(skill_name).saturating_add(self.equipment.stat_bonus(skill_name)))
+    }
+
+    /// Returns true if gold successfully changed. Gold can't go negative or overflow.
+    pub fn change_gold(&mut self, amount: i32) -> bool {
+        match self.gold.checked_add(amount) {
+            Some(gold) if gold >= 0 => {
+                self.gold = gold;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Splits new_count items off the stack at from into the empty slot at to.
+    ///
+    /// Returns true if the split happened.
+    pub fn split_item(&mut self, from: usize, to: usize, new_count: i32) -> bool {
+        if from >= MAX_ITEMS || to >= MAX_ITEMS || from == to || self.items[to].is_some() {
+            return false;
+        }
+        let new_item = match &mut self.items[from] {
+            Some(from_item) => from_item.try_split(new_count),
+            None => None,
+        };
+        if new_item.is_none() {
+            return false;
+        }
+        self.items[to] = new_item;
+        true
     }
 
-    /// Gets all player data except for skill progress, gold and inventory
+    /// Gets all player data except for skill progress, gold, inventory, bank, quests and flags
     pub fn get_minimal(&self) -> Self {
         Self {
             name: self.name.clone(),
@@ -135,31 +333,79 @@ impl PlayerData {
 
             skills: self.skills.clone(),
             skill_progress: skills::SkillProgress::default(),
+            hp: self.hp,
             gold: 0,
-            equipped_item: self.equipped_item.clone(),
+            equipment: self.equipment.clone(),
             items: [const {None}; MAX_ITEMS],
+            bank: Vec::new(),
             friends: Vec::new(),
+            quests: Vec::new(),
+            flags: itemvalue::CustomData::default(),
         }
     }
 
-    /// Returns amount of levels gained
-    pub fn add_xp(&mut self, skill: Skill, amount: i32) -> i32 {
-        let level_before = self.skills[skill];
-        if level_before == 100 {return 0;}
+    /// Gets all player data except for the bank, which is sent separately only when opened
+    pub fn get_private(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            pid: self.pid,
+
+            server_name: self.server_name.clone(),
+            location: self.location.clone(),
+
+            x: self.x,
+            y: self.y,
 
-        self.skill_progress[skill] += amount;
-        let mut level_after = level_before as i32;
-        while self.skill_progress[skill] >= level_after * level_after * 100 {
-            level_after += 1;
+            skills: self.skills.clone(),
+            skill_progress: self.skill_progress.clone(),
+            hp: self.hp,
+            gold: self.gold,
+            equipment: self.equipment.clone(),
+            items: self.items.clone(),
+            bank: Vec::new(),
+            friends: self.friends.clone(),
+            quests: self.quests.clone(),
+            flags: self.flags.clone(),
         }
-        let level_delta = level_after - level_before as i32;
-        if level_after >= 100 {
-            self.skills[skill] = 100;
-            self.skill_progress[skill] = 0;
-            return 100 - level_before as i32;
+    }
+
+    /// Returns the levels before and after adding the xp, or None if the skill doesn't exist.
+    /// 
+    /// The level follows the skill's xp curve and never goes past its max level or down.
+    pub fn add_xp(&mut self, skill: &str, amount: i32) -> Option<skills::LevelUp> {
+        skillregistry::with_def(skill, |def| {
+            let level_before = self.skills.level(skill);
+            let xp = self.skill_progress.xp(skill).saturating_add(amount).max(0);
+            let level_after = def.level_for_xp(xp).min(def.max_level).max(level_before);
+
+            self.skill_progress.set_xp(skill, xp);
+            self.skills.set_level(skill, level_after);
+            skills::LevelUp {
+                skill: skill.to_string(),
+                old_level: level_before,
+                new_level: level_after,
+            }
+        })
+    }
+}
+
+/// Reason a craft failed
+#[derive(Clone, PartialEq, Debug)]
+pub enum CraftError {
+    Unmet(requirements::Requirement),
+    InventoryFull,
+    /// The recipe's skill isn't registered, so its xp couldn't be given
+    UnknownSkill(String),
+}
+
+impl CraftError {
+    /// Message shown to the player when the craft fails
+    pub fn message(&self) -> String {
+        match self {
+            CraftError::Unmet(requirement) => requirement.failure_message(),
+            CraftError::InventoryFull => "You don't have enough inventory space to craft that.".to_string(),
+            CraftError::UnknownSkill(_) => "That can't be crafted right now.".to_string(),
         }
-        self.skills[skill] = level_after as u8;
-        return level_delta;
     }
 }
 
@@ -179,10 +425,14 @@ impl Default for PlayerData {
 
             skills: skills::Skills::default(),
             skill_progress: skills::SkillProgress::default(),
+            hp: combat::max_hp(1),
             gold: 0,
-            equipped_item: None,
+            equipment: Equipment::default(),
             items: [const {None}; MAX_ITEMS],
+            bank: vec![None; BANK_SIZE],
             friends: Vec::new(),
+            quests: Vec::new(),
+            flags: itemvalue::CustomData::default(),
         }
     }
 }
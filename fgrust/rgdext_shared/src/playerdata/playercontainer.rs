@@ -1,5 +1,5 @@
 use godot::prelude::*;
-use super::{item::ItemResource, skills::Skill, *};
+use super::{equipment::EquipSlot, item::ItemResource, *};
 
 #[derive(GodotClass)]
 #[class(no_init, base=RefCounted)]
@@ -48,6 +48,10 @@ impl PlayerContainer {
         })
     }
 
+    pub fn data(&self) -> &PlayerData {
+        &self.data
+    }
+
     #[func]
     pub fn to_bytearray(&self) -> PackedByteArray {
         self.data.to_bytearray()
@@ -90,8 +94,28 @@ impl PlayerContainer {
     }
 
     #[func]
+    fn get_hp(&self) -> i32 {
+        self.data.hp
+    }
+
+    #[func]
+    /// Depends on the effective endurance level
+    fn get_max_hp(&self) -> i32 {
+        self.data.max_hp()
+    }
+
+    #[func]
+    /// Shorthand for the item equipped in the main_hand slot
     fn get_equipped_item(&self) -> Option<Gd<ItemResource>> {
-        self.data.equipped_item.as_ref().map(|i| i.to_resource())
+        self.data.equipment[EquipSlot::MainHand].as_ref().map(|i| i.to_resource())
+    }
+
+    #[func]
+    /// Returns null if nothing is equipped in the slot or the slot name is invalid
+    fn get_equipped(&self, slot: String) -> Option<Gd<ItemResource>> {
+        EquipSlot::try_from_str(slot.as_str())
+            .and_then(|slot| self.data.equipment[slot].as_ref())
+            .map(|i| i.to_resource())
     }
 
     #[func]
@@ -107,18 +131,30 @@ impl PlayerContainer {
 
     #[func]
     fn get_stat(&self, stat: String) -> i32 {
-        if let Some(skill) = skills::Skill::try_from_str(stat.as_str()) {
-            self.data.skills[skill] as i32
+        if skillregistry::is_registered(stat.as_str()) {
+            self.data.skills.level(stat.as_str())
         }
         else {
             -1
         }
     }
 
+    #[func]
+    /// Sum of the bonuses equipped items give to the stat
+    fn get_stat_bonus(&self, stat: String) -> i32 {
+        self.data.equipment.stat_bonus(stat.as_str())
+    }
+
+    #[func]
+    /// Stat level with equipment bonuses added, -1 if the stat doesn't exist
+    fn get_effective_stat(&self, stat: String) -> i32 {
+        self.data.effective_level(stat.as_str()).unwrap_or(-1)
+    }
+
     #[func]
     fn get_stat_progress(&self, stat: String) -> i32 {
-        if let Some(skill) = skills::Skill::try_from_str(stat.as_str()) {
-            self.data.skill_progress[skill]
+        if skillregistry::is_registered(stat.as_str()) {
+            self.data.skill_progress.xp(stat.as_str())
         }
         else {
             -1
@@ -126,10 +162,66 @@ impl PlayerContainer {
     }
 
     #[func]
+    /// Value of the player flag set by [method ScriptResponse.set_player_flag], null if it's not set.
+    fn get_flag(&self, key: String) -> Variant {
+        self.data.flag(key.as_str()).map(|value| value.to_variant()).unwrap_or_default()
+    }
+
+    #[func]
+    fn has_flag(&self, key: String) -> bool {
+        self.data.flags.contains_key(key.as_str())
+    }
+
+    #[func]
+    /// Ids of the quests the player has started, completed ones included, in the order they were started.
+    fn get_quests(&self) -> Array<GString> {
+        let mut arr = Array::new();
+        for progress in &self.data.quests {
+            arr.push(progress.id.as_str());
+        }
+        arr
+    }
+
+    #[func]
+    /// One of "not_started", "in_progress" or "completed".
+    fn get_quest_state(&self, id: String) -> GString {
+        self.data.quest_state(id.as_str()).as_str().into()
+    }
+
+    #[func]
+    /// Index of the current step of the quest, the amount of steps if it's completed or -1 if it's not started.
+    fn get_quest_step(&self, id: String) -> i32 {
+        match self.data.quests.iter().find(|q| q.id == id) {
+            Some(progress) => progress.step as i32,
+            None => -1,
+        }
+    }
+
+    #[func]
+    /// Description of the current step of the quest, empty if it's not in progress.
+    fn get_quest_description(&self, id: String) -> GString {
+        let step = self.data.quests.iter().find(|q| q.id == id).map(|q| q.step);
+        match (step, quest::get(id.as_str())) {
+            (Some(step), Some(def)) => def.steps.get(step).map(|s| s.description.as_str()).unwrap_or("").into(),
+            _ => "".into(),
+        }
+    }
+
+    #[func]
+    /// Ids of all registered skills in display order.
     fn skill_array() -> Array<GString> {
         let mut arr = Array::new();
-        for skill in Skill::skill_strs() {
-            arr.push(skill);
+        for skill in skillregistry::skill_ids() {
+            arr.push(skill.as_str());
+        }
+        arr
+    }
+
+    #[func]
+    fn equip_slot_array() -> Array<GString> {
+        let mut arr = Array::new();
+        for slot in EquipSlot::slot_strs() {
+            arr.push(slot);
         }
         arr
     }
@@ -0,0 +1,466 @@
+use bitcode::{Decode, Encode};
+use godot::{classes::{DirAccess, ResourceLoader}, prelude::*};
+
+use super::PlayerData;
+
+
+registry_storage!(
+    /// Every quest known to this server or client, in load order
+    quests: Vec<QuestDef>
+);
+
+/// Something a quest step asks of the player
+#[derive(Clone, PartialEq, Debug)]
+pub enum Objective {
+    /// Holding the items, equipped ones included
+    CollectItems{id_string: String, count: i32},
+    /// Interacting with an entity that has the tag
+    TalkTo{tag: String},
+    /// Walking onto the tile of the map
+    ReachTile{map: String, x: i32, y: i32},
+    /// Skill level without bonuses from equipped items
+    SkillLevel{skill: String, level: i32},
+}
+
+impl Objective {
+    /// State objectives are checked against the player's data every time it changes instead of being marked done once,
+    /// so e.g. dropping collected items undoes the objective until the step completes
+    fn is_state(&self) -> bool {
+        matches!(self, Objective::CollectItems{..} | Objective::SkillLevel{..})
+    }
+
+    fn is_met(&self, data: &PlayerData) -> bool {
+        match self {
+            Objective::CollectItems{id_string, count} => data.count_item(id_string) >= *count,
+            Objective::SkillLevel{skill, level} => data.skills.level(skill) >= *level,
+            Objective::TalkTo{..} | Objective::ReachTile{..} => false,
+        }
+    }
+
+    fn matches(&self, event: &QuestEvent) -> bool {
+        match (self, event) {
+            (Objective::TalkTo{tag}, QuestEvent::Interacted{tags}) => tags.contains(tag),
+            (Objective::ReachTile{map, x, y}, QuestEvent::Walked{map: event_map, x: event_x, y: event_y}) => {
+                map == event_map && x == event_x && y == event_y
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Something the player did that may complete objectives of their current quest steps
+pub enum QuestEvent<'a> {
+    /// Interacted with an entity with the tags
+    Interacted{tags: &'a [String]},
+    Walked{map: &'a str, x: i32, y: i32},
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct QuestStep {
+    /// Shown to the player while the step is in progress
+    pub description: String,
+    /// All of these have to be done to complete the step
+    pub objectives: Vec<Objective>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct QuestDef {
+    pub id: String,
+    pub name: String,
+    pub steps: Vec<QuestStep>,
+}
+
+/// Progress of a started quest, saved with the player
+#[derive(Clone, Encode, Decode, PartialEq, Debug)]
+pub struct QuestProgress {
+    pub id: String,
+    /// Index of the current step, the amount of steps once the quest is completed
+    pub step: usize,
+    /// Which objectives of the current step have been done, by index. State objectives are never marked.
+    pub done: Vec<bool>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum QuestState {
+    NotStarted,
+    InProgress,
+    Completed,
+}
+
+impl QuestState {
+    pub fn try_from_str(s: &str) -> Option<Self> {
+        match s {
+            "not_started" => Some(QuestState::NotStarted),
+            "in_progress" => Some(QuestState::InProgress),
+            "completed" => Some(QuestState::Completed),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuestState::NotStarted => "not_started",
+            QuestState::InProgress => "in_progress",
+            QuestState::Completed => "completed",
+        }
+    }
+}
+
+/// A quest step that got completed
+#[derive(Clone, PartialEq, Debug)]
+pub struct QuestUpdate {
+    pub id: String,
+    /// Index of the completed step
+    pub step: usize,
+    /// Whether it was the last step
+    pub completed: bool,
+}
+
+/// Adds the quest to the registry, replacing any previous quest with the same id in place
+pub fn register(quest: QuestDef) {
+    let mut quests = quests().write().unwrap();
+    match quests.iter_mut().find(|q| q.id == quest.id) {
+        Some(existing) => *existing = quest,
+        None => quests.push(quest),
+    }
+}
+
+/// Returns a copy of the quest with the given id
+pub fn get(id: &str) -> Option<QuestDef> {
+    quests().read().unwrap().iter().find(|q| q.id == id).cloned()
+}
+
+pub fn quest_ids() -> Vec<String> {
+    quests().read().unwrap().iter().map(|q| q.id.clone()).collect()
+}
+
+impl PlayerData {
+    pub fn quest_state(&self, id: &str) -> QuestState {
+        match self.quests.iter().find(|q| q.id == id) {
+            None => QuestState::NotStarted,
+            Some(progress) => match get(id) {
+                Some(def) if progress.step >= def.steps.len() => QuestState::Completed,
+                _ => QuestState::InProgress,
+            },
+        }
+    }
+
+    /// Returns false if the quest doesn't exist or the player has already started it
+    pub fn start_quest(&mut self, id: &str) -> bool {
+        if get(id).is_none() || self.quests.iter().any(|q| q.id == id) {
+            return false;
+        }
+        self.quests.push(QuestProgress{id: id.to_string(), step: 0, done: Vec::new()});
+        true
+    }
+
+    /// Marks the objectives of the current quest steps that the event does done, returning true if there were any.
+    ///
+    /// The steps themselves complete on [PlayerData::update_quests].
+    pub fn quest_event(&mut self, event: &QuestEvent) -> bool {
+        let mut marked = false;
+        let quests = quests().read().unwrap();
+        for progress in self.quests.iter_mut() {
+            let Some(def) = quests.iter().find(|q| q.id == progress.id) else {continue};
+            let Some(step) = def.steps.get(progress.step) else {continue};
+            progress.done.resize(step.objectives.len(), false);
+            for (i, objective) in step.objectives.iter().enumerate() {
+                if !progress.done[i] && objective.matches(event) {
+                    progress.done[i] = true;
+                    marked = true;
+                }
+            }
+        }
+        marked
+    }
+
+    /// Completes every current quest step whose objectives are all done or met, possibly several steps of a quest in a row.
+    ///
+    /// Returns the completed steps in order.
+    pub fn update_quests(&mut self) -> Vec<QuestUpdate> {
+        let mut updates = Vec::new();
+        let mut progresses = std::mem::take(&mut self.quests);
+        for progress in progresses.iter_mut() {
+            let Some(def) = get(&progress.id) else {continue};
+            while let Some(step) = def.steps.get(progress.step) {
+                progress.done.resize(step.objectives.len(), false);
+                let all_done = step.objectives.iter()
+                    .zip(progress.done.iter())
+                    .all(|(objective, done)| *done || (objective.is_state() && objective.is_met(self)));
+                if !all_done {
+                    break;
+                }
+                updates.push(QuestUpdate{id: progress.id.clone(), step: progress.step, completed: progress.step + 1 == def.steps.len()});
+                progress.step += 1;
+                progress.done.clear();
+            }
+        }
+        self.quests = progresses;
+        updates
+    }
+}
+
+/// Registers every QuestResource saved as a .tres file directly in the given directory.
+///
+/// Returns the amount of quests registered.
+pub fn load_directory(path: &str) -> i32 {
+    let mut loaded = 0;
+    for file in DirAccess::get_files_at(path).as_slice() {
+        let file = file.to_string();
+        let file = file.strip_suffix(".remap").unwrap_or(&file);
+        if !file.ends_with(".tres") {
+            continue;
+        }
+
+        let resource_path = format!("{}/{}", path.trim_end_matches('/'), file);
+        match ResourceLoader::singleton().load(&resource_path).and_then(|r| r.try_cast::<QuestResource>().ok()) {
+            Some(quest) => {
+                let def = quest.bind().to_def();
+                if get(&def.id).is_some() {
+                    godot_error!("Quest id {} registered twice, the quest in {} overrides the previous one", def.id, resource_path);
+                }
+                register(def);
+                loaded += 1;
+            },
+            None => godot_error!("{} is not a QuestResource", resource_path),
+        }
+    }
+    loaded
+}
+
+#[derive(GodotClass)]
+#[class(no_init, base=Object)]
+/// Registry of all quests, loaded at startup from the QuestResource files in a directory.
+pub struct QuestRegistry {
+    base: Base<Object>
+}
+
+#[godot_api]
+impl QuestRegistry {
+    #[func]
+    /// Registers every QuestResource .tres file in the directory, returns the amount of quests registered.
+    fn load_directory(path: GString) -> i32 {
+        load_directory(&path.to_string())
+    }
+
+    #[func]
+    fn get_quest_ids() -> Array<GString> {
+        let mut arr = Array::new();
+        for id in quest_ids() {
+            arr.push(id.as_str());
+        }
+        arr
+    }
+
+    #[func]
+    /// Returns a dictionary with fields "id": String, "name": String and "steps": Array of step descriptions,
+    /// or an empty dictionary if the quest doesn't exist.
+    fn get_quest(id: GString) -> Dictionary {
+        let mut dict = Dictionary::new();
+        if let Some(quest) = get(&id.to_string()) {
+            let mut steps = Array::<GString>::new();
+            for step in &quest.steps {
+                steps.push(step.description.as_str());
+            }
+            dict.set("id", GString::from(&quest.id));
+            dict.set("name", GString::from(&quest.name));
+            dict.set("steps", steps);
+        }
+        dict
+    }
+}
+
+#[derive(GodotClass)]
+#[class(base=Resource)]
+/// Quest definitions are saved as QuestResources in the quests directory and loaded into the [QuestRegistry] at startup.
+///
+/// Quests are started with [method ScriptResponse.start_quest] and advance on their own as the player does the objectives.
+pub struct QuestResource {
+    #[export]
+    /// Unique string id of the quest.
+    id: GString,
+    #[export]
+    name: GString,
+    #[export]
+    steps: Array<Gd<QuestStepResource>>,
+
+    base: Base<Resource>,
+}
+
+#[godot_api]
+impl IResource for QuestResource {
+    fn init(base: Base<Resource>) -> Self {
+        Self {
+            id: "".into(),
+            name: "".into(),
+            steps: Array::new(),
+            base,
+        }
+    }
+}
+
+impl QuestResource {
+    pub fn to_def(&self) -> QuestDef {
+        QuestDef {
+            id: self.id.to_string(),
+            name: self.name.to_string(),
+            steps: self.steps.iter_shared().map(|step| step.bind().to_step(&self.id)).collect(),
+        }
+    }
+}
+
+#[derive(GodotClass)]
+#[class(base=Resource)]
+pub struct QuestStepResource {
+    #[export]
+    /// Shown to the player while the step is in progress.
+    description: GString,
+    #[export]
+    /// All of these have to be done to complete the step.
+    objectives: Array<Gd<QuestObjectiveResource>>,
+
+    base: Base<Resource>,
+}
+
+#[godot_api]
+impl IResource for QuestStepResource {
+    fn init(base: Base<Resource>) -> Self {
+        Self {
+            description: "".into(),
+            objectives: Array::new(),
+            base,
+        }
+    }
+}
+
+impl QuestStepResource {
+    fn to_step(&self, quest_id: &GString) -> QuestStep {
+        QuestStep {
+            description: self.description.to_string(),
+            objectives: self.objectives.iter_shared().filter_map(|objective| objective.bind().to_objective(quest_id)).collect(),
+        }
+    }
+}
+
+#[derive(GodotClass)]
+#[class(base=Resource)]
+pub struct QuestObjectiveResource {
+    #[export]
+    /// One of "collect_items", "talk_to", "reach_tile" or "skill_level".
+    kind: GString,
+    #[export]
+    /// Item id_string for collect_items, entity tag for talk_to, map name for reach_tile and skill name for skill_level.
+    target: GString,
+    #[export]
+    /// Item count for collect_items and level for skill_level.
+    amount: i32,
+    #[export]
+    /// Tile for reach_tile.
+    tile: Vector2i,
+
+    base: Base<Resource>,
+}
+
+#[godot_api]
+impl IResource for QuestObjectiveResource {
+    fn init(base: Base<Resource>) -> Self {
+        Self {
+            kind: "collect_items".into(),
+            target: "".into(),
+            amount: 1,
+            tile: Vector2i::ZERO,
+            base,
+        }
+    }
+}
+
+impl QuestObjectiveResource {
+    fn to_objective(&self, quest_id: &GString) -> Option<Objective> {
+        let target = self.target.to_string();
+        match self.kind.to_string().as_str() {
+            "collect_items" => Some(Objective::CollectItems{id_string: target, count: self.amount}),
+            "talk_to" => Some(Objective::TalkTo{tag: target}),
+            "reach_tile" => Some(Objective::ReachTile{map: target, x: self.tile.x, y: self.tile.y}),
+            "skill_level" => Some(Objective::SkillLevel{skill: target, level: self.amount}),
+            kind => {
+                godot_error!("Quest {} has an objective of unknown kind {}, skipping it", quest_id, kind);
+                None
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playerdata::item::Item;
+
+    fn register_delivery() {
+        register(QuestDef {
+            id: "delivery".into(),
+            name: "Delivery".into(),
+            steps: vec![
+                QuestStep {
+                    description: "Get 3 logs".into(),
+                    objectives: vec![Objective::CollectItems{id_string: "log".into(), count: 3}],
+                },
+                QuestStep {
+                    description: "Bring them to the carpenter at the docks".into(),
+                    objectives: vec![
+                        Objective::ReachTile{map: "docks".into(), x: 4, y: 2},
+                        Objective::TalkTo{tag: "carpenter".into()},
+                    ],
+                },
+            ],
+        });
+    }
+
+    #[test]
+    fn test_start_quest() {
+        register_delivery();
+        let mut data = PlayerData::default();
+        assert_eq!(data.quest_state("delivery"), QuestState::NotStarted);
+        assert!(!data.start_quest("missing"));
+        assert!(data.start_quest("delivery"));
+        assert!(!data.start_quest("delivery"));
+        assert_eq!(data.quest_state("delivery"), QuestState::InProgress);
+    }
+
+    #[test]
+    fn test_quest_steps_complete_in_order() {
+        register_delivery();
+        let mut data = PlayerData::default();
+        data.start_quest("delivery");
+        assert!(data.update_quests().is_empty());
+
+        // Action objectives of later steps don't count yet
+        let tags = vec!["carpenter".to_string()];
+        assert!(!data.quest_event(&QuestEvent::Interacted{tags: &tags}));
+
+        data.give_items(&[("log".into(), 3)]);
+        assert_eq!(data.update_quests(), vec![QuestUpdate{id: "delivery".into(), step: 0, completed: false}]);
+
+        assert!(!data.quest_event(&QuestEvent::Walked{map: "docks", x: 4, y: 3}));
+        assert!(data.quest_event(&QuestEvent::Walked{map: "docks", x: 4, y: 2}));
+        assert!(data.update_quests().is_empty());
+        assert!(data.quest_event(&QuestEvent::Interacted{tags: &tags}));
+        assert_eq!(data.update_quests(), vec![QuestUpdate{id: "delivery".into(), step: 1, completed: true}]);
+        assert_eq!(data.quest_state("delivery"), QuestState::Completed);
+        assert!(data.update_quests().is_empty());
+    }
+
+    #[test]
+    fn test_state_objectives_are_rechecked() {
+        register_delivery();
+        let mut data = PlayerData::default();
+        data.items[0] = Some(Item::new("log", 2));
+        data.start_quest("delivery");
+        assert!(data.update_quests().is_empty());
+
+        data.items[1] = Some(Item::new("log", 1));
+        data.items[0] = None;
+        assert!(data.update_quests().is_empty());
+        data.items[0] = Some(Item::new("log", 2));
+        assert_eq!(data.update_quests().len(), 1);
+    }
+}
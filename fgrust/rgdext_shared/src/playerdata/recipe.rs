@@ -0,0 +1,305 @@
+use godot::{classes::{DirAccess, ResourceLoader}, prelude::*};
+
+use super::{playercontainer::PlayerContainer, requirements::Requirement};
+
+
+registry_storage!(
+    /// Every recipe known to this server or client, in load order
+    recipes: Vec<RecipeDef>
+);
+
+/// A crafting recipe, turning input items into output items
+#[derive(Clone, Debug)]
+pub struct RecipeDef {
+    pub id: String,
+    pub name: String,
+    /// (id_string, count) of items taken from the inventory
+    pub inputs: Vec<(String, i32)>,
+    /// (id_string, count) of items given
+    pub outputs: Vec<(String, i32)>,
+    /// Skill that the level requirement is for and that gets the xp, empty for none
+    pub skill: String,
+    pub level: i32,
+    pub xp: i32,
+    /// Station type that has to be nearby to craft this, empty if it can be crafted anywhere
+    pub station: String,
+}
+
+impl RecipeDef {
+    /// Requirements other than having the input items
+    pub fn requirements(&self) -> Vec<Requirement> {
+        if self.skill.is_empty() {
+            return Vec::new();
+        }
+        vec![Requirement::SkillLevel{skill: self.skill.clone(), level: self.level}]
+    }
+}
+
+/// Adds the recipe to the registry, replacing any previous recipe with the same id in place
+pub fn register(recipe: RecipeDef) {
+    let mut recipes = recipes().write().unwrap();
+    match recipes.iter_mut().find(|r| r.id == recipe.id) {
+        Some(existing) => *existing = recipe,
+        None => recipes.push(recipe),
+    }
+}
+
+/// Returns a copy of the recipe with the given id
+pub fn get(id: &str) -> Option<RecipeDef> {
+    recipes().read().unwrap().iter().find(|r| r.id == id).cloned()
+}
+
+pub fn recipe_ids() -> Vec<String> {
+    recipes().read().unwrap().iter().map(|r| r.id.clone()).collect()
+}
+
+/// Registers every RecipeResource saved as a .tres file directly in the given directory.
+///
+/// Returns the amount of recipes registered.
+pub fn load_directory(path: &str) -> i32 {
+    let mut loaded = 0;
+    for file in DirAccess::get_files_at(path).as_slice() {
+        let file = file.to_string();
+        let file = file.strip_suffix(".remap").unwrap_or(&file);
+        if !file.ends_with(".tres") {
+            continue;
+        }
+
+        let resource_path = format!("{}/{}", path.trim_end_matches('/'), file);
+        match ResourceLoader::singleton().load(&resource_path).and_then(|r| r.try_cast::<RecipeResource>().ok()) {
+            Some(recipe) => {
+                let def = recipe.bind().to_def();
+                if get(&def.id).is_some() {
+                    godot_error!("Recipe id {} registered twice, the recipe in {} overrides the previous one", def.id, resource_path);
+                }
+                register(def);
+                loaded += 1;
+            },
+            None => godot_error!("{} is not a RecipeResource", resource_path),
+        }
+    }
+    loaded
+}
+
+/// Converts an item id_string -> count dictionary into pairs, skipping invalid entries
+fn item_counts(dict: &Dictionary, recipe_id: &GString) -> Vec<(String, i32)> {
+    let mut counts = Vec::new();
+    for (k, v) in dict.iter_shared() {
+        match v.try_to_relaxed::<i32>() {
+            Ok(count) if count > 0 => counts.push((k.to_string(), count)),
+            _ => godot_error!("Items of recipe {} should map item ids to positive ints!", recipe_id),
+        }
+    }
+    counts
+}
+
+fn item_counts_dict(counts: &[(String, i32)]) -> Dictionary {
+    let mut dict = Dictionary::new();
+    for (id_string, count) in counts {
+        dict.set(id_string.as_str(), *count);
+    }
+    dict
+}
+
+#[derive(GodotClass)]
+#[class(base=Resource)]
+/// Recipe definitions are saved as RecipeResources in the recipes directory and loaded into the [RecipeRegistry] at startup.
+pub struct RecipeResource {
+    #[export]
+    /// Unique string id of the recipe, sent by the client to craft it.
+    id: GString,
+    #[export]
+    name: GString,
+    #[export]
+    /// Item id_string -> count of items taken from the inventory.
+    inputs: Dictionary,
+    #[export]
+    /// Item id_string -> count of items given.
+    outputs: Dictionary,
+    #[export]
+    /// Skill that the level requirement is for and that gets the xp, leave empty for none.
+    skill: GString,
+    #[export]
+    level: i32,
+    #[export]
+    xp: i32,
+    #[export]
+    /// Station type that has to be nearby, matching the station of a GenericScriptedEntity.
+    ///
+    /// Leave empty if the recipe can be crafted anywhere.
+    station: GString,
+
+    base: Base<Resource>,
+}
+
+#[godot_api]
+impl IResource for RecipeResource {
+    fn init(base: Base<Resource>) -> Self {
+        Self {
+            id: "".into(),
+            name: "".into(),
+            inputs: Dictionary::new(),
+            outputs: Dictionary::new(),
+            skill: "".into(),
+            level: 1,
+            xp: 0,
+            station: "".into(),
+            base,
+        }
+    }
+}
+
+impl RecipeResource {
+    pub fn to_def(&self) -> RecipeDef {
+        RecipeDef {
+            id: self.id.to_string(),
+            name: self.name.to_string(),
+            inputs: item_counts(&self.inputs, &self.id),
+            outputs: item_counts(&self.outputs, &self.id),
+            skill: self.skill.to_string(),
+            level: self.level,
+            xp: self.xp,
+            station: self.station.to_string(),
+        }
+    }
+}
+
+#[derive(GodotClass)]
+#[class(no_init, base=Object)]
+/// Registry of all crafting recipes, loaded at startup from the RecipeResource files in a directory.
+pub struct RecipeRegistry {
+    base: Base<Object>
+}
+
+#[godot_api]
+impl RecipeRegistry {
+    #[func]
+    /// Registers every RecipeResource .tres file in the directory, returns the amount of recipes registered.
+    fn load_directory(path: GString) -> i32 {
+        load_directory(&path.to_string())
+    }
+
+    #[func]
+    fn get_recipe_ids() -> Array<GString> {
+        let mut arr = Array::new();
+        for id in recipe_ids() {
+            arr.push(id.as_str());
+        }
+        arr
+    }
+
+    #[func]
+    /// Returns a dictionary with fields "id": String, "name": String, "inputs": Dictionary, "outputs": Dictionary,
+    /// "skill": String, "level": int, "xp": int and "station": String, or an empty dictionary if the recipe doesn't exist.
+    fn get_recipe(id: GString) -> Dictionary {
+        let mut dict = Dictionary::new();
+        if let Some(recipe) = get(&id.to_string()) {
+            dict.set("id", GString::from(&recipe.id));
+            dict.set("name", GString::from(&recipe.name));
+            dict.set("inputs", item_counts_dict(&recipe.inputs));
+            dict.set("outputs", item_counts_dict(&recipe.outputs));
+            dict.set("skill", GString::from(&recipe.skill));
+            dict.set("level", recipe.level);
+            dict.set("xp", recipe.xp);
+            dict.set("station", GString::from(&recipe.station));
+        }
+        dict
+    }
+
+    #[func]
+    /// Ids of the recipes for the station (plus the ones craftable anywhere) that the player can currently craft.
+    fn get_craftable(player: Gd<PlayerContainer>, station: GString) -> Array<GString> {
+        let station = station.to_string();
+        let player = player.bind();
+        let mut arr = Array::new();
+        for recipe in recipes().read().unwrap().iter() {
+            let at_station = recipe.station.is_empty() || recipe.station == station;
+            if at_station && player.data().check_craft(recipe).is_ok() {
+                arr.push(recipe.id.as_str());
+            }
+        }
+        arr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playerdata::{equipment::EquipSlot, item::Item, requirements, testutil::{axe, register_test_items, register_test_skills}, CraftError, PlayerData};
+
+    fn upgrade_recipe() -> RecipeDef {
+        RecipeDef {
+            id: "axe_upgrade".into(),
+            name: "Upgrade axe".into(),
+            inputs: vec![("axe".into(), 1), ("fir_wood".into(), 10)],
+            outputs: vec![("axe_super".into(), 1), ("fairy".into(), 2)],
+            skill: "woodcutting".into(),
+            level: 2,
+            xp: 300,
+            station: "grindstone".into(),
+        }
+    }
+
+    #[test]
+    fn test_craft() {
+        register_test_items();
+        register_test_skills();
+        let recipe = upgrade_recipe();
+        let mut data = PlayerData::default();
+        data.items[0] = Some(axe());
+        data.items[1] = Some(Item::new("fir_wood", 6));
+        data.items[2] = Some(Item::new("fir_wood", 6));
+
+        assert_eq!(data.craft(&recipe), Err(CraftError::Unmet(recipe.requirements()[0].clone())));
+        data.add_xp("woodcutting", 100);
+
+        let level_up = data.craft(&recipe).unwrap().unwrap();
+        assert_eq!((level_up.old_level, level_up.new_level), (2, 3));
+        assert_eq!(data.count_item("axe"), 0);
+        assert_eq!(data.count_item("fir_wood"), 2);
+        assert_eq!(data.count_item("axe_super"), 1);
+        assert_eq!(data.count_item("fairy"), 2);
+
+        // Inputs are only taken from the inventory
+        data.items[0] = None;
+        data.equipment[EquipSlot::MainHand] = Some(axe());
+        assert!(matches!(data.craft(&recipe), Err(CraftError::Unmet(requirements::Requirement::HasItem{..}))));
+    }
+
+    #[test]
+    fn test_craft_unknown_skill_changes_nothing() {
+        register_test_items();
+        register_test_skills();
+        let mut recipe = upgrade_recipe();
+        recipe.skill = "missing".into();
+        recipe.level = 1;
+        let mut data = PlayerData::default();
+        data.items[0] = Some(axe());
+        data.items[1] = Some(Item::new("fir_wood", 10));
+        let before = data.to_bytes();
+
+        assert_eq!(data.craft(&recipe), Err(CraftError::UnknownSkill("missing".into())));
+        assert_eq!(data.to_bytes(), before);
+    }
+
+    #[test]
+    fn test_craft_full_inventory_changes_nothing() {
+        register_test_items();
+        register_test_skills();
+        let mut recipe = upgrade_recipe();
+        recipe.skill = String::new();
+        recipe.outputs.push(("axe".into(), 3));
+        let mut data = PlayerData::default();
+        for slot in data.items.iter_mut() {
+            *slot = Some(Item::new("oak_wood", 1));
+        }
+        data.items[0] = Some(axe());
+        data.items[1] = Some(Item::new("fir_wood", 10));
+
+        // Taking the inputs frees two slots, but the outputs need four
+        assert_eq!(data.craft(&recipe), Err(CraftError::InventoryFull));
+        assert_eq!(data.count_item("axe"), 1);
+        assert_eq!(data.count_item("fir_wood"), 10);
+        assert_eq!(data.count_item("axe_super"), 0);
+    }
+}
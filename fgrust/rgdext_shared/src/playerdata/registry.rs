@@ -0,0 +1,23 @@
+/// Declares a function returning the storage of a registry of definitions, shared by the whole process.
+///
+/// In tests every thread gets storage of its own instead, so that tests running in parallel can register
+/// the definitions they need without seeing each other's.
+macro_rules! registry_storage {
+    ($(#[$attr:meta])* $name:ident: $ty:ty) => {
+        $(#[$attr])*
+        fn $name() -> &'static std::sync::RwLock<$ty> {
+            #[cfg(not(test))]
+            {
+                static STORAGE: std::sync::LazyLock<std::sync::RwLock<$ty>> = std::sync::LazyLock::new(Default::default);
+                &STORAGE
+            }
+            #[cfg(test)]
+            {
+                thread_local! {
+                    static STORAGE: &'static std::sync::RwLock<$ty> = Box::leak(Box::default());
+                }
+                STORAGE.with(|storage| *storage)
+            }
+        }
+    };
+}
@@ -0,0 +1,103 @@
+use super::{itemregistry, itemvalue::ItemValue, quest::{self, QuestState}, skillregistry, PlayerData};
+
+
+/// A condition the player has to meet, e.g. to interact with an entity
+#[derive(Clone, PartialEq, Debug)]
+pub enum Requirement {
+    /// Skill level including bonuses from equipped items
+    SkillLevel{skill: String, level: i32},
+    /// Item that has to be equipped in any slot
+    Equipped{id_string: String},
+    /// Items that have to be in the inventory or equipped
+    HasItem{id_string: String, count: i32},
+    Gold{amount: i32},
+    /// The quest has to be in the given state
+    Quest{id: String, state: QuestState},
+    /// The player flag has to have the value, or not be set if None
+    Flag{key: String, value: Option<ItemValue>},
+}
+
+impl Requirement {
+    pub fn is_met(&self, data: &PlayerData) -> bool {
+        match self {
+            Requirement::SkillLevel{skill, level} => data.effective_level(skill).is_some_and(|l| l >= *level),
+            Requirement::Equipped{id_string} => data.equipment.has_equipped(id_string),
+            Requirement::HasItem{id_string, count} => data.count_item(id_string) >= *count,
+            Requirement::Gold{amount} => data.gold >= *amount,
+            Requirement::Quest{id, state} => data.quest_state(id) == *state,
+            Requirement::Flag{key, value} => data.flag(key) == *value,
+        }
+    }
+
+    /// Message shown to the player when the requirement isn't met
+    pub fn failure_message(&self) -> String {
+        match self {
+            Requirement::SkillLevel{skill, level} => {
+                let name = skillregistry::with_def(skill, |def| def.name.clone()).unwrap_or(skill.clone());
+                format!("You need level {level} {name} to do that.")
+            },
+            Requirement::Equipped{id_string} => {
+                let name = itemregistry::with_def(id_string, |def| def.name.clone());
+                format!("You need to equip {name} to do that.")
+            },
+            Requirement::HasItem{id_string, count} => {
+                let name = itemregistry::with_def(id_string, |def| def.name.clone());
+                if *count == 1 {
+                    format!("You need {name} to do that.")
+                }
+                else {
+                    format!("You need {count} {name} to do that.")
+                }
+            },
+            Requirement::Gold{amount} => format!("You need {amount} gold to do that."),
+            Requirement::Quest{id, state} => {
+                let name = quest::get(id).map(|def| def.name).unwrap_or(id.clone());
+                match state {
+                    QuestState::NotStarted => format!("You've already started {name}."),
+                    QuestState::InProgress => format!("You need to be doing {name} to do that."),
+                    QuestState::Completed => format!("You need to complete {name} to do that."),
+                }
+            },
+            // Flags are internal to scripts, so there's nothing more specific to tell the player
+            Requirement::Flag{..} => "You can't do that right now.".to_string(),
+        }
+    }
+}
+
+/// Returns the first requirement the player doesn't meet, or None if all of them are met
+pub fn first_unmet<'a>(requirements: &'a [Requirement], data: &PlayerData) -> Option<&'a Requirement> {
+    requirements.iter().find(|requirement| !requirement.is_met(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playerdata::{item::Item, testutil::{axe, register_test_items, register_test_skills}};
+
+    #[test]
+    fn test_requirements() {
+        register_test_items();
+        register_test_skills();
+        let mut data = PlayerData::default();
+        data.items[0] = Some(axe());
+        data.items[1] = Some(Item::new("oak_wood", 3));
+        data.gold = 10;
+
+        let requirements = [
+            Requirement::SkillLevel{skill: "woodcutting".into(), level: 3},
+            Requirement::Equipped{id_string: "axe".into()},
+            Requirement::HasItem{id_string: "oak_wood".into(), count: 3},
+            Requirement::Gold{amount: 10},
+        ];
+        assert_eq!(first_unmet(&requirements, &data), Some(&requirements[0]));
+
+        // The equipped axe gives +2 woodcutting and still counts as held
+        assert!(data.equip_item(0));
+        assert_eq!(first_unmet(&requirements, &data), None);
+
+        data.gold = 9;
+        assert_eq!(first_unmet(&requirements, &data), Some(&requirements[3]));
+        assert!(!Requirement::SkillLevel{skill: "missing".into(), level: 1}.is_met(&data));
+        assert_eq!(requirements[0].failure_message(), "You need level 3 Woodcutting to do that.");
+    }
+}
@@ -0,0 +1,62 @@
+/// Small seedable SplitMix64 generator, so the same seed always gives the same loot drops and combat rolls
+#[derive(Clone, Debug)]
+pub struct SeededRng(u64);
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in [min, max], returns min if max is lower
+    pub fn range(&mut self, min: i32, max: i32) -> i32 {
+        if max <= min {
+            return min;
+        }
+        let span = (max as i64 - min as i64 + 1) as u64;
+        (min as i64 + (self.next_u64() % span) as i64) as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range() {
+        let mut rng = SeededRng::new(0);
+        assert_eq!(rng.range(5, 5), 5);
+        assert_eq!(rng.range(5, 1), 5);
+
+        let mut seen = [0; 5];
+        let (mut negative, mut positive) = (false, false);
+        for _ in 0..1000 {
+            let value = rng.range(-2, 2);
+            assert!((-2..=2).contains(&value));
+            seen[(value + 2) as usize] += 1;
+
+            // Ranges at the edges of i32 must neither overflow nor leave the bounds
+            assert!((i32::MAX - 1..=i32::MAX).contains(&rng.range(i32::MAX - 1, i32::MAX)));
+            assert!((i32::MIN..=i32::MIN + 1).contains(&rng.range(i32::MIN, i32::MIN + 1)));
+            let value = rng.range(i32::MIN, i32::MAX);
+            negative |= value < 0;
+            positive |= value > 0;
+
+            assert!((0. ..1.).contains(&rng.next_f64()));
+        }
+        assert!(seen.iter().all(|&count| count > 0), "range never hit some value: {seen:?}");
+        assert!(negative && positive, "full i32 range only hit one sign");
+    }
+}
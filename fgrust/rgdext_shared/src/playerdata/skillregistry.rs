@@ -0,0 +1,238 @@
+use godot::{classes::ConfigFile, prelude::*};
+
+
+registry_storage!(
+    /// Definitions of every skill known to this server or client, in the order they're displayed
+    skill_defs: Vec<SkillDef>
+);
+
+pub const DEFAULT_MAX_LEVEL: i32 = 100;
+pub const DEFAULT_XP_BASE: f64 = 100.;
+pub const DEFAULT_XP_EXPONENT: f64 = 2.;
+
+/// Display name, level cap and xp curve of a skill
+#[derive(Clone, Debug)]
+pub struct SkillDef {
+    pub id: String,
+    pub name: String,
+    pub max_level: i32,
+    /// xp_table[i] is the total xp needed to reach level i + 2
+    xp_table: Vec<i32>,
+}
+
+impl SkillDef {
+    /// Reaching level L + 1 takes a total of xp_base * L^xp_exponent xp
+    pub fn with_curve(id: impl ToString, name: impl ToString, max_level: i32, xp_base: f64, xp_exponent: f64) -> Self {
+        let xp_table = (1..max_level.max(1))
+            .map(|level| (xp_base * (level as f64).powf(xp_exponent)).round() as i32)
+            .collect();
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            max_level: max_level.max(1),
+            xp_table,
+        }
+    }
+
+    /// The table lists the total xp needed for levels 2, 3, ... so the max level is one more than its length.
+    ///
+    /// Returns None if the table isn't strictly increasing.
+    pub fn with_table(id: impl ToString, name: impl ToString, xp_table: Vec<i32>) -> Option<Self> {
+        if xp_table.windows(2).any(|w| w[0] >= w[1]) || xp_table.first().is_some_and(|xp| *xp <= 0) {
+            return None;
+        }
+        Some(Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            max_level: xp_table.len() as i32 + 1,
+            xp_table,
+        })
+    }
+
+    /// Total xp needed to reach the level, None if it's above the max level
+    pub fn xp_for_level(&self, level: i32) -> Option<i32> {
+        if level <= 1 {
+            return Some(0);
+        }
+        self.xp_table.get(level as usize - 2).copied()
+    }
+
+    /// Level reached with the given total xp
+    pub fn level_for_xp(&self, xp: i32) -> i32 {
+        1 + self.xp_table.iter().take_while(|required| xp >= **required).count() as i32
+    }
+}
+
+/// Adds the definition to the registry, replacing any previous definition with the same id in place
+pub fn register(def: SkillDef) {
+    let mut defs = skill_defs().write().unwrap();
+    match defs.iter_mut().find(|d| d.id == def.id) {
+        Some(existing) => *existing = def,
+        None => defs.push(def),
+    }
+}
+
+pub fn is_registered(id: &str) -> bool {
+    skill_defs().read().unwrap().iter().any(|def| def.id == id)
+}
+
+/// Runs the closure with the definition of the given skill, returns None if it isn't registered
+pub fn with_def<R>(id: &str, f: impl FnOnce(&SkillDef) -> R) -> Option<R> {
+    skill_defs().read().unwrap().iter().find(|def| def.id == id).map(f)
+}
+
+/// Ids of all registered skills in display order
+pub fn skill_ids() -> Vec<String> {
+    skill_defs().read().unwrap().iter().map(|def| def.id.clone()).collect()
+}
+
+/// Registers every section of the config file as a skill.
+///
+/// Keys of a section are name, max_level, xp_base and xp_exponent, or alternatively
+/// xp_table, an array with the total xp needed for levels 2, 3, ...
+///
+/// Returns the amount of skills registered.
+pub fn load_file(path: &str) -> i32 {
+    let mut config = ConfigFile::new_gd();
+    let err = config.load(path);
+    if err != godot::global::Error::OK {
+        godot_error!("Couldn't load skills from {}: {:?}", path, err);
+        return 0;
+    }
+
+    let mut loaded = 0;
+    for id in config.get_sections().as_slice() {
+        let get = |key: &str, default: Variant| config.get_value_ex(id, key).default(&default).done();
+        let name = get("name", id.to_variant()).to::<String>();
+
+        let def = if config.has_section_key(id, "xp_table") {
+            get("xp_table", Variant::nil()).try_to::<VariantArray>().ok()
+                .and_then(|table| table.iter_shared().map(|xp| xp.try_to_relaxed::<i32>().ok()).collect::<Option<Vec<i32>>>())
+                .and_then(|table| SkillDef::with_table(id, name, table))
+        }
+        else {
+            let max_level = get("max_level", DEFAULT_MAX_LEVEL.to_variant()).try_to_relaxed::<i32>().ok();
+            let xp_base = get("xp_base", DEFAULT_XP_BASE.to_variant()).try_to_relaxed::<f64>().ok();
+            let xp_exponent = get("xp_exponent", DEFAULT_XP_EXPONENT.to_variant()).try_to_relaxed::<f64>().ok();
+            match (max_level, xp_base, xp_exponent) {
+                (Some(max_level), Some(xp_base), Some(xp_exponent)) if max_level >= 1 && xp_base > 0. => {
+                    Some(SkillDef::with_curve(id, name, max_level, xp_base, xp_exponent))
+                },
+                _ => None,
+            }
+        };
+
+        match def {
+            Some(def) => {
+                register(def);
+                loaded += 1;
+            },
+            None => godot_error!("Invalid definition of skill {} in {}", id, path),
+        }
+    }
+    loaded
+}
+
+#[derive(GodotClass)]
+#[class(no_init, base=Object)]
+/// Registry of all skills, loaded at startup from a config file where every section is a skill.
+pub struct SkillRegistry {
+    base: Base<Object>
+}
+
+#[godot_api]
+impl SkillRegistry {
+    #[func]
+    /// Registers every section of the config file as a skill, returns the amount of skills registered.
+    fn load_file(path: GString) -> i32 {
+        load_file(&path.to_string())
+    }
+
+    #[func]
+    fn has_skill(id: GString) -> bool {
+        is_registered(&id.to_string())
+    }
+
+    #[func]
+    /// Skill ids in display order.
+    fn get_skill_ids() -> Array<GString> {
+        let mut arr = Array::new();
+        for id in skill_ids() {
+            arr.push(id.as_str());
+        }
+        arr
+    }
+
+    #[func]
+    fn get_display_name(id: GString) -> GString {
+        with_def(&id.to_string(), |def| GString::from(&def.name)).unwrap_or(id)
+    }
+
+    #[func]
+    /// Returns -1 if the skill doesn't exist.
+    fn get_max_level(id: GString) -> i32 {
+        with_def(&id.to_string(), |def| def.max_level).unwrap_or(-1)
+    }
+
+    #[func]
+    /// Total xp needed to reach the level, -1 if the skill doesn't exist or the level is above its max.
+    fn get_xp_for_level(id: GString, level: i32) -> i32 {
+        with_def(&id.to_string(), |def| def.xp_for_level(level))
+            .flatten()
+            .unwrap_or(-1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playerdata::{skills, testutil::register_test_skills, PlayerData};
+
+    #[test]
+    fn test_xp_curve() {
+        register_test_skills();
+        let mut data = PlayerData::default();
+        let levels = |l: Option<skills::LevelUp>| l.map(|l| (l.old_level, l.new_level));
+        assert_eq!(data.skills.level("woodcutting"), 1);
+        assert_eq!(data.add_xp("missing", 100), None);
+
+        // Same curve as the old hard-coded one, level L + 1 at L² * 100 total xp
+        assert_eq!(levels(data.add_xp("woodcutting", 99)), Some((1, 1)));
+        assert_eq!(levels(data.add_xp("woodcutting", 1)), Some((1, 2)));
+        assert_eq!(levels(data.add_xp("woodcutting", 800)), Some((2, 4)));
+        assert_eq!(data.skills.level("woodcutting"), 4);
+        assert_eq!(data.effective_level("woodcutting"), Some(4));
+
+        assert_eq!(levels(data.add_xp("woodcutting", i32::MAX)), Some((4, 100)));
+        assert_eq!(data.skills.level("woodcutting"), 100);
+        assert_eq!(data.skill_progress.xp("woodcutting"), i32::MAX);
+        assert_eq!(levels(data.add_xp("woodcutting", 1)), Some((100, 100)));
+    }
+
+    #[test]
+    fn test_xp_table() {
+        register_test_skills();
+        let mut data = PlayerData::default();
+        assert_eq!(data.add_xp("fishing", 29).unwrap().new_level, 2);
+        assert_eq!(data.add_xp("fishing", 1000).unwrap().new_level, 4);
+        assert_eq!(data.skills.level("fishing"), 4);
+        assert!(!data.add_xp("fishing", -2000).unwrap().leveled_up());
+        assert_eq!(data.skill_progress.xp("fishing"), 0);
+        assert_eq!(data.skills.level("fishing"), 4);
+
+        assert!(SkillDef::with_table("bad", "Bad", vec![10, 10]).is_none());
+    }
+
+    #[test]
+    fn test_skills_survive_new_skills() {
+        register_test_skills();
+        let mut data = PlayerData::default();
+        data.add_xp("fishing", 30);
+        let decoded = PlayerData::from_bytes(&data.to_bytes()).unwrap();
+
+        register(SkillDef::with_curve("cooking", "Cooking", 50, 50., 1.5));
+        assert_eq!(decoded.skills.level("fishing"), 3);
+        assert_eq!(decoded.skills.level("cooking"), 1);
+        assert_eq!(decoded.skill_progress.xp("cooking"), 0);
+    }
+}
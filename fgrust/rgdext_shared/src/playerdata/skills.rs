@@ -1,97 +1,83 @@
+use std::collections::BTreeMap;
+
 use bitcode::{Decode, Encode};
-// use serde::{Serialize, Deserialize};
 
 
-// #[derive(Decode, Encode)]
-#[derive(Clone, Copy)]
-#[repr(usize)]
-pub enum Skill {
-    Woodcutting,
-    Mining,
-    Smelting,
-    Crafting,
-    Farming,
-    Strength,
-    Agility,
-    Endurance,
-    Magic,
-    Ranged
+/// Skill levels keyed by skill id, see [super::skillregistry] for which skills exist.
+///
+/// Skills without an entry are at level 1, so adding a skill doesn't invalidate stored data,
+/// and entries of removed skills are kept around in case they come back.
+#[derive(Clone, Default, Encode, Decode, Debug)]
+pub struct Skills {
+    levels: BTreeMap<String, i32>,
 }
-const NUM_SKILLS: usize = 10;
 
-impl Skill {
-    pub fn try_from_str(s: &str) -> Option<Skill> {
-        match s {
-            "woodcutting" => Some(Skill::Woodcutting),
-            "mining" => Some(Skill::Mining),
-            "smelting" => Some(Skill::Smelting),
-            "crafting" => Some(Skill::Crafting),
-            "farming" => Some(Skill::Farming),
-            "strength" => Some(Skill::Strength),
-            "agility" => Some(Skill::Agility),
-            "endurance" => Some(Skill::Endurance),
-            "magic" => Some(Skill::Magic),
-            "ranged" => Some(Skill::Ranged),
-            _ => None,
-        }
+impl Skills {
+    pub fn level(&self, skill: &str) -> i32 {
+        self.levels.get(skill).copied().unwrap_or(1)
     }
 
-    pub fn skill_strs() -> [&'static str; NUM_SKILLS] {
-        [
-            "woodcutting",
-            "mining",
-            "smelting",
-            "crafting",
-            "farming",
-            "strength",
-            "agility",
-            "endurance",
-            "magic",
-            "ranged",
-        ]
+    pub fn set_level(&mut self, skill: &str, level: i32) {
+        self.levels.insert(skill.to_string(), level);
     }
 }
 
-#[derive(Clone, Encode, Decode, Debug)]
-pub struct Skills {
-    skills: [u8; NUM_SKILLS],
+/// Result of giving xp to a skill
+#[derive(Clone, PartialEq, Debug)]
+pub struct LevelUp {
+    pub skill: String,
+    pub old_level: i32,
+    pub new_level: i32,
 }
 
-impl std::ops::Index<Skill> for Skills {
-    type Output = u8;
-
-    fn index(&self, index: Skill) -> &u8 {
-        &self.skills[index as usize]
+impl LevelUp {
+    pub fn leveled_up(&self) -> bool {
+        self.new_level > self.old_level
     }
-}
 
-impl std::ops::IndexMut<Skill> for Skills {
-    fn index_mut(&mut self, index: Skill) -> &mut u8 {
-        &mut self.skills[index as usize]
+    pub fn levels_gained(&self) -> i32 {
+        self.new_level - self.old_level
     }
-}
 
-impl Default for Skills {
-    fn default() -> Self {
-        Self{skills: [1; NUM_SKILLS]}
+    /// Every level that was reached, in order
+    pub fn levels_crossed(&self) -> std::ops::RangeInclusive<i32> {
+        self.old_level + 1..=self.new_level
     }
 }
 
+/// Total xp gained in each skill, keyed by skill id. Skills without an entry have 0 xp.
 #[derive(Clone, Default, Encode, Decode, Debug)]
 pub struct SkillProgress {
-    skill_progress: [i32; NUM_SKILLS],
+    xp: BTreeMap<String, i32>,
 }
 
-impl std::ops::Index<Skill> for SkillProgress {
-    type Output = i32;
+impl SkillProgress {
+    pub fn xp(&self, skill: &str) -> i32 {
+        self.xp.get(skill).copied().unwrap_or(0)
+    }
 
-    fn index(&self, index: Skill) -> &i32 {
-        &self.skill_progress[index as usize]
+    pub fn set_xp(&mut self, skill: &str, xp: i32) {
+        self.xp.insert(skill.to_string(), xp);
     }
 }
 
-impl std::ops::IndexMut<Skill> for SkillProgress {
-    fn index_mut(&mut self, index: Skill) -> &mut i32 {
-        &mut self.skill_progress[index as usize]
+#[cfg(test)]
+mod tests {
+    use crate::playerdata::{testutil::register_test_skills, PlayerData};
+
+    #[test]
+    fn test_multi_level_gain() {
+        register_test_skills();
+        let mut data = PlayerData::default();
+        let level_up = data.add_xp("woodcutting", 900).unwrap();
+        assert!(level_up.leveled_up());
+        assert_eq!(level_up.levels_gained(), 3);
+        assert_eq!(level_up.levels_crossed().collect::<Vec<_>>(), vec![2, 3, 4]);
+
+        // Each gain only reports the levels it crossed itself
+        let level_up = data.add_xp("woodcutting", 700).unwrap();
+        assert_eq!(level_up.levels_crossed().collect::<Vec<_>>(), vec![5]);
+        let level_up = data.add_xp("woodcutting", 1).unwrap();
+        assert_eq!(level_up.levels_crossed().count(), 0);
     }
-}
\ No newline at end of file
+}
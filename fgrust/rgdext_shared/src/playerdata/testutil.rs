@@ -0,0 +1,28 @@
+//! Definitions shared by the tests of the player data modules
+use super::{equipment::EquipSlot, item::Item, itemregistry, skillregistry};
+
+
+pub(super) fn axe() -> Item {
+    Item::new("axe", 1)
+}
+
+pub(super) fn register_test_items() {
+    let def = |id_string: &str, stackable, equip_slot, stat_bonuses: &[(&str, i32)]| itemregistry::ItemDef {
+        id_string: id_string.to_string(),
+        name: id_string.to_string(),
+        description: String::new(),
+        stackable,
+        equip_slot,
+        stat_bonuses: stat_bonuses.iter().map(|(s, b)| (s.to_string(), *b)).collect(),
+    };
+    itemregistry::register(def("oak_wood", true, None, &[]));
+    itemregistry::register(def("fir_wood", true, None, &[]));
+    itemregistry::register(def("fairy", true, None, &[]));
+    itemregistry::register(def("axe", false, Some(EquipSlot::MainHand), &[("woodcutting", 2)]));
+    itemregistry::register(def("axe_super", false, Some(EquipSlot::MainHand), &[]));
+}
+
+pub(super) fn register_test_skills() {
+    skillregistry::register(skillregistry::SkillDef::with_curve("woodcutting", "Woodcutting", 100, 100., 2.));
+    skillregistry::register(skillregistry::SkillDef::with_table("fishing", "Fishing", vec![10, 30, 60]).unwrap());
+}
@@ -9,10 +9,7 @@ enum ServerType {
 
 impl ServerType {
     fn is_client(&self) -> bool {
-        match self {
-            ServerType::Client{..} => true,
-            _ => false,
-        }
+        matches!(self, ServerType::Client{..})
     }
 }
 
@@ -234,16 +231,12 @@ impl ServerConnector {
                             godot_error!("Error completing auth: {:?}", err)
                         }
                     }
-                    else {
-                        if self.log_level >= 2 {
-                            godot_print!("Token invalid for {} {}", self.target_name, net_id);
-                        }
+                    else if self.log_level >= 2 {
+                        godot_print!("Token invalid for {} {}", self.target_name, net_id);
                     }
                 }
-                else {
-                    if self.log_level >= 1 {
-                        godot_error!("Somehow token unset while auth enabled!");
-                    }
+                else if self.log_level >= 1 {
+                    godot_error!("Somehow token unset while auth enabled!");
                 }
             },
             ServerType::None => {}
@@ -3,6 +3,9 @@ extends ServerConnector
 signal retrieved(pid: int, data: PackedByteArray)
 signal request_save(pid: int)
 signal dm_received(text: String, from: String, from_pid: int, target_pid: int)
+signal connected
+## Maps instance keys to the var_to_bytes encoded persistent states of their entities
+signal entity_states_retrieved(states: Dictionary)
 
 func _ready() -> void:
 	var config := ConfigFile.new()
@@ -18,6 +21,10 @@ func _ready() -> void:
 	set_client(port, ip)
 	
 	start_server()
+	multiplayer.peer_connected.connect(_on_peer_connected)
+
+func _on_peer_connected(_net_id: int) -> void:
+	connected.emit()
 
 func relay_dm(text: String, from: String, from_pid: int, target_pid: int) -> void:
 	rpc_id(1, "_relay_dm", text, from, from_pid, target_pid)
@@ -29,6 +36,13 @@ func save(pid: int, data: PackedByteArray, unlock: bool) -> void:
 func retrieve(pid: int, lock: bool) -> void:
 	rpc_id(1, "_retrieve", pid, lock)
 
+func save_entity_states(key: String, data: PackedByteArray) -> void:
+	rpc_id(1, "_save_entity_states", key, data)
+
+## Retrieves the states of all instances whose keys start with the prefix
+func retrieve_entity_states(prefix: String) -> void:
+	rpc_id(1, "_retrieve_entity_states", prefix)
+
 @rpc("any_peer", "call_remote", "reliable", 0)
 func _save(pid: int) -> void:
 	pass
@@ -39,6 +53,15 @@ func _retrieve(pid: int, data: PackedByteArray) -> void:
 	print("Retrieved data from db for pid ", pid)
 	retrieved.emit(pid, data)
 
+@rpc("any_peer", "call_remote", "reliable", 0)
+func _save_entity_states(key: String, data: PackedByteArray) -> void:
+	pass
+
+@rpc("any_peer", "call_remote", "reliable", 0)
+func _retrieve_entity_states(states: Dictionary) -> void:
+	print("Retrieved persistent entity states of %s instances from db" % states.size())
+	entity_states_retrieved.emit(states)
+
 @rpc("any_peer", "call_remote", "reliable", 1)
 func _relay_dm(text: String, from: String, from_pid: int, target_pid: int) -> void:
 	dm_received.emit(text, from, from_pid, target_pid)
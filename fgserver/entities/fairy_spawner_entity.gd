@@ -7,6 +7,16 @@ var fairy: GenericScriptedEntity = null
 var timer: int = 0
 var respawn_cooldown: int = 0
 
+func _ready() -> void:
+	timer = persistent_state.get("timer", 0)
+	respawn_cooldown = persistent_state.get("respawn_cooldown", 0)
+
+func _on_unload() -> void:
+	# A living fairy is lost with the instance, so it respawns on the usual cooldown
+	if fairy != null:
+		timer = 0
+	persistent_state = {"timer": timer, "respawn_cooldown": respawn_cooldown}
+
 func _process(delta: float) -> void:
 	if fairy == null:
 		timer += 1
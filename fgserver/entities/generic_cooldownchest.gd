@@ -12,7 +12,9 @@ var loot_ready: bool = true
 func _ready() -> void:
 	interactable = true
 	related_scene = "chest"
-	public_data["open"] = false
+	loot_ready = persistent_state.get("loot_ready", true)
+	time = persistent_state.get("time", 0.)
+	public_data["open"] = not loot_ready
 
 func _process(delta: float) -> void:
 	if not loot_ready:
@@ -22,6 +24,9 @@ func _process(delta: float) -> void:
 			loot_ready = true
 			set_public_value("open", false)
 
+func _on_unload() -> void:
+	persistent_state = {"loot_ready": loot_ready, "time": time}
+
 func _on_player_interaction(player: PlayerContainer, net_id: int) -> Array[ScriptResponse]:
 	if loot_ready:
 		loot_ready = false
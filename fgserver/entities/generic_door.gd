@@ -0,0 +1,25 @@
+class_name GenericDoor
+
+extends GenericScriptedEntity
+
+## Doors start closed, so place them on a collision tile so that clients predict it too
+var open: bool = false
+
+func _ready() -> void:
+	interactable = true
+	related_scene = "door"
+	public_data["open"] = false
+
+func _on_player_interaction(player: PlayerContainer, net_id: int) -> Array[ScriptResponse]:
+	return _toggle()
+
+## Levers toggle the doors with their target tag
+func _on_message(from_entity_id: int, message: Variant) -> Array[ScriptResponse]:
+	if message is String and message == "toggle":
+		return _toggle()
+	return []
+
+func _toggle() -> Array[ScriptResponse]:
+	open = not open
+	set_public_value("open", open)
+	return [ScriptResponse.set_collision(pos.x, pos.y, not open)]
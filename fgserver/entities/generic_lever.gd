@@ -0,0 +1,19 @@
+class_name GenericLever
+
+extends GenericScriptedEntity
+
+## Doors and other entities with this tag get a "toggle" message when the lever is pulled
+@export var target_tag: String = ""
+var pulled: bool = false
+
+func _ready() -> void:
+	interactable = true
+	related_scene = "lever"
+	public_data["pulled"] = false
+
+func _on_player_interaction(player: PlayerContainer, net_id: int) -> Array[ScriptResponse]:
+	pulled = not pulled
+	set_public_value("pulled", pulled)
+	if target_tag.is_empty():
+		return []
+	return [ScriptResponse.send_message_to_tag(target_tag, "toggle")]
@@ -3,6 +3,8 @@ class_name GenericTree
 extends GenericScriptedEntity
 
 const cooldown: float = 20.
+## How many ticks cutting down the tree takes
+const cut_ticks: int = 30
 var oak_wood: ItemResource = load("res://items/oak_wood.tres")
 var fir_wood: ItemResource = load("res://items/fir_wood.tres")
 
@@ -14,8 +16,10 @@ var cut_down: bool = false
 func _ready() -> void:
 	interactable = true
 	related_scene = "tree"
+	cut_down = persistent_state.get("cut_down", false)
+	time = persistent_state.get("time", 0.)
 	public_data["kind"] = kind
-	public_data["cut"] = false
+	public_data["cut"] = cut_down
 
 func _process(delta: float) -> void:
 	if cut_down:
@@ -25,31 +29,48 @@ func _process(delta: float) -> void:
 			cut_down = false
 			set_public_value("cut", false)
 
+func _on_unload() -> void:
+	persistent_state = {"cut_down": cut_down, "time": time}
+
 func _on_player_interaction(player: PlayerContainer, net_id: int) -> Array[ScriptResponse]:
 	if cut_down:
 		return []
+	var failure := _check_can_cut(player, net_id)
+	if not failure.is_empty():
+		return failure
+	return [ScriptResponse.start_action(cut_ticks, [], net_id)]
+
+func _on_action_completed(player: PlayerContainer, net_id: int) -> Array[ScriptResponse]:
+	# Someone else may have cut the tree down in the meantime
+	if cut_down:
+		return []
+	var failure := _check_can_cut(player, net_id)
+	if not failure.is_empty():
+		return failure
+	
+	cut_down = true
+	set_public_value("cut", true)
+	var required_power := _required_power()
+	var wood_resource: ItemResource = oak_wood if kind == "oak" else fir_wood
+	return [
+		ScriptResponse.give_item(wood_resource, net_id),
+		ScriptResponse.give_xp("woodcutting", required_power * 20, net_id)
+	]
+
+func _required_power() -> int:
+	return 1 if kind == "oak" else 2
+
+## Returns a failure message response if the player can't cut down this tree, empty array otherwise
+func _check_can_cut(player: PlayerContainer, net_id: int) -> Array[ScriptResponse]:
 	var eq_item := player.get_equipped_item()
-	if eq_item == null or eq_item.custom_data["tool"] != "axe":
+	if eq_item == null or eq_item.get_custom_string("tool", "") != "axe":
 		return [ScriptResponse.chat_message("You need to equip an axe to cut down a tree.", net_id)]
-	var woodcutting := player.get_stat("woodcutting")
-	var axe_power: int = eq_item.custom_data["power"]
-	var required_power: int
-	var wood_resource: ItemResource
-	if kind == "oak":
-		required_power = 1
-		wood_resource = oak_wood
-	elif kind == "fir":
-		required_power = 2
-		wood_resource = fir_wood
+	var woodcutting := player.get_effective_stat("woodcutting")
+	var axe_power := eq_item.get_custom_int("power", 0)
+	var required_power := _required_power()
 	
-	if axe_power >= required_power:
-		if woodcutting >= required_power:
-			cut_down = true
-			set_public_value("cut", true)
-			
-			return [
-				ScriptResponse.give_item(wood_resource, net_id),
-				ScriptResponse.give_xp("woodcutting", required_power * 20, net_id)
-			]
+	if axe_power < required_power:
+		return [ScriptResponse.chat_message("Your axe is too weak to cut down this tree.", net_id)]
+	if woodcutting < required_power:
 		return [ScriptResponse.chat_message("Your woodcutting level is too low to cut down this tree.", net_id)]
-	return [ScriptResponse.chat_message("Your axe is too weak to cut down this tree.", net_id)]
+	return []
@@ -4,6 +4,7 @@ extends GenericScriptedEntity
 
 func _ready() -> void:
 	related_scene = "grindstone"
+	station = "grindstone"
 	interactable = true
 
 func _on_player_interaction(player: PlayerContainer, net_id: int) -> Array[ScriptResponse]:
@@ -11,20 +12,17 @@ func _on_player_interaction(player: PlayerContainer, net_id: int) -> Array[Scrip
 	if equipped_item == null:
 		return [ScriptResponse.chat_message("You have no equipped tool.", net_id)]
 	
-	var tool_type = equipped_item.custom_data.get("tool")
-	if tool_type == null:
+	if not equipped_item.has_custom("tool"):
 		return [ScriptResponse.chat_message("Your equipped item is not a tool.", net_id)]
 	
-	var power = equipped_item.custom_data.get("power")
-	var sharpened = equipped_item.custom_data.get("sharpened")
-	if power == null:
+	if not equipped_item.has_custom("power"):
 		return [] # This one shouldn't happen assuming all tools have a power field
-	if sharpened != null:
+	if equipped_item.get_custom_bool("sharpened", false):
 		return [ScriptResponse.chat_message("Your equipped tool is already sharpened.", net_id)]
 	
-	equipped_item.custom_data.set("sharpened", 1)
+	var power := equipped_item.get_custom_int("power", 0)
+	equipped_item.custom_data.set("sharpened", true)
 	equipped_item.custom_data.set("power", power + 1)
-	equipped_item.name += " [+1]"
 	
 	return [
 		ScriptResponse.take_item(equipped_item.id_string, equipped_item.count, net_id),